@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use quick_xml::escape::escape;
+
+use crate::helpers::map_text;
+
+// pull a post's own `*[HTML]: HyperText Markup Language` definition lines out of its body
+pub fn take_definitions(body: &str) -> (String, BTreeMap<String, String>) {
+    let mut definitions = BTreeMap::new();
+    let mut rest = String::with_capacity(body.len());
+    for line in body.split_inclusive('\n') {
+        let definition = line
+            .strip_prefix("*[")
+            .and_then(|l| l.split_once("]:"))
+            .filter(|(abbr, _title)| !abbr.trim().is_empty());
+        match definition {
+            Some((abbr, title)) => {
+                definitions.insert(abbr.trim().to_string(), title.trim().to_string());
+            }
+            None => rest.push_str(line),
+        }
+    }
+    (rest, definitions)
+}
+
+// wrap whole word uses of each abbreviation in the text of rendered html with <abbr title>
+pub fn wrap(html: &str, abbreviations: &BTreeMap<String, String>) -> String {
+    if abbreviations.is_empty() {
+        return html.to_string();
+    }
+    // longest first, so "HTML5" wins over "HTML"
+    let mut abbrs: Vec<(&String, &String)> = abbreviations.iter().collect();
+    abbrs.sort_by_key(|(abbr, _title)| std::cmp::Reverse(abbr.len()));
+
+    map_text(html, |text| wrap_text(text, &abbrs))
+}
+
+fn wrap_text(text: &str, abbrs: &[(&String, &String)]) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        let at_word_start = !text[..i]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric);
+        let found = abbrs.iter().find(|(abbr, _title)| {
+            at_word_start
+                && text[i..].starts_with(abbr.as_str())
+                && !text[i + abbr.len()..]
+                    .chars()
+                    .next()
+                    .is_some_and(char::is_alphanumeric)
+        });
+        match found {
+            Some((abbr, title)) => {
+                output.push_str(&format!(
+                    "<abbr title=\"{}\">{}</abbr>",
+                    escape(title.as_str()),
+                    abbr
+                ));
+                i += abbr.len();
+            }
+            None => {
+                let c = text[i..].chars().next().unwrap_or_default();
+                output.push(c);
+                i += c.len_utf8();
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_whole_words_outside_code() {
+        let (body, abbrs) = take_definitions("Use HTML.\n\n*[HTML]: HyperText \"Markup\"\n");
+        assert_eq!(body, "Use HTML.\n\n");
+        let html = "<p>HTML and HTMLX, <code>HTML</code> and <a href=\"/HTML\">HTML</a> é HTML</p>";
+        assert_eq!(
+            wrap(html, &abbrs),
+            "<p><abbr title=\"HyperText &quot;Markup&quot;\">HTML</abbr> and HTMLX, \
+             <code>HTML</code> and <a href=\"/HTML\">HTML</a> é \
+             <abbr title=\"HyperText &quot;Markup&quot;\">HTML</abbr></p>"
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use color_eyre::Result;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderErrorReason,
+};
+
+use crate::helpers::content_hash;
+
+// how much of the content hash goes into a fingerprinted file name
+const FINGERPRINT_LEN: usize = 8;
+
+#[derive(Debug, Default)]
+pub struct Assets {
+    pub copied: usize,
+    // each file's path under the static dir and the path it was written to under dest
+    pub paths: BTreeMap<String, String>,
+}
+
+// copy everything under src into dest keeping its layout, skipping files dest already has
+// an up to date copy of; fingerprinted files get a content hash in their name. a dry run
+// counts and maps the files without copying anything
+pub fn copy_dir(src: &Path, dest: &Path, fingerprint: bool, dry_run: bool) -> Result<Assets> {
+    let mut assets = Assets::default();
+    copy_into(src, dest, "", fingerprint, dry_run, &mut assets)?;
+    Ok(assets)
+}
+
+fn copy_into(
+    src: &Path,
+    dest: &Path,
+    prefix: &str,
+    fingerprint: bool,
+    dry_run: bool,
+    assets: &mut Assets,
+) -> Result<()> {
+    if !dry_run {
+        fs::DirBuilder::new().recursive(true).create(dest)?;
+    }
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_dir() {
+            let prefix = format!("{}{}/", prefix, name);
+            copy_into(
+                &entry.path(),
+                &dest.join(&name),
+                &prefix,
+                fingerprint,
+                dry_run,
+                assets,
+            )?;
+            continue;
+        }
+
+        let output_name = match fingerprint {
+            true => fingerprinted(&name, &content_hash(&fs::read(entry.path())?)),
+            false => name.clone(),
+        };
+        let target = dest.join(&output_name);
+        if is_stale(&entry.path(), &target) {
+            if !dry_run {
+                fs::copy(entry.path(), &target)?;
+            }
+            assets.copied += 1;
+        }
+        assets.paths.insert(
+            format!("{}{}", prefix, name),
+            format!("{}{}", prefix, output_name),
+        );
+    }
+    Ok(())
+}
+
+// style.css becomes style.<hash>.css
+fn fingerprinted(name: &str, hash: &str) -> String {
+    let hash = &hash[..FINGERPRINT_LEN];
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{}.{}.{}", stem, hash, ext),
+        _ => format!("{}.{}", name, hash),
+    }
+}
+
+fn is_stale(src: &Path, target: &Path) -> bool {
+    let (src, target) = match (fs::metadata(src), fs::metadata(target)) {
+        (Ok(s), Ok(t)) => (s, t),
+        _ => return true,
+    };
+    match (src.modified(), target.modified()) {
+        (Ok(s), Ok(t)) => s > t || src.len() != target.len(),
+        _ => true,
+    }
+}
+
+// `{{asset "css/style.css"}}` is the path that file from the static dir was written to
+#[derive(Debug, Default)]
+pub struct AssetHelper {
+    pub paths: BTreeMap<String, String>,
+}
+
+impl HelperDef for AssetHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _r: &'reg Handlebars<'reg>,
+        _ctx: &'rc Context,
+        _rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let name = h
+            .param(0)
+            .and_then(|p| p.value().as_str())
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("asset", 0))?;
+        let path = self
+            .paths
+            .get(name.trim_start_matches('/'))
+            .ok_or_else(|| RenderErrorReason::Other(format!("no static file named {}", name)))?;
+        out.write(path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_nested_files_once() -> Result<()> {
+        let dest = std::env::temp_dir().join(format!("site-gen-assets-{}", std::process::id()));
+        assert_eq!(
+            copy_dir(Path::new("fixtures"), &dest, false, true)?.copied,
+            5
+        );
+        assert!(!dest.exists());
+        assert_eq!(
+            copy_dir(Path::new("fixtures"), &dest, false, false)?.copied,
+            5
+        );
+        assert!(dest.join("data/file1.md").is_file());
+        let assets = copy_dir(Path::new("fixtures"), &dest, false, false)?;
+        assert_eq!(assets.copied, 0);
+        assert_eq!(assets.paths["data/file1.md"], "data/file1.md");
+        fs::remove_dir_all(dest)?;
+        Ok(())
+    }
+
+    #[test]
+    fn fingerprints_names_with_their_content() -> Result<()> {
+        assert_eq!(
+            fingerprinted("style.css", "0123456789abcdef"),
+            "style.01234567.css"
+        );
+        assert_eq!(
+            fingerprinted(".htaccess", "0123456789abcdef"),
+            ".htaccess.01234567"
+        );
+
+        let mut hbs = Handlebars::new();
+        let paths = BTreeMap::from([("style.css".to_string(), "style.01234567.css".to_string())]);
+        hbs.register_helper("asset", Box::new(AssetHelper { paths }));
+        hbs.register_template_string("page", "{{asset \"/style.css\"}}")?;
+        hbs.register_template_string("missing", "{{asset \"gone.css\"}}")?;
+        assert_eq!(hbs.render("page", &())?, "style.01234567.css");
+        assert!(hbs.render("missing", &()).is_err());
+        Ok(())
+    }
+}
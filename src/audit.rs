@@ -0,0 +1,266 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use color_eyre::Result;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::prettify::{attribute, tag_end, tag_name};
+
+const FONT_EXTENSIONS: [&str; 5] = ["woff2", "woff", "ttf", "otf", "eot"];
+
+// <link> rels a browser fetches on its own; stylesheets are counted separately
+const FETCHED_RELS: [&str; 8] = [
+    "icon",
+    "apple-touch-icon",
+    "manifest",
+    "preconnect",
+    "dns-prefetch",
+    "preload",
+    "prefetch",
+    "modulepreload",
+];
+
+// what a page loads from somewhere
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Script,
+    Stylesheet,
+    Font,
+    Image,
+    Media,
+    Frame,
+    Other,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Script => "script",
+            Kind::Stylesheet => "stylesheet",
+            Kind::Font => "font",
+            Kind::Image => "image",
+            Kind::Media => "media",
+            Kind::Frame => "frame",
+            Kind::Other => "other",
+        }
+    }
+}
+
+// the hosts other than the site's own that pages and stylesheets in dest load from, with what
+// they load and which files load it, relative to dest
+pub type Audit = BTreeMap<String, BTreeMap<Kind, BTreeSet<PathBuf>>>;
+
+pub fn audit(dest: &Path, site_host: &str) -> Result<Audit> {
+    let mut files = vec![];
+    collect(dest, Path::new(""), &mut files)?;
+
+    let mut found = Audit::new();
+    for file in files {
+        let text = fs::read_to_string(dest.join(&file))?;
+        let references = match file.extension().is_some_and(|e| e == "css") {
+            true => css_references(&text),
+            false => html_references(&text),
+        };
+        for (kind, url) in references {
+            match third_party_host(&url, site_host) {
+                Some(host) => found
+                    .entry(host)
+                    .or_default()
+                    .entry(kind)
+                    .or_default()
+                    .insert(file.clone()),
+                None => continue,
+            };
+        }
+    }
+    Ok(found)
+}
+
+fn collect(dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir.join(relative))? {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            collect(dir, &path, files)?;
+            continue;
+        }
+        let is_page = path
+            .extension()
+            .is_some_and(|e| e == "html" || e == "htm" || e == "css");
+        if is_page {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+// relative urls, inline data and the site's own host aren't third parties
+fn third_party_host(url: &str, site_host: &str) -> Option<String> {
+    let url = match url.starts_with("//") {
+        true => url::Url::parse(&format!("https:{}", url)).ok()?,
+        false => url::Url::parse(url).ok()?,
+    };
+    let host = url.host_str()?.to_ascii_lowercase();
+    (host != site_host.to_ascii_lowercase()).then_some(host)
+}
+
+// everything a browser fetches while loading the page; links aren't followed, and <template>s
+// (like held back embeds) aren't loaded until a script asks
+pub fn html_references(html: &str) -> Vec<(Kind, String)> {
+    let mut found = vec![];
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let tag = &rest[..tag_end(rest)];
+        rest = &rest[tag.len()..];
+        let name = tag_name(tag);
+        let mut add = |kind, value: Option<&str>| {
+            if let Some(value) = value {
+                found.push((kind, value.to_string()));
+            }
+        };
+        match name.as_str() {
+            "template" | "script" | "style" => {
+                let close = rest.find(&format!("</{}", name)).unwrap_or(rest.len());
+                match name.as_str() {
+                    "script" => add(Kind::Script, attribute(tag, "src")),
+                    "style" => found.extend(css_references(&rest[..close])),
+                    _ => (),
+                }
+                rest = &rest[close..];
+            }
+            "link" => {
+                let rel = attribute(tag, "rel")
+                    .unwrap_or_default()
+                    .to_ascii_lowercase();
+                let rels: Vec<_> = rel.split_whitespace().collect();
+                if rels.contains(&"stylesheet") {
+                    add(Kind::Stylesheet, attribute(tag, "href"));
+                } else if attribute(tag, "as") == Some("font") {
+                    add(Kind::Font, attribute(tag, "href"));
+                } else if rels.iter().any(|r| FETCHED_RELS.contains(r)) {
+                    add(Kind::Other, attribute(tag, "href"));
+                }
+            }
+            "img" | "source" | "input" => {
+                let kind = match name.as_str() {
+                    "source" => Kind::Media,
+                    _ => Kind::Image,
+                };
+                add(kind, attribute(tag, "src"));
+                for candidate in attribute(tag, "srcset").unwrap_or_default().split(',') {
+                    add(Kind::Image, candidate.split_whitespace().next());
+                }
+            }
+            "video" | "audio" | "track" => {
+                add(Kind::Media, attribute(tag, "src"));
+                add(Kind::Image, attribute(tag, "poster"));
+            }
+            "iframe" | "frame" | "embed" => add(Kind::Frame, attribute(tag, "src")),
+            "object" => add(Kind::Frame, attribute(tag, "data")),
+            _ => (),
+        }
+        if let Some(style) = attribute(tag, "style") {
+            found.extend(css_references(style));
+        }
+    }
+    found
+}
+
+// the url()s and @imports in a stylesheet, fonts told apart by their extension
+pub fn css_references(css: &str) -> Vec<(Kind, String)> {
+    let mut found = vec![];
+    for (start, _) in css.match_indices("url(") {
+        let value = &css[start + 4..];
+        let value = value[..value.find(')').unwrap_or(value.len())]
+            .trim()
+            .trim_matches(|c| c == '"' || c == '\'');
+        let path = value.split(['?', '#']).next().unwrap_or_default();
+        let is_font = path.rsplit_once('.').is_some_and(|(_name, ext)| {
+            FONT_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str())
+        });
+        let is_import = css[..start].trim_end().ends_with("@import");
+        let kind = match (is_import, is_font) {
+            (true, _) => Kind::Stylesheet,
+            (false, true) => Kind::Font,
+            (false, false) => Kind::Image,
+        };
+        found.push((kind, value.to_string()));
+    }
+    for (start, _) in css.match_indices("@import") {
+        let value = css[start + 7..].trim_start();
+        if let Some(q @ ('"' | '\'')) = value.chars().next() {
+            if let Some(url) = value[1..].split(q).next() {
+                found.push((Kind::Stylesheet, url.to_string()));
+            }
+        }
+    }
+    found
+}
+
+// one host per line followed by what's loaded from it and where, or json for scripts
+pub fn report(found: &Audit, as_json: bool) -> Result<String> {
+    if as_json {
+        let hosts: Vec<_> = found
+            .iter()
+            .map(|(host, kinds)| json!({ "host": host, "loads": kinds }))
+            .collect();
+        return Ok(serde_json::to_string_pretty(&hosts)?);
+    }
+    if found.is_empty() {
+        return Ok("Nothing is loaded from third parties".to_string());
+    }
+
+    let mut lines = vec![];
+    for (host, kinds) in found {
+        lines.push(host.to_string());
+        for (kind, files) in kinds {
+            let files: Vec<_> = files.iter().map(|f| f.to_string_lossy()).collect();
+            lines.push(format!("  {} in {}", kind.name(), files.join(", ")));
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_what_pages_load_but_not_links_or_templates() {
+        let html = "<link rel=\"stylesheet\" href=\"https://fonts.googleapis.com/css\">\
+            <link rel=\"canonical\" href=\"https://example.com/a.html\">\
+            <script src=\"//cdn.example.net/a.js\"></script><script>var s = '<img src=x>';</script>\
+            <img src=\"/local.png\" srcset=\"https://i.example.org/a.jpg 2x\">\
+            <a href=\"https://elsewhere.com\">link</a><p style=\"background: url('https://bg.com/a.png')\">\
+            <template><iframe src=\"https://www.youtube.com/embed/a\"></iframe></template>\
+            <style>@import \"https://s.com/a.css\"; @font-face { src: url(https://f.com/a.woff2) }</style>";
+        assert_eq!(
+            html_references(html),
+            vec![
+                (
+                    Kind::Stylesheet,
+                    "https://fonts.googleapis.com/css".to_string()
+                ),
+                (Kind::Script, "//cdn.example.net/a.js".to_string()),
+                (Kind::Image, "/local.png".to_string()),
+                (Kind::Image, "https://i.example.org/a.jpg".to_string()),
+                (Kind::Image, "https://bg.com/a.png".to_string()),
+                (Kind::Font, "https://f.com/a.woff2".to_string()),
+                (Kind::Stylesheet, "https://s.com/a.css".to_string()),
+            ]
+        );
+        assert_eq!(
+            third_party_host("//cdn.example.net/a.js", "example.com"),
+            Some("cdn.example.net".to_string())
+        );
+        assert_eq!(
+            third_party_host("https://Example.com/a", "example.com"),
+            None
+        );
+        assert_eq!(third_party_host("/local.png", "example.com"), None);
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use slug::slugify;
+
+use crate::helpers::page_url;
+
+// one `[authors.<key>]` entry, e.g. `[authors.todd] name = "Todd"`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthorConfig {
+    pub name: String,
+    pub bio: Option<String>,
+    /// Image shown beside the author's name
+    pub avatar: Option<String>,
+    /// The author's own site
+    pub url: Option<String>,
+}
+
+/// A post's author: the `[authors]` entry its front matter names, or just a name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Author {
+    /// The key into `[authors]`, when the author is listed there
+    pub key: Option<String>,
+    pub name: String,
+    pub bio: Option<String>,
+    pub avatar: Option<String>,
+    pub url: Option<String>,
+    /// The author's archive, relative to dest, when the templates have an author page
+    pub page: Option<String>,
+}
+
+// authors/todd.html, then authors/todd1.html for the second page of posts
+pub fn prefix(key: &str) -> String {
+    format!("authors/{}", slugify(key))
+}
+
+// an author from a post's front matter; anything that isn't a key is taken as a name
+pub fn resolve(value: &str, authors: &BTreeMap<String, AuthorConfig>, pages: bool) -> Author {
+    match authors.get(value) {
+        Some(config) => Author {
+            key: Some(value.to_string()),
+            name: config.name.clone(),
+            bio: config.bio.clone(),
+            avatar: config.avatar.clone(),
+            url: config.url.clone(),
+            page: pages.then(|| page_url(&prefix(value), 0)),
+        },
+        None => Author {
+            key: None,
+            name: value.to_string(),
+            bio: None,
+            avatar: None,
+            url: None,
+            page: None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_keys_and_falls_back_to_names() {
+        let authors: BTreeMap<String, AuthorConfig> =
+            toml::from_str("[todd]\nname = \"Todd Kennedy\"\nbio = \"Writes things\"").unwrap();
+        let todd = resolve("todd", &authors, true);
+        assert_eq!(todd.name, "Todd Kennedy");
+        assert_eq!(todd.bio.as_deref(), Some("Writes things"));
+        assert_eq!(todd.page.as_deref(), Some("authors/todd.html"));
+        assert_eq!(resolve("todd", &authors, false).page, None);
+
+        let guest = resolve("A Guest", &authors, true);
+        assert_eq!(
+            (guest.key, guest.name, guest.page),
+            (None, "A Guest".to_string(), None)
+        );
+    }
+}
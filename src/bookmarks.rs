@@ -0,0 +1,65 @@
+use std::fs;
+use std::path::Path;
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::parse_date;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub title: String,
+    pub url: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub date: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BookmarkFile {
+    #[serde(default)]
+    bookmark: Vec<Bookmark>,
+}
+
+// read every [[bookmark]] from the data file, newest first
+pub fn load(path: &Path) -> Result<Vec<Bookmark>> {
+    parse(&fs::read_to_string(path)?)
+}
+
+fn parse(data: &str) -> Result<Vec<Bookmark>> {
+    let mut bookmarks = toml::from_str::<BookmarkFile>(data)?.bookmark;
+    // undated bookmarks keep their file order after the dated ones
+    bookmarks.sort_by_key(|b| std::cmp::Reverse(b.date.as_deref().map(parse_date)));
+    Ok(bookmarks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorts_newest_first() -> Result<()> {
+        let data = r#"
+            [[bookmark]]
+            title = "old"
+            url = "https://example.com/old"
+            date = "2023-01-01T00:00:00Z"
+
+            [[bookmark]]
+            title = "undated"
+            url = "https://example.com/undated"
+
+            [[bookmark]]
+            title = "new"
+            url = "https://example.com/new"
+            tags = ["rust"]
+            date = "2024-01-01T00:00:00Z"
+        "#;
+        let bookmarks = parse(data)?;
+        let titles: Vec<_> = bookmarks.iter().map(|b| b.title.as_str()).collect();
+        assert_eq!(titles, vec!["new", "old", "undated"]);
+        assert_eq!(bookmarks[0].tags, vec!["rust"]);
+        Ok(())
+    }
+}
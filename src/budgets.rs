@@ -0,0 +1,111 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::{css_references, html_references, Kind};
+use crate::helpers::dest_file;
+
+// the `[budgets]` table; a build over one warns, or fails with --strict
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BudgetConfig {
+    /// The most bytes of html any one page may be
+    pub max_html_bytes: Option<u64>,
+    /// The most bytes a page may load from the site: its html plus the stylesheets, scripts,
+    /// fonts, images and media it references, and what those stylesheets load
+    pub max_weight_bytes: Option<u64>,
+}
+
+// a page, relative to dest, that's bigger than a budget allows
+#[derive(Debug, PartialEq)]
+pub struct Overage {
+    pub page: PathBuf,
+    pub budget: &'static str,
+    pub size: u64,
+    pub limit: u64,
+}
+
+impl fmt::Display for Overage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is over the {} budget: {} bytes of {}",
+            self.page.display(),
+            self.budget,
+            self.size,
+            self.limit
+        )
+    }
+}
+
+// every page under dest over one of the budgets
+pub fn check(dest: &Path, site_url: &str, config: &BudgetConfig) -> Result<Vec<Overage>> {
+    let mut pages = vec![];
+    collect(dest, Path::new(""), &mut pages)?;
+
+    let mut over = vec![];
+    for page in pages {
+        let html = fs::read_to_string(dest.join(&page))?;
+        let size = html.len() as u64;
+        if let Some(limit) = config.max_html_bytes.filter(|limit| size > *limit) {
+            over.push(Overage {
+                page: page.clone(),
+                budget: "html",
+                size,
+                limit,
+            });
+        }
+        if let Some(limit) = config.max_weight_bytes {
+            let size = size + assets_weight(dest, &page, &html, site_url);
+            if size > limit {
+                over.push(Overage {
+                    page,
+                    budget: "weight",
+                    size,
+                    limit,
+                });
+            }
+        }
+    }
+    Ok(over)
+}
+
+fn collect(dir: &Path, relative: &Path, pages: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir.join(relative))? {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            collect(dir, &path, pages)?;
+        } else if path.extension().is_some_and(|e| e == "html" || e == "htm") {
+            pages.push(path);
+        }
+    }
+    Ok(())
+}
+
+// the size of each local file the page loads, counted once however often it's referenced
+fn assets_weight(dest: &Path, page: &Path, html: &str, site_url: &str) -> u64 {
+    let mut loaded = BTreeSet::new();
+    for (kind, url) in html_references(html) {
+        let file = match dest_file(page, &url, site_url) {
+            Some(file) => file,
+            None => continue,
+        };
+        if kind == Kind::Stylesheet {
+            let css = fs::read_to_string(dest.join(&file)).unwrap_or_default();
+            for (_kind, url) in css_references(&css) {
+                loaded.extend(dest_file(&file, &url, site_url));
+            }
+        }
+        loaded.insert(file);
+    }
+    loaded
+        .iter()
+        .filter_map(|file| fs::metadata(dest.join(file)).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
@@ -0,0 +1,148 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use color_eyre::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::helpers::content_hash;
+
+// where the cache used to live, inside dest; it's removed so it's never published
+pub const LEGACY_CACHE_FILE: &str = ".site-gen-cache.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedEntry {
+    hash: String,
+    entry: Value,
+}
+
+// what the previous build parsed and rendered, so unchanged posts and pages can be skipped.
+// it holds whole posts, so it's kept in the cache dir rather than anywhere that's deployed
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BuildCache {
+    // parsed entries keyed by source file
+    #[serde(default)]
+    entries: BTreeMap<String, CachedEntry>,
+
+    // the context hash each output file was last rendered from
+    #[serde(default)]
+    pages: BTreeMap<String, String>,
+
+    // what this build looked up, everything else is dropped on save
+    #[serde(skip)]
+    used_entries: BTreeSet<String>,
+    #[serde(skip)]
+    used_pages: BTreeSet<String>,
+}
+
+impl BuildCache {
+    pub fn load(cache_dir: &Path, dest: &Path) -> BuildCache {
+        match fs::read_to_string(cache_path(cache_dir, dest)) {
+            Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+                warn!("Unable to read build cache, rebuilding everything: {}", e);
+                BuildCache::default()
+            }),
+            Err(_e) => BuildCache::default(),
+        }
+    }
+
+    pub fn save(mut self, cache_dir: &Path, dest: &Path) -> Result<()> {
+        self.entries
+            .retain(|source, _e| self.used_entries.contains(source));
+        self.pages
+            .retain(|output, _h| self.used_pages.contains(output));
+        fs::DirBuilder::new().recursive(true).create(cache_dir)?;
+        fs::write(cache_path(cache_dir, dest), serde_json::to_string(&self)?)?;
+        let legacy = dest.join(LEGACY_CACHE_FILE);
+        if legacy.is_file() {
+            fs::remove_file(legacy)?;
+        }
+        Ok(())
+    }
+
+    // the entry parsed from source last time, if the source hasn't changed since
+    pub fn entry(&mut self, source: &str, hash: &str) -> Option<Value> {
+        self.used_entries.insert(source.to_string());
+        self.entries
+            .get(source)
+            .filter(|cached| cached.hash == hash)
+            .map(|cached| cached.entry.clone())
+    }
+
+    pub fn store_entry(&mut self, source: &str, hash: String, entry: Value) {
+        self.used_entries.insert(source.to_string());
+        self.entries
+            .insert(source.to_string(), CachedEntry { hash, entry });
+    }
+
+    // whether output is still on disk from a render of the same context
+    pub fn page_unchanged(&mut self, output: &Path, hash: &str) -> bool {
+        let key = output.to_string_lossy().to_string();
+        self.used_pages.insert(key.clone());
+        output.is_file() && self.pages.get(&key).map(String::as_str) == Some(hash)
+    }
+
+    pub fn store_page(&mut self, output: &Path, hash: String) {
+        let key = output.to_string_lossy().to_string();
+        self.used_pages.insert(key.clone());
+        self.pages.insert(key, hash);
+    }
+}
+
+// one per dest, so sites sharing a cache dir don't share a build cache
+pub fn cache_path(cache_dir: &Path, dest: &Path) -> PathBuf {
+    let key = content_hash(dest.to_string_lossy().as_bytes());
+    cache_dir.join(format!("build-{}.json", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn entries_are_reused_only_while_their_hash_matches() {
+        let mut cache = BuildCache::default();
+        cache.store_entry("post.md", "a".to_string(), json!({"title": "Post"}));
+        assert_eq!(cache.entry("post.md", "a"), Some(json!({"title": "Post"})));
+        assert_eq!(cache.entry("post.md", "b"), None);
+        assert_eq!(cache.entry("other.md", "a"), None);
+    }
+
+    #[test]
+    fn pages_need_their_output_to_exist() {
+        let mut cache = BuildCache::default();
+        let exists = Path::new("fixtures/data/file1.md");
+        let missing = Path::new("fixtures/data/missing.html");
+        cache.store_page(exists, "a".to_string());
+        cache.store_page(missing, "a".to_string());
+        assert!(cache.page_unchanged(exists, "a"));
+        assert!(!cache.page_unchanged(exists, "b"));
+        assert!(!cache.page_unchanged(missing, "a"));
+    }
+
+    #[test]
+    fn saves_outside_dest() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("site-gen-build-cache-{}", std::process::id()));
+        let (cache_dir, dest) = (dir.join("cache"), dir.join("public"));
+        fs::create_dir_all(&dest)?;
+        fs::write(dest.join(LEGACY_CACHE_FILE), "{}")?;
+
+        let mut cache = BuildCache::default();
+        cache.store_entry("post.md", "a".to_string(), json!({"title": "Post"}));
+        cache.save(&cache_dir, &dest)?;
+        assert!(cache_path(&cache_dir, &dest).is_file());
+        assert!(!dest.join(LEGACY_CACHE_FILE).exists());
+        assert_eq!(fs::read_dir(&dest)?.count(), 0);
+
+        let mut loaded = BuildCache::load(&cache_dir, &dest);
+        assert!(loaded.entry("post.md", "a").is_some());
+        assert!(BuildCache::load(&cache_dir, &dir.join("other"))
+            .entries
+            .is_empty());
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -1,31 +1,143 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
-use chrono::{DateTime, FixedOffset, Local};
+use chrono::{DateTime, Datelike, FixedOffset, Local, SecondsFormat};
 use color_eyre::Result;
-use comrak::{markdown_to_html, ComrakOptions};
-use handlebars::Handlebars;
+use comrak::{format_html, parse_document, Arena};
+use handlebars::{Handlebars, RenderError};
+use log::{debug, error, info, warn};
+use quick_xml::escape::escape;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use slug::slugify;
 use thiserror::Error;
 use truncate_string_at_whitespace::truncate_text;
 use voca_rs::strip::strip_tags;
 
-use crate::helpers::{get_entries, parse_date};
+use crate::abbreviations;
+use crate::assets::{self, AssetHelper};
+use crate::authors::{self, Author};
+use crate::bookmarks;
+use crate::budgets;
+use crate::build_cache::BuildCache;
+use crate::calendar;
+use crate::citations::{self, Reference};
+use crate::comments::{self, Comment, DEFAULT_COMMENTS_DIR};
+use crate::critical;
+use crate::digest::bucket_key;
+use crate::embeds::{self, Embed};
+use crate::feed::{Feed, FeedEntry};
+use crate::fonts::{self, FontFacesHelper};
+use crate::front_matter::{self, Source};
+use crate::generator::{self, Generator, GENERATOR};
+use crate::helpers::{
+    content_hash, content_hash_parts, get_entries, get_partials, get_posts, page_url, pagination,
+    parallel_map, parse_date, permalink, within, without_nulls, PageLinks,
+};
+use crate::highlight::Highlighter;
+use crate::i18n::{self, LinkHelper, Translation};
+use crate::images::{self, SrcsetHelper};
+use crate::links;
+use crate::manifest::{ChangeKind, Manifest};
+use crate::minify::minify;
+use crate::planet::{self, PlanetItem};
+use crate::prettify::prettify;
+use crate::prune;
+use crate::query::{LandingPage, PostsHelper};
+use crate::remote;
+use crate::render_cache::CachedPartial;
+use crate::search;
+use crate::sections::SectionConfig;
+use crate::sidenotes::sidenotes;
+use crate::sitemap::{self, SitemapUrl};
+use crate::sites::Peer;
+use crate::slug_history::{self, Hops, SlugHistory};
+use crate::styles;
+use crate::toc::{self, TocEntry};
+use crate::typography;
+use crate::webmentions::{self, Mentions};
+use crate::writer::OutputWriter;
 use crate::Opt;
 
-#[derive(Debug)]
-struct FileEntry {
-    modified: DateTime<FixedOffset>,
-    raw_text: String,
-    contents: String,
-    tags: Vec<String>,
-    title: String,
-    url: String,
-    hero_image: Option<String>,
-    share_image: Option<String>,
-    description: Option<String>,
+// a post as the builder renders it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileEntry {
+    /// The post's front matter id, which stays the same when it's renamed
+    pub id: Option<String>,
+    /// The markdown file the post was parsed from
+    #[serde(default)]
+    pub source: PathBuf,
+    /// The directory under src the post is in, if it isn't at the top
+    pub section: Option<String>,
+    /// When the post was published, or last modified without a date
+    pub modified: DateTime<FixedOffset>,
+    /// The post's text without markup, for summaries and feeds
+    pub raw_text: String,
+    #[serde(default)]
+    pub word_count: usize,
+    /// The word count at the configured words_per_minute, rounded up
+    #[serde(default)]
+    pub reading_time_minutes: usize,
+    /// The rendered html
+    pub contents: String,
+    /// The post's headings, nested, when a template shows them or heading_ids is on under
+    /// [markdown]
+    #[serde(default)]
+    pub toc: Vec<TocEntry>,
+    /// The rendered html above a `<!--more-->` marker, for index pages and feeds
+    #[serde(default)]
+    pub summary: Option<String>,
+    pub tags: Vec<String>,
+    pub title: String,
+    /// Where the post is written, relative to dest
+    pub url: String,
+    pub hero_image: Option<String>,
+    pub share_image: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub author: Option<Author>,
+    pub lang: Option<String>,
+    pub dir: Option<String>,
+    pub content_warning: Option<String>,
+    pub license: Option<String>,
+    /// Sites from the config's [sites] table that also publish the post
+    pub syndicate: Vec<String>,
+    /// Copies of the post posted elsewhere, linked as u-syndication
+    #[serde(default)]
+    pub syndication: Vec<String>,
+    /// Where the post really lives when another site published it first
+    pub canonical: Option<String>,
+    pub draft: bool,
+    /// Up to five other posts sharing the most tags with this one, filled in once every
+    /// post is parsed
+    #[serde(default)]
+    pub related: Vec<RelatedPost>,
+    /// The same post in the site's other languages, filled in once every post is parsed
+    #[serde(default)]
+    pub translations: Vec<Translation>,
+}
+
+/// A post listed as related to another, for a "you might also like" section.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelatedPost {
+    pub title: String,
+    pub url: String,
+    pub description: Option<String>,
+    /// How many tags the two posts have in common
+    pub shared_tags: usize,
+}
+
+impl FileEntry {
+    // the url without its extension, used to key per-post data files
+    fn slug(&self) -> &str {
+        let url = self.url.trim_end_matches('/');
+        url.strip_suffix(".html").unwrap_or(url)
+    }
 }
 
 #[derive(Debug)]
@@ -33,7 +145,129 @@ pub struct Builder<'blog> {
     opts: Opt,
     files: Vec<PathBuf>,
     entries: Vec<FileEntry>,
+    // kept out of the site, but listed in the calendar
+    drafts: Vec<FileEntry>,
     hbs: Handlebars<'blog>,
+    data: Value,
+    comments: BTreeMap<String, Vec<Comment>>,
+    webmentions: BTreeMap<String, Mentions>,
+    planet: Vec<PlanetItem>,
+    feeds: Vec<Value>,
+    // the feeds pages under <lang>/ advertise, their language's own first
+    language_feeds: BTreeMap<String, Vec<Value>>,
+    // a summary of every post, newest first, for the posts helper
+    post_index: Arc<Vec<Value>>,
+    site_name: Option<String>,
+    peers: Vec<Peer>,
+    references: BTreeMap<String, Reference>,
+    references_hash: String,
+    highlighter: Option<Highlighter>,
+    render_errors: Mutex<Vec<String>>,
+    writer: OutputWriter,
+    cache: Mutex<BuildCache>,
+    // every file this build wrote or found already up to date, relative to dest
+    outputs: Mutex<BTreeSet<String>>,
+    template_hash: String,
+    // whether a template shows {{toc}}, which needs ids on headings to link to
+    uses_toc: bool,
+    build: BuildInfo,
+    unchanged_pages: AtomicUsize,
+    // posts and site data are loaded, by a build or the first render_file
+    loaded: bool,
+    // the asset helper knows where static files go, by copying them or a preview's dry run
+    assets_known: bool,
+    // compiled and copied css keyed by its path under dest, to inline critical rules from
+    stylesheets: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct IndexEntry<'a> {
+    title: &'a str,
+    contents: &'a str,
+    summary: Option<&'a str>,
+    author: Option<&'a Author>,
+    tags: &'a [String],
+    url: &'a str,
+    modified: String,
+    hero_image: Option<&'a str>,
+    lang: Option<&'a str>,
+    content_warning: Option<&'a str>,
+    site_url: Option<&'a str>,
+    word_count: usize,
+    reading_time_minutes: usize,
+}
+
+impl<'a> IndexEntry<'a> {
+    fn new(entry: &'a FileEntry, site_url: Option<&'a str>) -> IndexEntry<'a> {
+        IndexEntry {
+            title: &entry.title,
+            contents: &entry.contents,
+            summary: entry.summary.as_deref(),
+            author: entry.author.as_ref(),
+            tags: &entry.tags,
+            url: &entry.url,
+            modified: entry.modified.format(DATE_FORMAT).to_string(),
+            hero_image: entry.hero_image.as_deref(),
+            lang: entry.lang.as_deref(),
+            content_warning: entry.content_warning.as_deref(),
+            site_url,
+            word_count: entry.word_count,
+            reading_time_minutes: entry.reading_time_minutes,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct LandingPageData<'a> {
+    title: &'a str,
+    description: Option<&'a str>,
+    site_url: Option<&'a str>,
+    share_image: Option<&'a str>,
+    canonical: String,
+    year: String,
+    #[serde(flatten)]
+    posts: BTreeMap<&'a str, Vec<Value>>,
+}
+
+#[derive(Serialize)]
+struct IndexPage<'a> {
+    title: &'a str,
+    contents: Vec<IndexEntry<'a>>,
+    pagination: &'a [Value],
+    year: String,
+    pub_date: String,
+    description: Option<&'a str>,
+    site_url: Option<&'a str>,
+    share_image: Option<&'a str>,
+    // set on the index of one of the site's other languages
+    lang: Option<&'a str>,
+    #[serde(flatten)]
+    links: PageLinks,
+}
+
+// what a template renders: the page's own data plus site-wide data borrowed from the
+// builder, so large remote data isn't copied once per page before rendering
+#[derive(Serialize)]
+struct PageContext<'a, T: Serialize> {
+    #[serde(flatten)]
+    page: &'a T,
+    feeds: &'a [Value],
+    data: &'a Value,
+    site_lang: &'a str,
+    site_dir: &'a str,
+    build: &'a BuildInfo,
+    generator: &'a Generator,
+}
+
+// what built the site, as `build` in every template: {{build.commit}}, {{build.version}}...
+#[derive(Debug, Serialize)]
+struct BuildInfo {
+    // the commit src is at, when it's in a git repo
+    commit: Option<String>,
+    // volatile, so a new build alone doesn't rewrite every page
+    time_stamp: String,
+    version: &'static str,
+    profile: String,
 }
 
 #[derive(Debug, Error)]
@@ -46,33 +280,197 @@ enum BuilderError {
 
     #[error("URL had no host")]
     BadURL,
+
+    #[error("No template named {0} in the template dir")]
+    MissingTemplate(String),
+
+    #[cfg(not(feature = "scripting"))]
+    #[error("Can't use the {0} script helper, site-gen was built without the scripting feature")]
+    NoScripting(String),
+
+    #[error("Rendering {page} to {output:?} failed: {location}{reason}")]
+    Render {
+        page: String,
+        output: Box<PathBuf>,
+        location: String,
+        reason: String,
+    },
+
+    #[error("{} page(s) failed to render:\n{}", .0.len(), .0.join("\n"))]
+    RenderErrors(Vec<String>),
+
+    #[error("{} page(s) are over budget:\n{}", .0.len(), .0.join("\n"))]
+    OverBudget(Vec<String>),
+
+    #[error("{} broken link(s):\n{}", .0.len(), .0.join("\n"))]
+    BrokenLinks(Vec<String>),
+
+    #[error("{0:?} isn't a file inside the src dir")]
+    OutsideSrc(Box<PathBuf>),
+
+    #[error(
+        "{first:?} and {second:?} would both be written to {url}, rename one or use nested_output"
+    )]
+    DuplicateUrl {
+        url: String,
+        first: Box<PathBuf>,
+        second: Box<PathBuf>,
+    },
+
+    #[error("The tags {0:?} and {1:?} would both be written to {2}")]
+    DuplicateTagUrl(String, String, String),
+}
+
+impl BuilderError {
+    fn render(page: &str, output: &Path, e: RenderError) -> BuilderError {
+        let template = e.template_name.as_deref().unwrap_or("unnamed template");
+        let location = match (e.line_no, e.column_no) {
+            (Some(line), Some(column)) => {
+                format!("{} line {}, column {}: ", template, line, column)
+            }
+            _ => format!("{}: ", template),
+        };
+        BuilderError::Render {
+            page: page.to_string(),
+            output: Box::new(output.to_owned()),
+            location,
+            reason: e.reason().to_string(),
+        }
+    }
 }
 
-const HEADER_DELIMITER: &str = "---";
 const DATE_FORMAT: &str = "%A, %b %e, %Y";
 
+// context that changes every build without the page content changing
+const VOLATILE_KEYS: [&str; 4] = ["pub_date", "time_stamp", "tag_date", "year"];
+
+fn without_volatile(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(key, _value)| !VOLATILE_KEYS.contains(&key.as_str()))
+                .map(|(key, value)| (key.clone(), without_volatile(value)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(without_volatile).collect()),
+        _ => value.clone(),
+    }
+}
+
+// where a tag's own page lives, relative to the site root
+fn tag_url(tag: &str) -> String {
+    page_url(&tag_prefix(tag), 0)
+}
+
+// archive/2024, or archive/2024/03 for a month
+fn archive_prefix(year: i32, month: Option<u32>) -> String {
+    match month {
+        Some(month) => format!("archive/{}/{:02}", year, month),
+        None => format!("archive/{}", year),
+    }
+}
+
+// where the first page of a year's or month's archive lives
+fn archive_url(year: i32, month: Option<u32>) -> String {
+    page_url(&archive_prefix(year, month), 0)
+}
+
+// "March 2024"
+fn month_name(year: i32, month: u32) -> String {
+    match chrono::NaiveDate::from_ymd_opt(year, month, 1) {
+        Some(date) => date.format("%B %Y").to_string(),
+        None => format!("{}-{:02}", year, month),
+    }
+}
+
+// long tags are paginated like the index: tags/rust.html, tags/rust1.html...
+fn tag_prefix(tag: &str) -> String {
+    format!("tags/{}", slugify(tag))
+}
+
+// for pages when neither the post nor the config says otherwise
+const DEFAULT_LANG: &str = "en";
+const DEFAULT_DIR: &str = "ltr";
+const DEFAULT_PROFILE: &str = "production";
+
+// where --debug-context writes the data a page was rendered with
+const DEBUG_CONTEXT_FILE: &str = "debug-context.json";
+
+const RELATED_POSTS: usize = 5;
+
+// everything above it in a post is the summary index pages and feeds show
+const MORE_MARKER: &str = "<!--more-->";
+
+// where a page came from, so a live page can be traced back to what built it
+fn source_comment(source: Option<&Path>, template: &str, build: &BuildInfo) -> String {
+    let mut comment = match source {
+        Some(path) => format!("source: {}, ", path.display()),
+        None => String::new(),
+    };
+    comment.push_str(&format!(
+        "template: {}, built: {}",
+        template, build.time_stamp
+    ));
+    if let Some(commit) = &build.commit {
+        comment.push_str(&format!(", commit: {}", commit));
+    }
+    // a comment can't contain --
+    format!("\n<!-- {} -->\n", comment.replace("--", "- -"))
+}
+
+// the commit src is checked out at, None outside a repo
+fn source_commit(src: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .current_dir(src)
+        .output()
+        .ok()?;
+    let commit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !commit.is_empty()).then_some(commit)
+}
+
+#[cfg(feature = "scripting")]
+fn register_script(hbs: &mut Handlebars, name: &str, script: &Path) -> Result<()> {
+    hbs.register_script_helper_file(name, script)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "scripting"))]
+fn register_script(_hbs: &mut Handlebars, name: &str, _script: &Path) -> Result<()> {
+    Err(BuilderError::NoScripting(name.to_string()).into())
+}
+
 impl<'blog> Builder<'blog> {
+    /// Load the templates, helpers and post list for a site. Nothing is rendered until
+    /// [`Builder::build`].
     pub fn new(opts: Opt) -> Result<Builder<'blog>> {
-        let dest = &opts
-            .dest
-            .clone()
-            .ok_or(BuilderError::MissingValue("dest".to_string()))?;
-        fs::DirBuilder::new().recursive(true).create(dest)?;
+        if !opts.dry_run {
+            fs::DirBuilder::new().recursive(true).create(&opts.dest)?;
+        }
 
-        let src = &opts
-            .src
-            .clone()
-            .ok_or(BuilderError::MissingValue("src".to_string()))?;
-        let src = PathBuf::from(src);
-        let files = get_entries(&src).unwrap_or_default();
+        let src = PathBuf::from(&opts.src);
+        let files = get_posts(&src).unwrap_or_default();
 
         let mut hbs = Handlebars::new();
+        hbs.register_helper("cached", Box::new(CachedPartial::default()));
+        hbs.register_helper("asset", Box::new(AssetHelper::default()));
+        hbs.register_helper("srcset", Box::new(SrcsetHelper::default()));
+        hbs.register_helper("font_faces", Box::new(FontFacesHelper::default()));
+        hbs.register_helper("link", Box::new(LinkHelper::new(opts.url.as_deref())));
         let tmpl_src = PathBuf::from(&opts.template_dir);
         let templates = get_entries(&tmpl_src).unwrap_or_default();
+        let mut template_sources = String::new();
 
         for tpl_path in templates.iter() {
+            // _header.hbs is a partial, not a page of its own
+            if tpl_path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('_'))
+            {
+                continue;
+            }
             if let Some(filename) = tpl_path.to_str() {
-                let name = match tpl_path.iter().last() {
+                let name = match tpl_path.iter().next_back() {
                     Some(u) => match u.to_str() {
                         Some(u) => u.split('.').next().unwrap(),
                         None => filename,
@@ -80,284 +478,2259 @@ impl<'blog> Builder<'blog> {
                     None => filename,
                 };
                 hbs.register_template_file(name, tpl_path)?;
+                template_sources.push_str(name);
+                template_sources.push_str(&fs::read_to_string(tpl_path)?);
             }
         }
 
+        for (name, path) in get_partials(&tmpl_src)? {
+            let source = fs::read_to_string(&path)?;
+            hbs.register_partial(&name, &source)?;
+            template_sources.push_str(&name);
+            template_sources.push_str(&source);
+        }
+
+        // helpers/shout.rhai is {{shout ...}}
+        let scripts = get_entries(Path::new(&opts.helper_dir)).unwrap_or_default();
+        for script in scripts
+            .iter()
+            .filter(|p| p.extension().is_some_and(|e| e == "rhai"))
+        {
+            let name = script.file_stem().unwrap_or_default().to_string_lossy();
+            register_script(&mut hbs, &name, script)?;
+            template_sources.push_str(&name);
+            template_sources.push_str(&fs::read_to_string(script)?);
+        }
+
+        // pages written before an html option was switched on or off aren't up to date
+        if opts.prettify {
+            template_sources.push_str("prettify");
+        }
+        if opts.minify {
+            template_sources.push_str("minify");
+        }
+        if opts.generator_meta {
+            template_sources.push_str("generator");
+        }
+        if let Some(critical) = &opts.critical_css {
+            template_sources.push_str(&format!("critical {}", critical.fold));
+        }
+        let build = BuildInfo {
+            commit: source_commit(Path::new(&opts.src)),
+            time_stamp: Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+            version: env!("CARGO_PKG_VERSION"),
+            profile: opts.profile.clone().unwrap_or(DEFAULT_PROFILE.to_string()),
+        };
+        if opts.source_comments {
+            template_sources.push_str("source comments");
+        }
+
+        let cache = match opts.force {
+            true => BuildCache::default(),
+            false => BuildCache::load(
+                &remote::cache_dir(opts.cache_dir.as_deref()),
+                Path::new(&opts.dest),
+            ),
+        };
+
         Ok(Builder {
             opts,
             files,
             entries: vec![],
+            drafts: vec![],
             hbs,
+            data: json!({}),
+            comments: BTreeMap::new(),
+            webmentions: BTreeMap::new(),
+            planet: vec![],
+            feeds: vec![],
+            language_feeds: BTreeMap::new(),
+            post_index: Arc::new(vec![]),
+            site_name: None,
+            peers: vec![],
+            references: BTreeMap::new(),
+            references_hash: String::new(),
+            highlighter: None,
+            render_errors: Mutex::new(vec![]),
+            writer: OutputWriter::new(),
+            cache: Mutex::new(cache),
+            outputs: Mutex::new(BTreeSet::new()),
+            template_hash: content_hash(template_sources.as_bytes()),
+            uses_toc: toc::used_by(&template_sources),
+            build,
+            unchanged_pages: AtomicUsize::new(0),
+            loaded: false,
+            assets_known: false,
+            stylesheets: BTreeMap::new(),
         })
     }
 
+    /// Every post parsed by [`Builder::build`], newest first.
+    pub fn pages(&self) -> &[FileEntry] {
+        &self.entries
+    }
+
+    // pull in posts from other sites in the workspace that ask to be syndicated here
+    pub(crate) fn syndicate_from(&mut self, site_name: String, peers: Vec<Peer>) {
+        self.site_name = Some(site_name);
+        self.peers = peers;
+    }
+
+    /// Render every post and listing into dest, skipping pages that haven't changed since
+    /// the last build.
     pub fn build(&mut self) -> Result<()> {
-        for file in self.files.iter() {
-            let entry = self.parse_entry(file)?;
-            self.entries.push(entry);
+        self.load()?;
+        self.copy_static(self.opts.dry_run)?;
+        self.compile_styles()?;
+        self.resize_images()?;
+        self.subset_fonts()?;
+        self.hold_back_embeds()?;
+        self.build_blog()?;
+        self.writer.finish()?;
+        self.prune_styles()?;
+        self.check_budgets()?;
+        self.check_links()?;
+        let unchanged_pages = self.unchanged_pages.load(Ordering::SeqCst);
+        if unchanged_pages > 0 {
+            info!("Skipped {} unchanged pages", unchanged_pages);
+        }
+        let outputs = std::mem::take(self.outputs.get_mut().unwrap());
+        let mut manifest = Manifest::load(Path::new(&self.opts.dest));
+        if self.opts.dry_run {
+            for file in manifest.outputs.difference(&outputs) {
+                info!("Would stop building {} (clean --stale removes it)", file);
+            }
+        } else {
+            std::mem::take(self.cache.get_mut().unwrap()).save(
+                &remote::cache_dir(self.opts.cache_dir.as_deref()),
+                Path::new(&self.opts.dest),
+            )?;
+            manifest.record_outputs(outputs);
+            manifest.save(Path::new(&self.opts.dest))?;
         }
 
-        self.entries.sort_by(|a, b| {
-            let bd = b.modified.signed_duration_since(a.modified);
-            let ad = a.modified.signed_duration_since(b.modified);
-            bd.cmp(&ad)
-        });
+        let render_errors = std::mem::take(self.render_errors.get_mut().unwrap());
+        if !render_errors.is_empty() {
+            return Err(BuilderError::RenderErrors(render_errors).into());
+        }
+        Ok(())
+    }
 
-        self.build_blog()
+    /// Render one markdown file to html with the site's templates, posts and data, exactly
+    /// as a build would publish it, without writing anything. Drafts render too, so editors
+    /// can preview them.
+    pub fn render_file(&mut self, path: &Path) -> Result<String> {
+        let entry = self.page(path, None)?;
+        self.render_entry(&entry)
     }
 
-    fn build_blog(&self) -> Result<()> {
-        let mut count = 0;
-        let num_per_page = self.opts.entries;
+    /// [`Builder::render_file`] for text that may not be saved yet, rendered as if it were
+    /// the contents of path.
+    pub fn render_text(&mut self, path: &Path, text: &str) -> Result<String> {
+        let entry = self.page(path, Some(text))?;
+        self.render_entry(&entry)
+    }
 
-        // create a list of all the indexes we're gonna output
-        let mut pagination: Vec<_> = vec![];
-        let num_entries = self.entries.len() as u8;
-        if num_entries > num_per_page {
-            let mut num_pages = num_entries / num_per_page;
-            if num_entries % num_per_page > 0 {
-                num_pages += 1;
-            }
-            for index in 0..num_pages {
-                pagination.push(match index {
-                    0 => json!({
-                        "name": "home",
-                        "url": "index.html",
-                    }),
-                    _ => json!({
-                        "name": format!("page {}", index),
-                        "url": format!("index{}.html", index),
-                    }),
-                });
-            }
+    /// Parse one markdown file, or text standing in for it, the way a build would, with
+    /// its related posts filled in.
+    pub fn page(&mut self, path: &Path, text: Option<&str>) -> Result<FileEntry> {
+        self.load()?;
+        // only to learn where each asset would be copied
+        if !self.assets_known {
+            self.copy_static(true)?;
         }
+        let mut entry = match text {
+            Some(text) => self.parse_source(path, front_matter::parse_source(text)?)?,
+            None => self.parse_entry(path)?,
+        };
+        entry.related = self.related_posts(&entry);
+        entry.translations = self.translations(&entry);
+        Ok(entry)
+    }
 
-        // generate the pages
-        let now = Local::now();
-        let mut rss_data: Vec<_> = vec![];
-        let mut tag_map: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    fn render_entry(&self, entry: &FileEntry) -> Result<String> {
+        let (template, post_data) = self.entry_page(entry)?;
+        let context = self.page_context(&post_data, entry.lang.as_deref());
+        let html = self.hbs.render(template, &context).map_err(|e| {
+            BuilderError::render(&entry.title, &self.entry_output(entry, Path::new("")), e)
+        })?;
+        Ok(self.finish_html(html))
+    }
+
+    /// Fetch the site's data and parse every post, without rendering anything. Building
+    /// or rendering a file loads the site first if it hasn't been.
+    pub fn load(&mut self) -> Result<()> {
+        if self.loaded {
+            return Ok(());
+        }
+        let cache_dir = remote::cache_dir(self.opts.cache_dir.as_deref());
+        // a sandboxed build doesn't pull in remote data sources
+        let remote = match self.opts.sandbox {
+            true => json!({}),
+            false => remote::fetch_all(&self.opts.remote, &cache_dir, self.opts.offline)?,
+        };
+        self.data = json!({ "remote": remote });
 
-        let dest = &self
+        let comments_dir = self
             .opts
-            .dest
+            .comments_dir
             .clone()
-            .ok_or(BuilderError::MissingValue("dest".to_string()))?;
-        let dest = PathBuf::from(dest);
+            .unwrap_or(DEFAULT_COMMENTS_DIR.to_string());
+        self.comments = comments::load_all(Path::new(&comments_dir))?;
 
-        let url = &self
+        if let Some(config) = self
             .opts
-            .url
-            .clone()
-            .ok_or(BuilderError::MissingValue("url".to_string()))?;
-        let domain = url::Url::parse(url)?;
-        let domain = domain.host().ok_or(BuilderError::BadURL)?;
+            .webmentions
+            .as_ref()
+            .filter(|_c| !self.opts.sandbox)
+        {
+            let url = &self
+                .opts
+                .url
+                .clone()
+                .ok_or(BuilderError::MissingValue("url".to_string()))?;
+            let site_url = url::Url::parse(url)?;
+            self.webmentions =
+                webmentions::fetch(config, &site_url, &cache_dir, self.opts.offline)?;
+        }
 
-        for entry_set in self.entries.chunks(num_per_page.into()) {
-            // output individual page, and add to rss and tag dictionaries
-            for entry in entry_set {
-                let entry_text = if let Some(trun_len) = &self.opts.truncate {
-                    truncate_text(&entry.raw_text, *trun_len as usize)
-                } else {
-                    entry.raw_text.as_str()
-                };
-                let post_data = json!({
-                    "title": entry.title,
-                    "contents": entry.contents,
-                    "tags": entry.tags,
-                    "url": entry.url,
-                    "modified": entry.modified.format(DATE_FORMAT).to_string(),
-                    "hero_image": entry.hero_image,
-                    "share_image": entry.share_image,
-                    "description": entry.description.as_ref().unwrap_or(&truncate_text(&entry.raw_text, 300).to_string()),
-                    "site_url": self.opts.url,
-                });
-                let rendered = self.hbs.render("entry", &post_data)?;
-                let output_fn = dest.join(entry.url.as_str());
-                println!("Writing {} to {:?}", entry.title, output_fn);
-                fs::write(output_fn, rendered)?;
-
-                // this is one of the latest posts, add it to the rss list
-                if count == 0 {
-                    rss_data.push(json!({
-                        "title": entry.title,
-                        "description": entry_text,
-                        "modified": entry.modified.format("%+").to_string(),
-                        "url": entry.url,
-                        "site_url": &self.opts.url,
-                        "contents": entry.contents,
-                        "time_stamp": now.format("%+").to_string(),
-                        "tag_date": now.format("%F").to_string(),
-                        "author": &self.opts.author.clone().unwrap_or("anonymous".to_string()),
-                        "domain": domain.to_string()
-                    }));
-                }
+        // planet items are reduced to plain text, so they're safe to aggregate in the sandbox,
+        // but only from feeds already in the cache
+        if let Some(config) = &self.opts.planet {
+            let offline = self.opts.offline || self.opts.sandbox;
+            self.planet = planet::fetch(config, &cache_dir, offline)?;
+        }
 
-                // collect the tags for this post and associate them to the entry
-                for tag in entry.tags.iter() {
-                    let tag_entry = json!({
-                        "url": entry.url,
-                        "title": entry.title,
-                        "tag": tag,
-                    });
-                    match tag_map.get_mut(tag) {
-                        Some(tl) => tl.push(tag_entry),
-                        None => {
-                            tag_map.insert(tag.to_string(), vec![tag_entry]);
-                        }
-                    };
-                }
-            }
+        if let Some(bibliography) = &self.opts.bibliography {
+            let buf = fs::read_to_string(bibliography)?;
+            self.references_hash = content_hash(buf.as_bytes());
+            self.references = citations::parse_bibtex(&buf);
+        }
 
-            // get whole chunk of posts to generate the paginated indexes
-            let entries: Vec<_> = entry_set
-                .iter()
-                .map(|entry| {
-                    json!({
-                        "title": entry.title,
-                        "contents": entry.contents,
-                        "tags": entry.tags,
-                        "url": entry.url,
-                        "modified": entry.modified.format(DATE_FORMAT).to_string(),
-                        "hero_image": entry.hero_image,
-                        "site_url": &self.opts.url,
-                    })
-                })
-                .collect();
+        if let Some(theme) = &self.opts.highlight_theme {
+            self.highlighter = Some(Highlighter::new(theme)?);
+        }
 
-            let page_data = json!({
-                "title": &self.opts.title,
-                "contents": entries,
-                "pagination": pagination,
-                "year": now.format("%Y").to_string(),
-                "pub_date": now.format("%a, %e %b, %Y %T %Z").to_string(),
-                "description": &self.opts.description,
-                "site_url": self.opts.url,
-            });
+        // posts parse independently, results come back in file order so the sort stays stable
+        for entry in parallel_map(&self.files, |file| self.parse_entry(file)) {
+            let entry = entry?;
+            if entry.draft {
+                debug!("Skipping draft {}", entry.title);
+                self.drafts.push(entry);
+                continue;
+            }
+            self.entries.push(entry);
+        }
 
-            let index_fn = if count == 0 {
-                "index.html".to_string()
-            } else {
-                format!("index{}.html", count)
-            };
+        if let Some(site_name) = &self.site_name {
+            for peer in self.peers.iter().filter(|p| &p.name != site_name) {
+                for file in get_posts(Path::new(&peer.src)).unwrap_or_default() {
+                    let mut entry = self.parse_entry(&file)?;
+                    if !entry.draft && entry.syndicate.contains(site_name) {
+                        info!("Syndicating {} from {}", entry.title, peer.name);
+                        entry.canonical = Some(format!("{}/{}", peer.url, entry.url));
+                        self.entries.push(entry);
+                    }
+                }
+            }
+        }
 
-            let output_fn = dest.join(index_fn.as_str());
-            let index_page = self.hbs.render("index", &page_data)?;
-            println!("Writing page {} to {:?}", count, output_fn);
-            fs::write(output_fn, index_page)?;
-            count += 1;
+        // without nested_output, src/2023/notes.md and src/2024/notes.md are both notes.html
+        let mut sources = BTreeMap::new();
+        for entry in self.entries.iter() {
+            if let Some(first) = sources.insert(&entry.url, &entry.source) {
+                return Err(BuilderError::DuplicateUrl {
+                    url: entry.url.clone(),
+                    first: Box::new(first.clone()),
+                    second: Box::new(entry.source.clone()),
+                }
+                .into());
+            }
         }
 
-        // generate rss with latest data
-        let rss_data = json!({
-            "title": &self.opts.title,
-            "entries": rss_data,
-            "year": now.format("%Y").to_string(),
-            "pub_date": now.format("%a, %e %b, %Y %T %Z").to_string(),
-            "site_url": self.opts.url,
-            "description": &self.opts.description,
-            "time_stamp": now.format("%+").to_string(),
-            "tag_date": now.format("%F").to_string(),
-            "domain": domain.to_string(),
+        self.entries.sort_by(|a, b| {
+            let bd = b.modified.signed_duration_since(a.modified);
+            let ad = a.modified.signed_duration_since(b.modified);
+            bd.cmp(&ad)
         });
-        let rss_fn = dest.join("index.rss");
-        let rss_feed = self.hbs.render("atom", &rss_data)?;
-        println!("Writing RSS feed to {:?}", rss_fn);
-        fs::write(rss_fn, rss_feed)?;
 
-        // generate tag list
-        let tags_data = json!({ "tags": tag_map });
-        let tags_fn = dest.join("tags.html");
-        let tags_page = self.hbs.render("tag-list", &tags_data)?;
-        println!("Writing tags to {:?}", tags_fn);
-        fs::write(tags_fn, tags_page)?;
+        let related: Vec<_> = self.entries.iter().map(|e| self.related_posts(e)).collect();
+        for (entry, related) in self.entries.iter_mut().zip(related) {
+            entry.related = related;
+        }
+        let translations: Vec<_> = self.entries.iter().map(|e| self.translations(e)).collect();
+        for (entry, translations) in self.entries.iter_mut().zip(translations) {
+            entry.translations = translations;
+        }
 
+        self.feeds = self.site_feeds();
+        self.language_feeds = self.language_feeds();
+        self.index_posts();
+        self.loaded = true;
         Ok(())
     }
 
-    fn parse_entry(&self, file: &Path) -> Result<FileEntry> {
-        let filename = file
-            .to_str()
-            .ok_or(BuilderError::BadFilename(Box::new(file.to_owned())))?;
-        let buf = fs::read_to_string(filename).unwrap();
-
-        let mut pub_date = DateTime::<FixedOffset>::from(Local::now());
-        let mut tag_list: Vec<String> = vec![];
-        let mut title = String::new();
-        let mut share_image = None;
-        let mut hero_image = None;
-        let mut description = None;
-
-        // extract metadata from post
-        let mut sep_count = 0;
-        for line in buf.lines() {
-            if line == HEADER_DELIMITER {
-                sep_count += 1;
-                if sep_count == 2 {
-                    break;
-                }
-            }
+    // the posts sharing the most tags with entry, newest first among equals
+    fn related_posts(&self, entry: &FileEntry) -> Vec<RelatedPost> {
+        let mut related: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|other| other.url != entry.url)
+            .map(|other| {
+                let shared = other.tags.iter().filter(|t| entry.tags.contains(t)).count();
+                (shared, other)
+            })
+            .filter(|(shared, _other)| *shared > 0)
+            .collect();
+        // entries are already newest first and the sort is stable
+        related.sort_by_key(|(shared, _other)| std::cmp::Reverse(*shared));
+        related
+            .into_iter()
+            .take(RELATED_POSTS)
+            .map(|(shared_tags, other)| RelatedPost {
+                title: other.title.clone(),
+                url: other.url.clone(),
+                description: other.description.clone(),
+                shared_tags,
+            })
+            .collect()
+    }
 
-            let elements: Vec<&str> = line.split(' ').collect();
-            let data_type = elements.first();
-            let data_value = elements[1..].join(" ");
+    // the same post in the site's other languages: post.de.md translates post.md
+    fn translations(&self, entry: &FileEntry) -> Vec<Translation> {
+        if self.opts.languages.is_empty() {
+            return vec![];
+        }
+        let key = i18n::translation_key(&entry.source, &self.opts.languages);
+        self.entries
+            .iter()
+            .filter(|other| other.url != entry.url)
+            .filter(|other| i18n::translation_key(&other.source, &self.opts.languages) == key)
+            .map(|other| Translation {
+                lang: other.lang.clone().unwrap_or(self.site_lang().to_string()),
+                title: other.title.clone(),
+                url: other.url.clone(),
+            })
+            .collect()
+    }
 
-            match data_type {
-                Some(&"date:") => {
-                    pub_date = parse_date(data_value.as_str());
-                }
-                Some(&"tags:") => {
-                    tag_list = data_value
-                        .split(',')
-                        .map(|e| String::from(e.trim()))
-                        .collect()
-                }
-                Some(&"title:") => {
-                    title = data_value;
+    // stylesheets are written at the same place under dest as under the styles dir, with a
+    // css extension; with keep_going a broken one is reported once the build ends
+    fn compile_styles(&mut self) -> Result<()> {
+        let styles_dir = match &self.opts.styles_dir {
+            Some(d) => PathBuf::from(d),
+            None => return Ok(()),
+        };
+        for sheet in styles::stylesheets(&styles_dir)? {
+            let css = match styles::compile(&styles_dir.join(&sheet)) {
+                Ok(css) => css,
+                Err(e) if self.opts.keep_going => {
+                    error!("{}", e);
+                    self.render_errors.lock().unwrap().push(e.to_string());
+                    continue;
                 }
-                Some(&"share_image:") => {
-                    share_image = Some(data_value);
+                Err(e) => return Err(e),
+            };
+            let output = Path::new(&self.opts.dest).join(sheet.with_extension("css"));
+            // pruning writes it once the pages are, so it isn't rewritten in full every build
+            match self.pruning() {
+                true => self.track_output(&output),
+                false => {
+                    self.write_if_changed(css.clone(), &output, &format!("stylesheet {:?}", sheet))?
                 }
-                Some(&"hero_image:") => {
-                    hero_image = Some(data_value);
+            }
+            self.add_stylesheet(&sheet.with_extension("css").to_string_lossy(), css);
+        }
+        Ok(())
+    }
+
+    // resized copies of the images posts use, written beside the copied originals, for the
+    // srcsets added to post html and {{srcset}} in templates
+    fn resize_images(&mut self) -> Result<()> {
+        let widths = match &self.opts.images {
+            Some(config) => config.widths.clone(),
+            None => return Ok(()),
+        };
+        let static_dir = PathBuf::from(self.opts.static_dir.clone().unwrap_or_default());
+        let dest = PathBuf::from(&self.opts.dest);
+        let used: BTreeSet<String> = self.entries.iter().flat_map(images::referenced).collect();
+        let mut resized = BTreeMap::new();
+        for path in used {
+            let source = static_dir.join(&path);
+            if self.opts.static_dir.is_none() || !source.is_file() {
+                continue;
+            }
+            let variants = images::resize(&source, &path, &dest, &widths, self.opts.dry_run)?;
+            let outputs = self.outputs.get_mut().unwrap();
+            outputs.extend(variants.iter().map(|v| v.path.clone()));
+            resized.insert(path, variants);
+        }
+        info!("Resized {} images", resized.len());
+
+        for entry in self.entries.iter_mut() {
+            entry.contents = images::add_srcset(&entry.contents, &entry.url, &resized);
+        }
+        self.template_hash =
+            content_hash(format!("{}{:?}", self.template_hash, resized).as_bytes());
+        self.hbs
+            .register_helper("srcset", Box::new(SrcsetHelper { images: resized }));
+        Ok(())
+    }
+
+    // fonts cut down to the characters the site uses and written to dest as woff2, their
+    // @font-face rules going to the font_faces helper
+    fn subset_fonts(&mut self) -> Result<()> {
+        if self.opts.fonts.is_empty() {
+            return Ok(());
+        }
+        let dest = PathBuf::from(&self.opts.dest);
+        let site_url = self.opts.url.clone().unwrap_or_default();
+        let mut text: Vec<&str> = vec![&self.opts.title];
+        text.extend(self.opts.description.as_deref());
+        for entry in self.entries.iter() {
+            text.extend([entry.title.as_str(), entry.raw_text.as_str()]);
+            text.extend(entry.description.as_deref());
+            text.extend(entry.tags.iter().map(String::as_str));
+        }
+        let used = fonts::characters(text);
+
+        let mut faces = vec![];
+        for font in self.opts.fonts.iter() {
+            let mut chars = used.clone();
+            chars.extend(font.text.chars());
+            let subset = match fonts::subset(Path::new(&font.src), &chars) {
+                Ok(subset) => subset,
+                Err(e) if self.opts.keep_going => {
+                    error!("{}", e);
+                    self.render_errors.lock().unwrap().push(e.to_string());
+                    continue;
                 }
-                Some(&"description:") => {
-                    description = Some(data_value);
+                Err(e) => return Err(e),
+            };
+            let path = font.output_path();
+            self.write_if_changed(subset, &dest.join(&path), "font")?;
+            faces.push(font.font_face(&format!("{}/{}", site_url, path)));
+        }
+        info!("Subset {} fonts to {} characters", faces.len(), used.len());
+
+        let css = faces.join("\n");
+        self.template_hash = content_hash(format!("{}{}", self.template_hash, css).as_bytes());
+        self.hbs
+            .register_helper("font_faces", Box::new(FontFacesHelper { css }));
+        Ok(())
+    }
+
+    // third-party iframes become click-to-load placeholders, their preview images fetched
+    // now and served from dest, so pages make no third-party requests until a reader asks
+    fn hold_back_embeds(&mut self) -> Result<()> {
+        if self.opts.embeds.is_empty() {
+            return Ok(());
+        }
+        let cache_dir = remote::cache_dir(self.opts.cache_dir.as_deref());
+        let dest = PathBuf::from(&self.opts.dest);
+        let site_url = self.opts.url.clone().unwrap_or_default();
+        let found: BTreeSet<Embed> = self
+            .entries
+            .iter()
+            .flat_map(|entry| embeds::find(&entry.contents, &self.opts.embeds))
+            .collect();
+
+        let mut previews = BTreeMap::new();
+        for embed in found {
+            // a sandboxed build doesn't fetch anything
+            let url = match embed.preview_url(&self.opts.embeds) {
+                Some(url) if !self.opts.sandbox => url,
+                _ => continue,
+            };
+            let path = embed.preview_path(&url);
+            let fetched = remote::fetch_cached_bytes(
+                &path.replace('/', "-"),
+                &url,
+                embeds::PREVIEW_TTL,
+                &cache_dir,
+                self.opts.offline,
+            );
+            match fetched {
+                Ok(image) => {
+                    self.write_if_changed(image, &dest.join(&path), "embed preview")?;
+                    previews.insert(embed, format!("{}/{}", site_url, path));
                 }
-                _ => (),
+                Err(e) => warn!("No preview for {} {}: {}", embed.provider, embed.id, e),
             }
         }
+        for entry in self.entries.iter_mut() {
+            entry.contents = embeds::hold_back(&entry.contents, &self.opts.embeds, &previews);
+        }
+        Ok(())
+    }
+
+    // summarize every post for the posts helper; pages using it depend on every post, so the
+    // index is part of what decides whether a page needs rendering again
+    fn index_posts(&mut self) {
+        let index: Vec<Value> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                json!({
+                    "id": entry.id,
+                    "title": entry.title,
+                    "url": entry.url,
+                    "tags": entry.tags,
+                    "section": entry.section,
+                    "modified": entry.modified.format(DATE_FORMAT).to_string(),
+                    "date": entry.modified.to_rfc3339(),
+                    "description": entry.description,
+                    "hero_image": entry.hero_image,
+                    "lang": entry.lang,
+                    "word_count": entry.word_count,
+                    "reading_time_minutes": entry.reading_time_minutes,
+                    "author": entry.author,
+                })
+            })
+            .collect();
+        self.template_hash = content_hash(
+            format!("{}{}", self.template_hash, Value::from(index.clone())).as_bytes(),
+        );
+        self.post_index = Arc::new(index);
+        self.hbs.register_helper(
+            "posts",
+            Box::new(PostsHelper {
+                posts: self.post_index.clone(),
+            }),
+        );
+    }
 
-        // generate the filename
-        let url = match file.iter().last() {
-            Some(u) => match u.to_str() {
-                Some(u) => String::from(u).replace(".md", ".html"),
-                None => String::from(filename),
-            },
-            None => String::from(filename),
+    // copy the static dir before rendering so templates can find where each file went
+    fn copy_static(&mut self, dry_run: bool) -> Result<()> {
+        self.assets_known = true;
+        let static_dir = match &self.opts.static_dir {
+            Some(d) => d.clone(),
+            None => return Ok(()),
         };
+        let assets = assets::copy_dir(
+            Path::new(&static_dir),
+            Path::new(&self.opts.dest),
+            self.opts.fingerprint,
+            dry_run,
+        )?;
+        let verb = if dry_run { "Would copy" } else { "Copied" };
+        info!(
+            "{} {} static files from {}",
+            verb, assets.copied, static_dir
+        );
+        self.outputs
+            .get_mut()
+            .unwrap()
+            .extend(assets.paths.values().cloned());
+        for (source, output) in assets.paths.iter() {
+            if source.ends_with(".css") {
+                let css = fs::read_to_string(Path::new(&static_dir).join(source))?;
+                self.add_stylesheet(output, css);
+            }
+        }
 
-        let mut comrak_options = ComrakOptions::default();
-        comrak_options.render.unsafe_ = true;
-        comrak_options.parse.smart = true;
-        comrak_options.extension.front_matter_delimiter = Some(HEADER_DELIMITER.to_owned());
-        comrak_options.extension.strikethrough = true;
-        comrak_options.extension.tagfilter = false;
-        let contents = markdown_to_html(buf.as_str(), &comrak_options);
-        let raw_text = strip_tags(contents.as_str());
+        // a restyled asset changes the path pages link to, so it has to invalidate them
+        self.template_hash =
+            content_hash(format!("{}{:?}", self.template_hash, assets.paths).as_bytes());
+        self.hbs.register_helper(
+            "asset",
+            Box::new(AssetHelper {
+                paths: assets.paths,
+            }),
+        );
+        Ok(())
+    }
 
-        println!("Parsed {:?} as {}", file, title);
+    fn build_blog(&self) -> Result<()> {
+        let mut tag_map: BTreeMap<String, Vec<Value>> = BTreeMap::new();
 
-        let entry = FileEntry {
-            modified: pub_date,
-            tags: tag_list,
-            raw_text,
-            contents,
-            title,
-            url,
-            hero_image,
-            share_image,
-            description,
-        };
+        let dest = PathBuf::from(&self.opts.dest);
+
+        let url = &self
+            .opts
+            .url
+            .clone()
+            .ok_or(BuilderError::MissingValue("url".to_string()))?;
+        // feeds and the sitemap need an absolute url with a host
+        url::Url::parse(url)?.host().ok_or(BuilderError::BadURL)?;
+
+        // entry pages don't depend on each other, so render them on every core
+        for written in parallel_map(&self.entries, |entry| self.write_entry(entry, &dest)) {
+            written?;
+        }
+
+        // collect the tags for every post and associate them to the entry
+        for entry in self.entries.iter() {
+            for tag in entry.tags.iter() {
+                let tag_entry = json!({
+                    "url": entry.url,
+                    "title": entry.title,
+                    "tag": tag,
+                    "modified": entry.modified.format(DATE_FORMAT).to_string(),
+                });
+                match tag_map.get_mut(tag) {
+                    Some(tl) => tl.push(tag_entry),
+                    None => {
+                        tag_map.insert(tag.to_string(), vec![tag_entry]);
+                    }
+                };
+            }
+        }
+
+        // posts in the site's other languages get an index and feeds of their own
+        self.build_index(&dest, url, None)?;
+        for lang in self.other_languages() {
+            self.build_index(&dest, url, Some(lang))?;
+        }
+
+        self.build_tags(&dest, url, &tag_map)?;
+        self.build_authors(&dest, url)?;
+        self.build_sections(&dest, url)?;
+        self.build_landing_pages(&dest, url)?;
+
+        self.build_archives(&dest, url)?;
+        self.build_digests(&dest, url)?;
+        self.build_changes(&dest, url)?;
+        self.build_bookmarks(&dest, url)?;
+        self.build_planet(&dest, url)?;
+        self.build_search_index(&dest)?;
+        self.build_calendar(url)?;
+        self.build_sitemap(&dest, url)?;
+        self.build_redirects(&dest)?;
+
+        Ok(())
+    }
+
+    fn build_calendar(&self, site_url: &str) -> Result<()> {
+        if let Some(output) = &self.opts.calendar {
+            let ics = calendar::render(&self.opts.title, site_url, &self.entries, &self.drafts);
+            self.write_if_changed(ics, Path::new(output), "calendar")?;
+        }
+        Ok(())
+    }
+
+    fn build_search_index(&self, dest: &Path) -> Result<()> {
+        if let Some(config) = &self.opts.search {
+            for (name, json) in search::render(config, &self.entries)? {
+                self.write_if_changed(json, &dest.join(name), "search index")?;
+            }
+        }
+        Ok(())
+    }
+
+    // each configured section's own paginated listing, <section>.html, <section>1.html...
+    fn build_sections(&self, dest: &Path, site_url: &str) -> Result<()> {
+        let now = Local::now();
+        for (name, section) in self.opts.sections.iter() {
+            let template = section.index_template.as_deref().unwrap_or("index");
+            if !self.hbs.has_template(template) {
+                return Err(BuilderError::MissingTemplate(template.to_string()).into());
+            }
+            let entries = self.section_entries(name);
+            let num_per_page = section.entries.unwrap_or(self.opts.entries).max(1).into();
+            let pagination = pagination(name, name, entries.len(), num_per_page);
+            let num_pages = entries.len().div_ceil(num_per_page).max(1);
+            let title = format!("{}: {}", self.opts.title, name);
+            for count in 0..num_pages {
+                let chunk = entries.iter().skip(count * num_per_page).take(num_per_page);
+                let page_data = IndexPage {
+                    title: &title,
+                    contents: chunk
+                        .map(|entry| IndexEntry::new(entry, self.opts.url.as_deref()))
+                        .collect(),
+                    pagination: &pagination,
+                    year: now.format("%Y").to_string(),
+                    pub_date: now.format("%a, %e %b, %Y %T %Z").to_string(),
+                    description: self.opts.description.as_deref(),
+                    site_url: self.opts.url.as_deref(),
+                    share_image: self.opts.share_image.as_deref(),
+                    lang: None,
+                    links: PageLinks::new(site_url, name, count, num_pages),
+                };
+                let output_fn = dest.join(page_url(name, count));
+                let page = format!("{} page {}", name, count);
+                self.write_page(template, page_data, &output_fn, &page)?;
+            }
+        }
+        Ok(())
+    }
+
+    // config defined pages mixing the results of several queries, with each post's html so
+    // a pinned post can be shown whole
+    fn build_landing_pages(&self, dest: &Path, site_url: &str) -> Result<()> {
+        for (name, page) in self.opts.pages.iter() {
+            self.build_landing_page(dest, site_url, name, page)?;
+        }
+        Ok(())
+    }
+
+    fn build_landing_page(
+        &self,
+        dest: &Path,
+        site_url: &str,
+        name: &str,
+        page: &LandingPage,
+    ) -> Result<()> {
+        if !self.hbs.has_template(&page.template) {
+            return Err(BuilderError::MissingTemplate(page.template.clone()).into());
+        }
+        let mut posts = BTreeMap::new();
+        for (query, found) in page.run(&self.post_index)? {
+            let found = found
+                .into_iter()
+                .map(|post| {
+                    let mut post = post.clone();
+                    if let Some(entry) = self.entries.iter().find(|e| post["url"] == e.url) {
+                        post["contents"] = Value::from(entry.contents.as_str());
+                    }
+                    post
+                })
+                .collect();
+            posts.insert(query, found);
+        }
+        let path = page.path.trim_start_matches('/');
+        let canonical = match path.strip_suffix("index.html") {
+            Some(dir) => format!("{}/{}", site_url, dir),
+            None => format!("{}/{}", site_url, path),
+        };
+        let data = LandingPageData {
+            title: page.title.as_deref().unwrap_or(&self.opts.title),
+            description: self.opts.description.as_deref(),
+            site_url: Some(site_url),
+            share_image: self.opts.share_image.as_deref(),
+            canonical,
+            year: Local::now().format("%Y").to_string(),
+            posts,
+        };
+        self.write_page(&page.template, data, &dest.join(path), name)
+    }
+
+    // remember every url a post with an id has had and send its old urls on to the current one
+    fn build_redirects(&self, dest: &Path) -> Result<()> {
+        let lock = match &self.opts.slugs_lock {
+            Some(lock) => Path::new(lock),
+            None => return Ok(()),
+        };
+        let mut history = SlugHistory::load(lock)?;
+        let mut moved = false;
+        for entry in self.entries.iter().filter(|e| e.canonical.is_none()) {
+            if let Some(id) = &entry.id {
+                moved |= history.record(id, &entry.url);
+            }
+        }
+        if moved && !self.opts.dry_run {
+            history.save(lock)?;
+        }
+
+        let site_url = self.opts.url.clone().unwrap_or_default();
+        let live: BTreeSet<&str> = self.entries.iter().map(|e| e.url.as_str()).collect();
+        let (redirects, fixed) = slug_history::flatten(history.redirects(&live));
+        for hops in fixed {
+            match hops {
+                Hops::Flattened(_) => info!("{}", hops),
+                Hops::Loop(_) => warn!("{}", hops),
+            }
+        }
+        for (old, now) in redirects {
+            let mut output_fn = dest.join(old);
+            if old.ends_with('/') {
+                output_fn.push("index.html");
+            }
+            let page = slug_history::redirect_page(&format!("{}/{}", site_url, now));
+            self.write_if_changed(page, &output_fn, &format!("redirect from {}", old))?;
+        }
+        Ok(())
+    }
+
+    // the paginated index and the feeds of the posts in one of the site's other languages
+    // under <lang>/, or of every other post at the top of dest
+    fn build_index(&self, dest: &Path, url: &str, lang: Option<&str>) -> Result<()> {
+        let num_per_page = self.opts.entries;
+        let entries = self.language_entries(lang);
+        let dir = lang.map(|l| format!("{}/", l)).unwrap_or_default();
+        let prefix = format!("{}index", dir);
+
+        // create a list of all the indexes we're gonna output
+        let pagination = pagination(&prefix, "home", entries.len(), num_per_page.into());
+
+        // generate the pages
+        let now = Local::now();
+        let mut rss_entries: Vec<_> = vec![];
+
+        let num_pages = entries.len().div_ceil(num_per_page.max(1).into());
+        for (count, entry_set) in entries.chunks(num_per_page.into()).enumerate() {
+            // add each post to the rss list
+            for entry in entry_set {
+                let entry_text = if let Some(trun_len) = &self.opts.truncate {
+                    truncate_text(&entry.raw_text, *trun_len as usize)
+                } else {
+                    entry.raw_text.as_str()
+                };
+
+                // this is one of the latest posts, add it to the rss list unless its section
+                // keeps its posts out of the feeds
+                if rss_entries.len() < num_per_page.into()
+                    && self.section_config(entry).is_none_or(|s| s.feed)
+                {
+                    let mut feed_entry = FeedEntry {
+                        title: entry.title.clone(),
+                        url: entry
+                            .canonical
+                            .clone()
+                            .unwrap_or(format!("{}/{}", url, entry.url)),
+                        id: self.feed_id(entry),
+                        published: entry.modified,
+                        updated: entry.modified,
+                        author: self.entry_author(entry).to_string(),
+                        summary: Some(entry_text.trim().to_string()),
+                        summary_html: None,
+                        contents: entry.contents.clone(),
+                        lang: entry.lang.clone(),
+                        license: entry.license.clone(),
+                    };
+                    if let Some(summary) = &entry.summary {
+                        feed_entry.summary = Some(strip_tags(summary).trim().to_string());
+                        feed_entry.summary_html = Some(summary.clone());
+                    }
+                    // readers show the warning and keep the post folded away behind it
+                    if let Some(warning) = &entry.content_warning {
+                        feed_entry.summary = Some(warning.clone());
+                        feed_entry.summary_html = None;
+                        feed_entry.contents = format!(
+                            "<details><summary>{}</summary>{}</details>",
+                            escape(warning.as_str()),
+                            feed_entry.contents
+                        );
+                    }
+                    rss_entries.push(feed_entry);
+                }
+            }
+
+            // the index borrows its chunk of posts instead of copying their html into json
+            let page_data = IndexPage {
+                title: &self.opts.title,
+                contents: entry_set
+                    .iter()
+                    .map(|entry| IndexEntry::new(entry, self.opts.url.as_deref()))
+                    .collect(),
+                pagination: &pagination,
+                year: now.format("%Y").to_string(),
+                pub_date: now.format("%a, %e %b, %Y %T %Z").to_string(),
+                description: self.opts.description.as_deref(),
+                site_url: self.opts.url.as_deref(),
+                share_image: self.opts.share_image.as_deref(),
+                lang,
+                links: PageLinks::new(url, &prefix, count, num_pages),
+            };
+
+            let index_fn = page_url(&prefix, count);
+
+            let output_fn = dest.join(index_fn.as_str());
+            let page = match lang {
+                Some(lang) => format!("{} page {}", lang, count),
+                None => format!("page {}", count),
+            };
+            self.write_page("index", page_data, &output_fn, &page)?;
+        }
+
+        // generate rss with latest data
+        let mut feed = Feed::new(
+            self.opts.title.clone(),
+            url.to_string(),
+            format!("{}/{}index.rss", url, dir),
+            self.opts.description.clone(),
+            rss_entries,
+        );
+        feed.lang = Some(lang.unwrap_or(self.site_lang()).to_string());
+        feed.license = self.opts.license.clone();
+        let json_feed = feed.to_json_feed(&format!("{}/{}feed.json", url, dir))?;
+        self.write_feed(feed, &dest.join(format!("{}index.rss", dir)), "RSS feed")?;
+        self.write_if_changed(
+            json_feed,
+            &dest.join(format!("{}feed.json", dir)),
+            "JSON feed",
+        )?;
+        Ok(())
+    }
+
+    // the combined tag list, plus a page per tag when the templates provide one
+    fn build_tags(
+        &self,
+        dest: &Path,
+        site_url: &str,
+        tag_map: &BTreeMap<String, Vec<Value>>,
+    ) -> Result<()> {
+        let mut tag_pages = BTreeMap::new();
+        if self.hbs.has_template("tag") {
+            // slugs drop punctuation and case, so C and C++ would share tags/c.html
+            let mut slugs = BTreeMap::new();
+            for tag in tag_map.keys() {
+                if let Some(other) = slugs.insert(tag_url(tag), tag) {
+                    return Err(BuilderError::DuplicateTagUrl(
+                        other.clone(),
+                        tag.clone(),
+                        tag_url(tag),
+                    )
+                    .into());
+                }
+            }
+
+            let num_per_page: usize = self.opts.entries.max(1).into();
+            for (tag, entries) in tag_map.iter() {
+                let prefix = tag_prefix(tag);
+                let pagination = pagination(&prefix, tag, entries.len(), num_per_page);
+                let num_pages = entries.len().div_ceil(num_per_page);
+                for (count, entry_set) in entries.chunks(num_per_page).enumerate() {
+                    let url = page_url(&prefix, count);
+                    let links = PageLinks::new(site_url, &prefix, count, num_pages);
+                    let tag_data = json!({
+                        "title": format!("{}: {}", self.opts.title, tag),
+                        "tag": tag,
+                        "entries": entry_set,
+                        "pagination": pagination,
+                        "url": url,
+                        "site_url": self.opts.url,
+                        "description": &self.opts.description,
+                        "share_image": self.share_image(&[tag.as_str()]),
+                        "canonical": links.canonical,
+                        "rel_prev": links.rel_prev,
+                        "rel_next": links.rel_next,
+                    });
+                    let page = match count {
+                        0 => format!("tag {}", tag),
+                        _ => format!("tag {} page {}", tag, count),
+                    };
+                    self.write_page("tag", tag_data, &dest.join(&url), &page)?;
+                }
+                tag_pages.insert(tag, tag_url(tag));
+            }
+        }
+
+        let tags_data = json!({
+            "tags": tag_map,
+            "tag_pages": tag_pages,
+            "site_url": self.opts.url,
+            "share_image": self.opts.share_image,
+        });
+        let tags_fn = dest.join("tags.html");
+        self.write_page("tag-list", tags_data, &tags_fn, "tags")
+    }
+
+    // a paginated archive of each author's posts, when the templates provide an author page
+    fn build_authors(&self, dest: &Path, site_url: &str) -> Result<()> {
+        if !self.hbs.has_template("author") {
+            return Ok(());
+        }
+        let num_per_page: usize = self.opts.entries.max(1).into();
+        for key in self.opts.authors.keys() {
+            let author = authors::resolve(key, &self.opts.authors, true);
+            let entries: Vec<_> = self
+                .entries
+                .iter()
+                .filter(|entry| entry.author.as_ref().and_then(|a| a.key.as_ref()) == Some(key))
+                .map(|entry| {
+                    json!({
+                        "url": entry.url,
+                        "title": entry.title,
+                        "modified": entry.modified.format(DATE_FORMAT).to_string(),
+                        "description": entry.description,
+                    })
+                })
+                .collect();
+            let prefix = authors::prefix(key);
+            let pagination = pagination(&prefix, &author.name, entries.len(), num_per_page);
+            let num_pages = entries.len().div_ceil(num_per_page).max(1);
+            for count in 0..num_pages {
+                let entry_set = entries.chunks(num_per_page).nth(count).unwrap_or_default();
+                let url = page_url(&prefix, count);
+                let links = PageLinks::new(site_url, &prefix, count, num_pages);
+                let author_data = json!({
+                    "title": format!("{}: {}", self.opts.title, author.name),
+                    "author": author,
+                    "entries": entry_set,
+                    "pagination": pagination,
+                    "url": url,
+                    "site_url": self.opts.url,
+                    "description": author.bio.as_ref().or(self.opts.description.as_ref()),
+                    "share_image": author.avatar.as_ref().or(self.opts.share_image.as_ref()),
+                    "canonical": links.canonical,
+                    "rel_prev": links.rel_prev,
+                    "rel_next": links.rel_next,
+                });
+                let page = match count {
+                    0 => format!("author {}", key),
+                    _ => format!("author {} page {}", key, count),
+                };
+                self.write_page("author", author_data, &dest.join(&url), &page)?;
+            }
+        }
+        Ok(())
+    }
+
+    // every page that lives on this site; syndicated posts belong to their original site's map
+    fn build_sitemap(&self, dest: &Path, site_url: &str) -> Result<()> {
+        let mut urls: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.canonical.is_none())
+            .map(|entry| SitemapUrl {
+                loc: format!("{}/{}", site_url, entry.url),
+                lastmod: Some(entry.modified.to_rfc3339()),
+            })
+            .collect();
+        // listings were last modified when their newest post was
+        let num_per_page = self.opts.entries.max(1).into();
+        let languages = std::iter::once(None).chain(self.other_languages().map(Some));
+        for lang in languages {
+            let entries = self.language_entries(lang);
+            let prefix = match lang {
+                Some(lang) => format!("{}/index", lang),
+                None => "index".to_string(),
+            };
+            for index in 0..entries.len().div_ceil(num_per_page).max(1) {
+                urls.push(SitemapUrl {
+                    loc: format!("{}/{}", site_url, page_url(&prefix, index)),
+                    lastmod: entries
+                        .get(index * num_per_page)
+                        .map(|entry| entry.modified.to_rfc3339()),
+                });
+            }
+        }
+        urls.push(SitemapUrl {
+            loc: format!("{}/tags.html", site_url),
+            lastmod: self
+                .entries
+                .first()
+                .map(|entry| entry.modified.to_rfc3339()),
+        });
+        for (name, section) in self.opts.sections.iter() {
+            let entries = self.section_entries(name);
+            let num_per_page = section.entries.unwrap_or(self.opts.entries).max(1).into();
+            for index in 0..entries.len().div_ceil(num_per_page).max(1) {
+                urls.push(SitemapUrl {
+                    loc: format!("{}/{}", site_url, page_url(name, index)),
+                    lastmod: entries
+                        .get(index * num_per_page)
+                        .map(|entry| entry.modified.to_rfc3339()),
+                });
+            }
+        }
+        for page in self.opts.pages.values() {
+            urls.push(SitemapUrl {
+                loc: format!("{}/{}", site_url, page.path.trim_start_matches('/')),
+                lastmod: self
+                    .entries
+                    .first()
+                    .map(|entry| entry.modified.to_rfc3339()),
+            });
+        }
+        if self.hbs.has_template("tag") {
+            // each page of a tag was last modified when its newest post was
+            let mut dates_by_tag: BTreeMap<&str, Vec<&DateTime<FixedOffset>>> = BTreeMap::new();
+            for entry in self.entries.iter() {
+                for tag in entry.tags.iter() {
+                    dates_by_tag.entry(tag).or_default().push(&entry.modified);
+                }
+            }
+            for (tag, dates) in dates_by_tag {
+                for (index, page) in dates.chunks(num_per_page).enumerate() {
+                    urls.push(SitemapUrl {
+                        loc: format!("{}/{}", site_url, page_url(&tag_prefix(tag), index)),
+                        lastmod: page.first().map(|modified| modified.to_rfc3339()),
+                    });
+                }
+            }
+        }
+
+        if self.hbs.has_template("author") {
+            for key in self.opts.authors.keys() {
+                let dates: Vec<_> = self
+                    .entries
+                    .iter()
+                    .filter(|e| e.author.as_ref().and_then(|a| a.key.as_ref()) == Some(key))
+                    .map(|entry| entry.modified.to_rfc3339())
+                    .collect();
+                for index in 0..dates.len().div_ceil(num_per_page).max(1) {
+                    urls.push(SitemapUrl {
+                        loc: format!("{}/{}", site_url, page_url(&authors::prefix(key), index)),
+                        lastmod: dates.get(index * num_per_page).cloned(),
+                    });
+                }
+            }
+        }
+
+        if self.hbs.has_template("archive") {
+            // like tags, each page of an archive was last modified when its newest post was
+            let mut archives: BTreeMap<String, Vec<&DateTime<FixedOffset>>> = BTreeMap::new();
+            for entry in self.entries.iter() {
+                let (year, month) = (entry.modified.year(), entry.modified.month());
+                archives
+                    .entry(archive_prefix(year, None))
+                    .or_default()
+                    .push(&entry.modified);
+                if self.opts.archive_months {
+                    archives
+                        .entry(archive_prefix(year, Some(month)))
+                        .or_default()
+                        .push(&entry.modified);
+                }
+            }
+            for (prefix, dates) in archives {
+                for (index, page) in dates.chunks(num_per_page).enumerate() {
+                    urls.push(SitemapUrl {
+                        loc: format!("{}/{}", site_url, page_url(&prefix, index)),
+                        lastmod: page.first().map(|modified| modified.to_rfc3339()),
+                    });
+                }
+            }
+        }
+
+        for (name, xml) in sitemap::render(&urls, site_url, sitemap::MAX_URLS, sitemap::MAX_BYTES) {
+            self.write_if_changed(xml, &dest.join(name), "sitemap")?;
+        }
+        Ok(())
+    }
+
+    // a page per year of posts, and with archive_months per month, when the templates have
+    // an archive template
+    fn build_archives(&self, dest: &Path, site_url: &str) -> Result<()> {
+        if !self.hbs.has_template("archive") {
+            return Ok(());
+        }
+        // entries are sorted newest first, so each year and month is as well
+        let mut years: BTreeMap<i32, BTreeMap<u32, Vec<&FileEntry>>> = BTreeMap::new();
+        for entry in self.entries.iter() {
+            years
+                .entry(entry.modified.year())
+                .or_default()
+                .entry(entry.modified.month())
+                .or_default()
+                .push(entry);
+        }
+        let year_links: Vec<_> = years
+            .iter()
+            .rev()
+            .map(|(year, months)| {
+                json!({
+                    "year": year,
+                    "url": archive_url(*year, None),
+                    "count": months.values().map(Vec::len).sum::<usize>(),
+                })
+            })
+            .collect();
+
+        for (year, months) in years.iter() {
+            let month_links: Vec<_> = match self.opts.archive_months {
+                true => months
+                    .iter()
+                    .rev()
+                    .map(|(month, entries)| {
+                        json!({
+                            "month": month_name(*year, *month),
+                            "url": archive_url(*year, Some(*month)),
+                            "count": entries.len(),
+                        })
+                    })
+                    .collect(),
+                false => vec![],
+            };
+            let links = json!({"years": year_links, "months": month_links});
+            let entries: Vec<&FileEntry> = months.values().rev().flatten().copied().collect();
+            self.write_archive(dest, site_url, *year, None, &entries, &links)?;
+
+            if self.opts.archive_months {
+                for (month, entries) in months.iter() {
+                    self.write_archive(dest, site_url, *year, Some(*month), entries, &links)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // links holds the years and months every archive page links to; a period with more
+    // posts than fit on a page gets archive/2024.html, archive/20241.html...
+    fn write_archive(
+        &self,
+        dest: &Path,
+        site_url: &str,
+        year: i32,
+        month: Option<u32>,
+        entries: &[&FileEntry],
+        links: &Value,
+    ) -> Result<()> {
+        let prefix = archive_prefix(year, month);
+        let period = match month {
+            Some(month) => month_name(year, month),
+            None => year.to_string(),
+        };
+        let title = format!("{}: {}", self.opts.title, period);
+        let num_per_page: usize = self.opts.entries.max(1).into();
+        let pagination = pagination(&prefix, &period, entries.len(), num_per_page);
+        let num_pages = entries.len().div_ceil(num_per_page);
+        for (count, entry_set) in entries.chunks(num_per_page).enumerate() {
+            let url = page_url(&prefix, count);
+            let page_links = PageLinks::new(site_url, &prefix, count, num_pages);
+            let entries: Vec<_> = entry_set
+                .iter()
+                .map(|entry| {
+                    json!({
+                        "title": entry.title,
+                        "url": entry.url,
+                        "tags": entry.tags,
+                        "modified": entry.modified.format(DATE_FORMAT).to_string(),
+                    })
+                })
+                .collect();
+            let archive_data = json!({
+                "title": title,
+                "year": year,
+                "month": month.map(|m| month_name(year, m)),
+                "entries": entries,
+                "pagination": pagination,
+                "years": links["years"],
+                "months": links["months"],
+                "url": url,
+                "site_url": site_url,
+                "description": &self.opts.description,
+                "share_image": self.opts.share_image,
+                "canonical": page_links.canonical,
+                "rel_prev": page_links.rel_prev,
+                "rel_next": page_links.rel_next,
+            });
+            let page = match count {
+                0 => format!("archive {}", period),
+                _ => format!("archive {} page {}", period, count),
+            };
+            self.write_page("archive", archive_data, &dest.join(&url), &page)?;
+        }
+        Ok(())
+    }
+
+    fn build_digests(&self, dest: &Path, site_url: &str) -> Result<()> {
+        let period = match self.opts.digest {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        // entries are already sorted newest first, so each bucket is as well
+        let mut buckets: BTreeMap<String, Vec<&FileEntry>> = BTreeMap::new();
+        for entry in self.entries.iter() {
+            buckets
+                .entry(bucket_key(&entry.modified, period))
+                .or_default()
+                .push(entry);
+        }
+
+        let digest_dir = dest.join("digest");
+
+        let now = Local::now();
+        let mut feed_entries: Vec<_> = vec![];
+        for (key, entries) in buckets.iter().rev() {
+            let entries: Vec<_> = entries
+                .iter()
+                .map(|entry| {
+                    json!({
+                        "title": entry.title,
+                        "url": entry.url,
+                        "tags": entry.tags,
+                        "modified": entry.modified.format(DATE_FORMAT).to_string(),
+                        "description": entry.description.as_ref().unwrap_or(&truncate_text(&entry.raw_text, 300).to_string()),
+                        "word_count": entry.word_count,
+                        "reading_time_minutes": entry.reading_time_minutes,
+                    })
+                })
+                .collect();
+
+            let url = format!("digest/{}.html", key);
+            let digest_data = json!({
+                "title": format!("{}: {}", self.opts.title, key),
+                "period": key,
+                "entries": entries,
+                "url": url,
+                "site_url": self.opts.url,
+                "description": &self.opts.description,
+                "year": now.format("%Y").to_string(),
+                "pub_date": now.format("%a, %e %b, %Y %T %Z").to_string(),
+            });
+            let output_fn = dest.join(&url);
+            self.write_page(
+                "digest",
+                digest_data,
+                &output_fn,
+                &format!("digest {}", key),
+            )?;
+
+            let contents: String = entries
+                .iter()
+                .map(|e| {
+                    format!(
+                        "<li><a href=\"{}/{}\">{}</a></li>",
+                        self.opts.url.clone().unwrap_or_default(),
+                        e["url"].as_str().unwrap_or_default(),
+                        e["title"].as_str().unwrap_or_default(),
+                    )
+                })
+                .collect();
+            let modified = buckets[key]
+                .first()
+                .map(|e| e.modified)
+                .unwrap_or(now.fixed_offset());
+            feed_entries.push(FeedEntry {
+                title: format!("{}: {}", self.opts.title, key),
+                url: format!("{}/{}", site_url, url),
+                id: None,
+                published: modified,
+                updated: modified,
+                author: self.author().to_string(),
+                summary: None,
+                summary_html: None,
+                contents: format!("<ul>{}</ul>", contents),
+                lang: None,
+                license: None,
+            });
+        }
+
+        let feed = Feed::new(
+            format!("{} digest", self.opts.title),
+            site_url.to_string(),
+            format!("{}/digest/index.rss", site_url),
+            self.opts.description.clone(),
+            feed_entries,
+        );
+        self.write_feed(feed, &digest_dir.join("index.rss"), "digest feed")?;
+
+        Ok(())
+    }
+
+    fn build_changes(&self, dest: &Path, site_url: &str) -> Result<()> {
+        let now = Local::now();
+        let time_stamp = now.format("%+").to_string();
+
+        let mut manifest = Manifest::load(dest);
+        for entry in self.entries.iter() {
+            manifest.record(&entry.url, &entry.title, &entry.raw_text, &time_stamp);
+        }
+        if !self.opts.dry_run {
+            manifest.save(dest)?;
+        }
+
+        if !self.opts.changes {
+            return Ok(());
+        }
+
+        let changes: Vec<_> = manifest
+            .changes
+            .iter()
+            .map(|change| {
+                let label = match change.kind {
+                    ChangeKind::Added => "New",
+                    ChangeKind::Updated => "Updated",
+                };
+                json!({
+                    "title": change.title,
+                    "url": change.url,
+                    "kind": change.kind,
+                    "label": label,
+                    "date": parse_date(&change.date).format(DATE_FORMAT).to_string(),
+                    "modified": change.date,
+                })
+            })
+            .collect();
+
+        let changes_data = json!({
+            "title": format!("{}: what's new", self.opts.title),
+            "changes": changes,
+            "site_url": self.opts.url,
+            "description": &self.opts.description,
+            "pub_date": now.format("%a, %e %b, %Y %T %Z").to_string(),
+        });
+        let changes_fn = dest.join("changes.html");
+        self.write_page("changes", changes_data, &changes_fn, "changes")?;
+
+        let feed_entries: Vec<_> = manifest
+            .changes
+            .iter()
+            .map(|change| {
+                let label = match change.kind {
+                    ChangeKind::Added => "New",
+                    ChangeKind::Updated => "Updated",
+                };
+                let date = parse_date(&change.date);
+                FeedEntry {
+                    title: format!("{}: {}", label, change.title),
+                    url: format!("{}/{}", site_url, change.url),
+                    id: None,
+                    published: date,
+                    updated: date,
+                    author: self.author().to_string(),
+                    summary: None,
+                    summary_html: None,
+                    contents: change.title.clone(),
+                    lang: None,
+                    license: None,
+                }
+            })
+            .collect();
+        let feed = Feed::new(
+            format!("{}: what's new", self.opts.title),
+            site_url.to_string(),
+            format!("{}/changes.rss", site_url),
+            self.opts.description.clone(),
+            feed_entries,
+        );
+        self.write_feed(feed, &dest.join("changes.rss"), "changes feed")?;
+
+        Ok(())
+    }
+
+    fn build_bookmarks(&self, dest: &Path, site_url: &str) -> Result<()> {
+        let bookmarks = match &self.opts.bookmarks {
+            Some(path) => bookmarks::load(Path::new(path))?,
+            None => return Ok(()),
+        };
+
+        let now = Local::now();
+        let num_per_page: usize = self.opts.entries.into();
+        let pagination = pagination("bookmarks", "bookmarks", bookmarks.len(), num_per_page);
+
+        let num_pages = bookmarks.len().div_ceil(num_per_page.max(1));
+        for (count, page) in bookmarks.chunks(num_per_page.max(1)).enumerate() {
+            let links = PageLinks::new(site_url, "bookmarks", count, num_pages);
+            let items: Vec<_> = page
+                .iter()
+                .map(|b| {
+                    json!({
+                        "title": b.title,
+                        "url": b.url,
+                        "description": b.description,
+                        "tags": b.tags,
+                        "date": b.date.as_deref().map(|d| parse_date(d).format(DATE_FORMAT).to_string()),
+                    })
+                })
+                .collect();
+            let page_data = json!({
+                "title": format!("{}: bookmarks", self.opts.title),
+                "bookmarks": items,
+                "pagination": pagination,
+                "canonical": links.canonical,
+                "rel_prev": links.rel_prev,
+                "rel_next": links.rel_next,
+                "site_url": self.opts.url,
+                "description": &self.opts.description,
+                "year": now.format("%Y").to_string(),
+                "pub_date": now.format("%a, %e %b, %Y %T %Z").to_string(),
+            });
+            let output_fn = dest.join(page_url("bookmarks", count));
+            self.write_page(
+                "bookmarks",
+                page_data,
+                &output_fn,
+                &format!("bookmarks page {}", count),
+            )?;
+        }
+
+        let feed_entries: Vec<_> = bookmarks
+            .iter()
+            .take(num_per_page)
+            .map(|b| {
+                let date = b
+                    .date
+                    .as_deref()
+                    .map(parse_date)
+                    .unwrap_or(now.fixed_offset());
+                FeedEntry {
+                    title: b.title.clone(),
+                    url: b.url.clone(),
+                    id: None,
+                    published: date,
+                    updated: date,
+                    author: self.author().to_string(),
+                    summary: None,
+                    summary_html: None,
+                    contents: b.description.clone().unwrap_or_default(),
+                    lang: None,
+                    license: None,
+                }
+            })
+            .collect();
+        let feed = Feed::new(
+            format!("{}: bookmarks", self.opts.title),
+            site_url.to_string(),
+            format!("{}/bookmarks.rss", site_url),
+            self.opts.description.clone(),
+            feed_entries,
+        );
+        self.write_feed(feed, &dest.join("bookmarks.rss"), "bookmarks feed")?;
+
+        Ok(())
+    }
+
+    // a river of news from the subscribed feeds, rendered with the index template and an atom feed
+    fn build_planet(&self, dest: &Path, site_url: &str) -> Result<()> {
+        let config = match &self.opts.planet {
+            Some(c) => c,
+            None => return Ok(()),
+        };
+
+        let now = Local::now();
+        let title = config
+            .title
+            .clone()
+            .unwrap_or(format!("{}: planet", self.opts.title));
+        let num_per_page: usize = self.opts.entries.into();
+        let pagination = pagination("planet", "latest", self.planet.len(), num_per_page);
+
+        let num_pages = self.planet.len().div_ceil(num_per_page.max(1));
+        for (count, page) in self.planet.chunks(num_per_page.max(1)).enumerate() {
+            let links = PageLinks::new(site_url, "planet", count, num_pages);
+            let entries: Vec<_> = page
+                .iter()
+                .map(|item| {
+                    json!({
+                        "title": format!("{}: {}", item.source, item.title),
+                        "contents": item.contents,
+                        "tags": item.tags,
+                        "url": item.url,
+                        "modified": item.date.map(|d| d.format(DATE_FORMAT).to_string()),
+                        "site_url": &self.opts.url,
+                    })
+                })
+                .collect();
+            let page_data = json!({
+                "title": title,
+                "contents": entries,
+                "pagination": pagination,
+                "canonical": links.canonical,
+                "rel_prev": links.rel_prev,
+                "rel_next": links.rel_next,
+                "year": now.format("%Y").to_string(),
+                "pub_date": now.format("%a, %e %b, %Y %T %Z").to_string(),
+                "description": &self.opts.description,
+                "site_url": self.opts.url,
+            });
+            let output_fn = dest.join(page_url("planet", count));
+            self.write_page(
+                "index",
+                page_data,
+                &output_fn,
+                &format!("planet page {}", count),
+            )?;
+        }
+
+        let feed_entries: Vec<_> = self
+            .planet
+            .iter()
+            .take(num_per_page)
+            .map(|item| {
+                let date = item.date.unwrap_or(now.fixed_offset());
+                FeedEntry {
+                    title: item.title.clone(),
+                    url: item.url.clone(),
+                    id: None,
+                    published: date,
+                    updated: date,
+                    author: item.source.clone(),
+                    summary: None,
+                    summary_html: None,
+                    contents: item.contents.clone(),
+                    lang: None,
+                    license: None,
+                }
+            })
+            .collect();
+        let feed = Feed::new(
+            title,
+            site_url.to_string(),
+            format!("{}/planet.rss", site_url),
+            self.opts.description.clone(),
+            feed_entries,
+        );
+        self.write_feed(feed, &dest.join("planet.rss"), "planet feed")?;
+
+        Ok(())
+    }
+
+    // render a template with the context shared by every page merged in, and write it
+    // to output; with keep_going a failure is recorded and reported once the build ends
+    fn write_page<T: Serialize>(
+        &self,
+        name: &str,
+        data: T,
+        output: &Path,
+        page: &str,
+    ) -> Result<()> {
+        self.render_page(name, data, output, page, None)
+    }
+
+    // write_page for a page rendered from a source file
+    fn render_page<T: Serialize>(
+        &self,
+        name: &str,
+        data: T,
+        output: &Path,
+        page: &str,
+        source: Option<&Path>,
+    ) -> Result<()> {
+        self.track_output(output);
+        // pages under <lang>/ are in that language
+        let lang = output
+            .strip_prefix(&self.opts.dest)
+            .ok()
+            .and_then(|path| path.components().next())
+            .and_then(|dir| dir.as_os_str().to_str());
+        let data = self.page_context(&data, lang);
+        if let Some(debug_page) = &self.opts.debug_context {
+            if output.strip_prefix(&self.opts.dest).ok() == Some(Path::new(debug_page)) {
+                info!(
+                    "Writing the context for {} to {}",
+                    debug_page, DEBUG_CONTEXT_FILE
+                );
+                fs::write(DEBUG_CONTEXT_FILE, serde_json::to_string_pretty(&data)?)?;
+            }
+        }
+
+        // skip pages whose template and data match the last build; the build date alone
+        // doesn't make an index or feed worth rewriting
+        let context = serde_json::to_value(&data)?;
+        let hash = content_hash(
+            format!(
+                "{}{}{}",
+                self.template_hash,
+                name,
+                without_volatile(&context)
+            )
+            .as_bytes(),
+        );
+        if self.cache.lock().unwrap().page_unchanged(output, &hash) {
+            self.unchanged_pages.fetch_add(1, Ordering::SeqCst);
+            return Ok(());
+        }
+
+        let is_html = output.extension().is_some_and(|e| e == "html");
+        let mut rendered = match self.hbs.render(name, &context) {
+            Ok(r) if is_html => self.finish_html(r),
+            Ok(r) => r,
+            Err(e) => {
+                let e = BuilderError::render(page, output, e);
+                if !self.opts.keep_going {
+                    return Err(e.into());
+                }
+                error!("{}", e);
+                self.render_errors.lock().unwrap().push(e.to_string());
+                return Ok(());
+            }
+        };
+        if self.opts.source_comments && is_html {
+            rendered.push_str(&source_comment(source, name, &self.build));
+        }
+        self.cache.lock().unwrap().store_page(output, hash);
+        self.queue(output, rendered.into_bytes(), page)
+    }
+
+    fn page_context<'a, T: Serialize>(
+        &'a self,
+        page: &'a T,
+        lang: Option<&str>,
+    ) -> PageContext<'a, T> {
+        PageContext {
+            page,
+            feeds: lang
+                .and_then(|lang| self.language_feeds.get(lang))
+                .unwrap_or(&self.feeds),
+            data: &self.data,
+            site_lang: self.site_lang(),
+            site_dir: self.site_dir(),
+            build: &self.build,
+            generator: &GENERATOR,
+        }
+    }
+
+    // the html pipeline every rendered page goes through, in order
+    fn finish_html(&self, mut html: String) -> String {
+        if let Some(critical) = &self.opts.critical_css {
+            let site_url = self.opts.url.as_deref().unwrap_or_default();
+            html = critical::inline(&html, &self.stylesheets, site_url, critical);
+        }
+        if self.opts.generator_meta {
+            html = generator::with_meta_tag(&html);
+        }
+        if self.opts.prettify {
+            html = prettify(&html);
+        }
+        if self.opts.minify {
+            html = minify(&html);
+        }
+        html
+    }
+
+    // pages inlining critical rules from a stylesheet have to change with it
+    fn add_stylesheet(&mut self, path: &str, css: String) {
+        if self.opts.critical_css.is_some() {
+            self.template_hash = content_hash(format!("{}{}", self.template_hash, css).as_bytes());
+        } else if !self.pruning() {
+            return;
+        }
+        self.stylesheets.insert(path.replace('\\', "/"), css);
+    }
+
+    // pages over a budget are warned about, or with --strict fail the build; the whole of
+    // dest is checked, pages that weren't rebuilt included
+    fn check_budgets(&self) -> Result<()> {
+        let config = match &self.opts.budgets {
+            Some(config) if !self.opts.dry_run => config,
+            _ => return Ok(()),
+        };
+        let site_url = self.opts.url.as_deref().unwrap_or_default();
+        let over = budgets::check(Path::new(&self.opts.dest), site_url, config)?;
+        if over.is_empty() {
+            return Ok(());
+        }
+        let over: Vec<_> = over.iter().map(|o| o.to_string()).collect();
+        if self.opts.strict {
+            return Err(BuilderError::OverBudget(over).into());
+        }
+        for overage in over {
+            warn!("{}", overage);
+        }
+        Ok(())
+    }
+
+    // links to pages or anchors that aren't in dest are warned about, or with --strict fail
+    // the build; headings only have ids to link to with heading_ids under [markdown] or a
+    // template showing the toc
+    fn check_links(&self) -> Result<()> {
+        if !self.opts.check_links || self.opts.dry_run {
+            return Ok(());
+        }
+        let site_url = self.opts.url.as_deref().unwrap_or_default();
+        let broken = links::broken(Path::new(&self.opts.dest), site_url)?;
+        if broken.is_empty() {
+            return Ok(());
+        }
+        let broken: Vec<_> = broken.iter().map(|b| b.to_string()).collect();
+        if self.opts.strict {
+            return Err(BuilderError::BrokenLinks(broken).into());
+        }
+        for link in broken {
+            warn!("{}", link);
+        }
+        Ok(())
+    }
+
+    // only production builds are pruned, so serving the site keeps every rule to work on
+    fn pruning(&self) -> bool {
+        self.opts.prune_css.is_some() && self.build.profile == DEFAULT_PROFILE
+    }
+
+    // every stylesheet under dest cut down to the rules some page there uses, pages that
+    // weren't rebuilt included. it runs after the writer is done, so it writes directly
+    fn prune_styles(&self) -> Result<()> {
+        let config = match &self.opts.prune_css {
+            Some(config) if self.pruning() => config,
+            _ => return Ok(()),
+        };
+        let dest = Path::new(&self.opts.dest);
+        if self.opts.dry_run {
+            info!("Would prune {} stylesheets", self.stylesheets.len());
+            return Ok(());
+        }
+        let used = prune::used_selectors(dest, &config.safelist)?;
+        for (path, css) in self.stylesheets.iter() {
+            let pruned = prune::prune(css, &used);
+            let output = dest.join(path);
+            if fs::read_to_string(&output).ok().as_deref() == Some(pruned.as_str()) {
+                continue;
+            }
+            info!(
+                "Pruned stylesheet {} from {} to {} bytes",
+                path,
+                css.len(),
+                pruned.len()
+            );
+            fs::write(output, pruned)?;
+        }
+        Ok(())
+    }
+
+    // hand a file to the writer, or with --dry-run only say what writing it would do
+    fn queue(&self, output: &Path, data: Vec<u8>, page: &str) -> Result<()> {
+        if self.opts.dry_run {
+            match fs::read(output) {
+                Ok(existing) if existing == data => {
+                    self.unchanged_pages.fetch_add(1, Ordering::SeqCst);
+                }
+                Ok(_) => info!("Would change {} at {:?}", page, output),
+                Err(_) => info!("Would create {} at {:?}", page, output),
+            }
+            return Ok(());
+        }
+        debug!("Writing {} to {:?}", page, output);
+        // nested posts, tag pages and redirects need their subdirectory of dest
+        if let Some(parent) = output.parent() {
+            fs::DirBuilder::new().recursive(true).create(parent)?;
+        }
+        self.writer.write(output.to_owned(), data)
+    }
+
+    // note a generated file so `clean` knows it's safe to delete
+    fn track_output(&self, output: &Path) {
+        if let Ok(relative) = output.strip_prefix(&self.opts.dest) {
+            let relative: Vec<_> = relative.iter().map(|p| p.to_string_lossy()).collect();
+            self.outputs.lock().unwrap().insert(relative.join("/"));
+        }
+    }
+
+    // feeds are built rather than templated, so there's no context to compare
+    fn write_feed(&self, feed: Feed, output: &Path, page: &str) -> Result<()> {
+        self.write_if_changed(feed.to_atom(), output, page)
+    }
+
+    fn write_if_changed(&self, data: impl Into<Vec<u8>>, output: &Path, page: &str) -> Result<()> {
+        self.track_output(output);
+        let data = data.into();
+        if fs::read(output).ok().as_ref() == Some(&data) {
+            self.unchanged_pages.fetch_add(1, Ordering::SeqCst);
+            return Ok(());
+        }
+        self.queue(output, data, page)
+    }
+
+    fn write_entry(&self, entry: &FileEntry, dest: &Path) -> Result<()> {
+        let (template, post_data) = self.entry_page(entry)?;
+        let output_fn = self.entry_output(entry, dest);
+        let source = Some(entry.source.as_path()).filter(|s| !s.as_os_str().is_empty());
+        self.render_page(template, post_data, &output_fn, &entry.title, source)
+    }
+
+    // a permalink like "{slug}/" is a directory served by its index page
+    fn entry_output(&self, entry: &FileEntry, dest: &Path) -> PathBuf {
+        let mut output_fn = dest.join(entry.url.as_str());
+        if entry.url.is_empty() || entry.url.ends_with('/') {
+            output_fn.push("index.html");
+        }
+        output_fn
+    }
+
+    // the template a post renders with and the data it gets
+    fn entry_page(&self, entry: &FileEntry) -> Result<(&str, Value)> {
+        let post_data = json!({
+            "title": entry.title,
+            "contents": entry.contents,
+            "toc": entry.toc,
+            "tags": entry.tags,
+            "url": entry.url,
+            "modified": entry.modified.format(DATE_FORMAT).to_string(),
+            "hero_image": entry.hero_image,
+            "share_image": entry.share_image.as_deref().or(self.share_image(&entry.tags)),
+            "canonical": entry.canonical,
+            "description": entry.description.as_ref().unwrap_or(&truncate_text(&entry.raw_text, 300).to_string()),
+            "site_url": self.opts.url,
+            "comments": self.comments.get(entry.slug()).unwrap_or(&vec![]),
+            "webmentions": self.webmentions.get(&entry.url),
+            "lang": entry.lang.as_deref().unwrap_or(self.site_lang()),
+            "dir": entry.dir.as_deref().unwrap_or(self.site_dir()),
+            "content_warning": entry.content_warning,
+            "license": self.license(entry),
+            "json_ld": self.json_ld(entry)?,
+            "related": entry.related,
+            "syndication": entry.syndication,
+            "word_count": entry.word_count,
+            "reading_time_minutes": entry.reading_time_minutes,
+            "author": entry.author,
+            "translations": entry.translations,
+        });
+        let template = self
+            .section_config(entry)
+            .and_then(|s| s.template.as_deref())
+            .unwrap_or("entry");
+        if !self.hbs.has_template(template) {
+            return Err(BuilderError::MissingTemplate(template.to_string()).into());
+        }
+        Ok((template, post_data))
+    }
+
+    // a tag uri (RFC 4151) for posts with an id, so renaming one doesn't make it new again
+    fn feed_id(&self, entry: &FileEntry) -> Option<String> {
+        let id = entry.id.as_ref()?;
+        let site_url = url::Url::parse(self.opts.url.as_deref()?).ok()?;
+        Some(format!(
+            "tag:{},{}:{}",
+            site_url.host_str()?,
+            entry.modified.format("%Y"),
+            id
+        ))
+    }
+
+    fn author(&self) -> &str {
+        self.opts.author.as_deref().unwrap_or("anonymous")
+    }
+
+    // the image of the first of tags with one configured, else the site's
+    fn share_image<T: AsRef<str>>(&self, tags: &[T]) -> Option<&str> {
+        tags.iter()
+            .find_map(|tag| self.opts.tag_share_images.get(tag.as_ref()))
+            .or(self.opts.share_image.as_ref())
+            .map(|image| image.as_str())
+    }
+
+    fn license<'a>(&'a self, entry: &'a FileEntry) -> Option<&'a str> {
+        entry.license.as_deref().or(self.opts.license.as_deref())
+    }
+
+    // schema.org metadata for a post, ready to drop into a <script> tag
+    fn json_ld(&self, entry: &FileEntry) -> Result<String> {
+        let url = entry.canonical.clone().unwrap_or(format!(
+            "{}/{}",
+            self.opts.url.clone().unwrap_or_default(),
+            entry.url
+        ));
+        let data = without_nulls(json!({
+            "@context": "https://schema.org",
+            "@type": "BlogPosting",
+            "headline": entry.title,
+            "url": url,
+            "datePublished": entry.modified.to_rfc3339(),
+            "author": { "@type": "Person", "name": self.entry_author(entry) },
+            "inLanguage": entry.lang.as_deref().unwrap_or(self.site_lang()),
+            "license": self.license(entry),
+        }));
+        // a title can't close the script tag early
+        Ok(serde_json::to_string(&data)?.replace("</", "<\\/"))
+    }
+
+    // who wrote a post, the site's author unless its front matter says otherwise
+    fn entry_author<'a>(&'a self, entry: &'a FileEntry) -> &'a str {
+        entry
+            .author
+            .as_ref()
+            .map_or(self.author(), |author| author.name.as_str())
+    }
+
+    fn site_lang(&self) -> &str {
+        self.opts.lang.as_deref().unwrap_or(DEFAULT_LANG)
+    }
+
+    fn site_dir(&self) -> &str {
+        self.opts.dir.as_deref().unwrap_or(DEFAULT_DIR)
+    }
+
+    // every feed the site publishes, for <link rel="alternate"> autodiscovery
+    fn site_feeds(&self) -> Vec<Value> {
+        let site_url = self.opts.url.clone().unwrap_or_default();
+        let mut feeds = vec![json!({
+            "title": &self.opts.title,
+            "type": "application/atom+xml",
+            "href": format!("{}/index.rss", site_url),
+        })];
+        feeds.push(json!({
+            "title": &self.opts.title,
+            "type": "application/feed+json",
+            "href": format!("{}/feed.json", site_url),
+        }));
+        if self.opts.digest.is_some() {
+            feeds.push(json!({
+                "title": format!("{} digest", self.opts.title),
+                "type": "application/atom+xml",
+                "href": format!("{}/digest/index.rss", site_url),
+            }));
+        }
+        if self.opts.changes {
+            feeds.push(json!({
+                "title": format!("{}: what's new", self.opts.title),
+                "type": "application/atom+xml",
+                "href": format!("{}/changes.rss", site_url),
+            }));
+        }
+        if self.opts.bookmarks.is_some() {
+            feeds.push(json!({
+                "title": format!("{}: bookmarks", self.opts.title),
+                "type": "application/atom+xml",
+                "href": format!("{}/bookmarks.rss", site_url),
+            }));
+        }
+        if let Some(planet) = &self.opts.planet {
+            feeds.push(json!({
+                "title": planet.title.clone().unwrap_or(format!("{}: planet", self.opts.title)),
+                "type": "application/atom+xml",
+                "href": format!("{}/planet.rss", site_url),
+            }));
+        }
+        feeds
+    }
+
+    // each of the site's other languages' index feeds, ahead of the site-wide ones
+    fn language_feeds(&self) -> BTreeMap<String, Vec<Value>> {
+        let site_url = self.opts.url.clone().unwrap_or_default();
+        self.other_languages()
+            .map(|lang| {
+                let title = format!("{} ({})", self.opts.title, lang);
+                let mut feeds = vec![
+                    json!({
+                        "title": &title,
+                        "type": "application/atom+xml",
+                        "href": format!("{}/{}/index.rss", site_url, lang),
+                    }),
+                    json!({
+                        "title": &title,
+                        "type": "application/feed+json",
+                        "href": format!("{}/{}/feed.json", site_url, lang),
+                    }),
+                ];
+                feeds.extend(self.feeds.iter().cloned());
+                (lang.to_string(), feeds)
+            })
+            .collect()
+    }
+
+    // where a post is written under dest: wherever the permalink pattern puts it, or else beside
+    // its subdirectory of src with --nested-output, otherwise at the top level
+    fn entry_url(&self, file: &Path, date: &DateTime<FixedOffset>) -> Option<String> {
+        if let Some(pattern) = &self.opts.permalink {
+            return Some(permalink(pattern, file.file_stem()?.to_str()?, date));
+        }
+        let path = match file.strip_prefix(&self.opts.src) {
+            Ok(relative) if self.opts.nested_output => relative,
+            _ => Path::new(file.file_name()?),
+        };
+        let path = path.with_extension("html");
+        let parts: Option<Vec<&str>> = path.iter().map(|p| p.to_str()).collect();
+        Some(parts?.join("/"))
+    }
+
+    // the languages besides the site's own that posts are written in
+    fn other_languages(&self) -> impl Iterator<Item = &str> {
+        let site_lang = self.site_lang();
+        self.opts
+            .languages
+            .iter()
+            .map(String::as_str)
+            .filter(move |lang| *lang != site_lang)
+    }
+
+    // the posts in one of the site's other languages, or all the rest
+    fn language_entries(&self, lang: Option<&str>) -> Vec<&FileEntry> {
+        let others: Vec<_> = self.other_languages().collect();
+        self.entries
+            .iter()
+            .filter(|entry| {
+                let entry_lang = entry.lang.as_deref().filter(|l| others.contains(l));
+                entry_lang == lang
+            })
+            .collect()
+    }
+
+    fn section_entries(&self, name: &str) -> Vec<&FileEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.section.as_deref() == Some(name))
+            .collect()
+    }
+
+    fn section_config(&self, entry: &FileEntry) -> Option<&SectionConfig> {
+        self.opts.sections.get(entry.section.as_ref()?)
+    }
+
+    fn section(&self, file: &Path) -> Option<String> {
+        let relative = file.strip_prefix(&self.opts.src).ok()?;
+        let mut parts = relative.iter();
+        let first = parts.next()?;
+        parts.next()?;
+        Some(first.to_string_lossy().to_string())
+    }
+
+    fn parse_entry(&self, file: &Path) -> Result<FileEntry> {
+        self.parse_source(file, front_matter::read(file)?)
+    }
+
+    // parse_entry for a post already read
+    fn parse_source(&self, file: &Path, source: Source) -> Result<FileEntry> {
+        let filename = file
+            .to_str()
+            .ok_or(BuilderError::BadFilename(Box::new(file.to_owned())))?;
+        // a post's own bibliography sits next to it, and a sandboxed post's can't leave src
+        let post_references = match front_matter::header_value(&source.header, "references") {
+            Some(name) => {
+                let path = file.with_file_name(name.trim());
+                if self.opts.sandbox && !within(&path, Path::new(&self.opts.src)) {
+                    return Err(BuilderError::OutsideSrc(Box::new(path)).into());
+                }
+                fs::read_to_string(path)?
+            }
+            None => String::new(),
+        };
+
+        // anything that changes how a post parses invalidates its cached entry
+        let settings = format!(
+            "{}{:?}{:?}{:?}{}{}{:?}{}{}{:?}{}{:?}{:?}{:?}{}{:?}{}{:?}{}",
+            self.opts.sandbox,
+            self.opts.tag_aliases,
+            self.opts.abbreviations,
+            self.opts.bare_posts,
+            self.opts.title_from_heading,
+            self.opts.nested_output,
+            self.opts.permalink,
+            self.opts.sidenotes,
+            self.references_hash,
+            self.opts.highlight_theme,
+            self.opts.no_widows,
+            self.opts.short_words,
+            self.opts.lang,
+            self.opts.markdown,
+            self.opts.words_per_minute,
+            self.opts.authors,
+            self.hbs.has_template("author"),
+            self.opts.languages,
+            self.uses_toc
+        );
+        let hash = content_hash_parts(&[
+            source.header.as_bytes(),
+            source.body.as_bytes(),
+            post_references.as_bytes(),
+            settings.as_bytes(),
+        ]);
+        let cached = self.cache.lock().unwrap().entry(filename, &hash);
+        if let Some(cached) = cached {
+            if let Ok(entry) = serde_json::from_value(cached) {
+                return Ok(FileEntry {
+                    source: file.to_owned(),
+                    ..entry
+                });
+            }
+        }
+
+        let (mut front_matter, mut body) = match (source.header.is_empty(), self.opts.bare_posts) {
+            (true, Some(date_from)) => front_matter::bare_defaults(file, &source.body, date_from),
+            _ => (front_matter::parse(&source.header), source.body),
+        };
+        if front_matter.title.is_empty() && self.opts.title_from_heading {
+            if let Some((title, rest)) = front_matter::take_heading(&body) {
+                front_matter.title = title;
+                body = rest;
+            }
+        }
+        let languages = &self.opts.languages;
+        front_matter.lang = front_matter
+            .lang
+            .or_else(|| i18n::filename_lang(file, languages));
+        let pub_date = front_matter
+            .date
+            .unwrap_or(DateTime::<FixedOffset>::from(Local::now()));
+
+        let mut references_section = String::new();
+        let has_references = !self.references.is_empty() || !post_references.is_empty();
+        if has_references && body.contains("[@") {
+            let mut references = self.references.clone();
+            references.extend(citations::parse_bibtex(&post_references));
+            let (cited_body, cited) = citations::cite(&body, &references);
+            body = cited_body;
+            references_section = citations::references_html(&cited, &references);
+        }
+
+        // sidenotes are raw html, which sandboxed posts can't contain
+        if self.opts.sidenotes && !self.opts.sandbox {
+            body = sidenotes(&body);
+        }
+
+        let (body, post_abbreviations) = abbreviations::take_definitions(&body);
+        let mut abbreviations = self.opts.abbreviations.clone();
+        abbreviations.extend(post_abbreviations);
+
+        let url = self
+            .entry_url(&i18n::without_lang(file, languages), &pub_date)
+            .unwrap_or(String::from(filename));
+        let url = i18n::prefixed(
+            url,
+            front_matter.lang.as_deref(),
+            self.site_lang(),
+            languages,
+        );
+
+        let (body, excerpt) = match body.split_once(MORE_MARKER) {
+            Some((excerpt, rest)) => (format!("{}{}", excerpt, rest), Some(excerpt.to_string())),
+            None => (body, None),
+        };
+        let mut comrak_options = self.opts.markdown.options(self.opts.sandbox);
+        // templates that show the table of contents get heading ids for it to link to
+        if self.uses_toc {
+            comrak_options
+                .extension
+                .header_ids
+                .get_or_insert_with(String::new);
+        }
+        let render = |markdown: &str| -> Result<(String, Vec<TocEntry>)> {
+            let arena = Arena::new();
+            let root = parse_document(&arena, markdown, &comrak_options);
+            // without heading ids there'd be nothing for the contents to link to
+            let toc = match &comrak_options.extension.header_ids {
+                Some(prefix) => toc::table_of_contents(root, prefix),
+                None => vec![],
+            };
+            let mut html = vec![];
+            format_html(root, &comrak_options, &mut html)?;
+            let mut html = String::from_utf8(html)?;
+            if let Some(highlighter) = &self.highlighter {
+                html = highlighter.highlight_html(&html);
+            }
+            Ok((abbreviations::wrap(&html, &abbreviations), toc))
+        };
+        let (mut contents, toc) = render(&body)?;
+        let mut summary = excerpt
+            .as_deref()
+            .map(render)
+            .transpose()?
+            .map(|(summary, _toc)| summary);
+        let raw_text = strip_tags(contents.as_str());
+        let word_count = raw_text.split_whitespace().count();
+        let reading_time_minutes = word_count.div_ceil(self.opts.words_per_minute.max(1));
+        // after raw_text, so summaries don't pick up &nbsp;
+        if self.opts.no_widows {
+            let lang = front_matter.lang.as_deref().unwrap_or(self.site_lang());
+            contents = typography::no_widows(&contents, lang, &self.opts.short_words);
+            summary = summary.map(|s| typography::no_widows(&s, lang, &self.opts.short_words));
+        }
+        contents.push_str(&references_section);
+
+        debug!("Parsed {:?} as {}", file, front_matter.title);
+        let has_author_pages = self.hbs.has_template("author");
+
+        let entry = FileEntry {
+            modified: pub_date,
+            tags: front_matter::apply_aliases(front_matter.tags, &self.opts.tag_aliases),
+            raw_text,
+            word_count,
+            reading_time_minutes,
+            contents,
+            toc,
+            summary,
+            title: front_matter.title,
+            url,
+            hero_image: front_matter.hero_image,
+            share_image: front_matter.share_image,
+            description: front_matter.description,
+            author: front_matter.author.map(|author| {
+                authors::resolve(author.trim(), &self.opts.authors, has_author_pages)
+            }),
+            lang: front_matter.lang,
+            dir: front_matter.dir,
+            content_warning: front_matter.content_warning,
+            license: front_matter.license,
+            syndicate: front_matter.syndicate,
+            syndication: front_matter.syndication,
+            id: front_matter.id,
+            source: file.to_owned(),
+            section: self.section(file),
+            canonical: None,
+            draft: front_matter.draft,
+            related: vec![],
+            translations: vec![],
+        };
+        // drafts are parsed afresh each time rather than written to disk
+        if !entry.draft {
+            self.cache
+                .lock()
+                .unwrap()
+                .store_entry(filename, hash, serde_json::to_value(&entry)?);
+        }
 
         Ok(entry)
     }
@@ -0,0 +1,148 @@
+use chrono::{DateTime, FixedOffset, Local, Utc};
+
+use crate::builder::FileEntry;
+use crate::generator;
+
+// lines longer than this many bytes are folded onto continuation lines (RFC 5545 3.1)
+const LINE_LIMIT: usize = 75;
+
+// an iCalendar feed of posts for editorial calendars: published posts are confirmed events,
+// drafts and posts dated in the future are tentative
+pub fn render(title: &str, site_url: &str, entries: &[FileEntry], drafts: &[FileEntry]) -> String {
+    let now = Local::now().fixed_offset();
+    let host = url::Url::parse(site_url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_default();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!(
+            "PRODID:-//{}//{} {}//EN",
+            generator::NAME,
+            generator::NAME,
+            generator::VERSION
+        ),
+        format!("X-WR-CALNAME:{}", escape(title)),
+    ];
+    let posts = entries
+        .iter()
+        .map(|e| (e, false))
+        .chain(drafts.iter().map(|d| (d, true)));
+    for (entry, draft) in posts {
+        let scheduled = draft || entry.modified > now;
+        // an id survives renames, so a calendar keeps the event rather than adding another
+        let uid = entry.id.as_deref().unwrap_or(&entry.url);
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}@{}", escape(uid), host),
+            // the post's own date keeps the file the same between builds that change nothing
+            format!("DTSTAMP:{}", utc(&entry.modified)),
+            format!("DTSTART:{}", utc(&entry.modified)),
+            format!("SUMMARY:{}", escape(&entry.title)),
+            format!(
+                "STATUS:{}",
+                if scheduled { "TENTATIVE" } else { "CONFIRMED" }
+            ),
+        ]);
+        if !draft {
+            lines.push(format!("URL:{}/{}", site_url, entry.url));
+        }
+        if !entry.tags.is_empty() {
+            let tags: Vec<_> = entry.tags.iter().map(|t| escape(t)).collect();
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+        if let Some(description) = &entry.description {
+            lines.push(format!("DESCRIPTION:{}", escape(description)));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|l| fold(l) + "\r\n").collect()
+}
+
+fn utc(date: &DateTime<FixedOffset>) -> String {
+    date.with_timezone(&Utc)
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+// split a line every LINE_LIMIT bytes, never inside a character, each continuation starting
+// with a space
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + line.len() / LINE_LIMIT * 3);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > LINE_LIMIT {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn post(title: &str, date: &str, draft: bool) -> FileEntry {
+        serde_json::from_value(json!({
+            "id": null,
+            "section": null,
+            "modified": date,
+            "raw_text": "",
+            "contents": "",
+            "tags": ["rust", "a;b"],
+            "title": title,
+            "url": "post.html",
+            "hero_image": null,
+            "share_image": null,
+            "description": null,
+            "lang": null,
+            "dir": null,
+            "content_warning": null,
+            "license": null,
+            "syndicate": [],
+            "canonical": null,
+            "draft": draft,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn lists_published_and_scheduled_posts() {
+        let published = post("Out, now", "2024-05-04T10:00:00-07:00", false);
+        let future = post("Soon", "2999-01-01T00:00:00Z", false);
+        let draft = post("Maybe", "2024-06-01T00:00:00Z", true);
+        let ics = render(
+            "Blog",
+            "https://example.com",
+            &[published, future],
+            &[draft],
+        );
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.contains("UID:post.html@example.com\r\nDTSTAMP:"));
+        assert!(ics.contains(
+            "DTSTART:20240504T170000Z\r\nSUMMARY:Out\\, now\r\nSTATUS:CONFIRMED\r\n\
+             URL:https://example.com/post.html\r\nCATEGORIES:rust,a\\;b\r\n"
+        ));
+        assert!(ics.contains("SUMMARY:Soon\r\nSTATUS:TENTATIVE\r\nURL:"));
+        assert!(ics.contains("SUMMARY:Maybe\r\nSTATUS:TENTATIVE\r\nCATEGORIES:"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(
+            fold(&"x".repeat(80)),
+            format!("{}\r\n {}", "x".repeat(75), "x".repeat(5))
+        );
+    }
+}
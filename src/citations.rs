@@ -0,0 +1,266 @@
+use std::collections::BTreeMap;
+
+use log::warn;
+use quick_xml::escape::escape;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Reference {
+    pub key: String,
+    pub author: Option<String>,
+    pub title: Option<String>,
+    pub year: Option<String>,
+    // journal, book or publisher
+    pub container: Option<String>,
+    pub url: Option<String>,
+}
+
+impl Reference {
+    // "Knuth 1984", for the in-text marker
+    fn label(&self) -> String {
+        let author = self
+            .author
+            .as_deref()
+            .map(surname)
+            .unwrap_or(self.key.as_str());
+        match &self.year {
+            Some(year) => format!("{} {}", author, year),
+            None => author.to_string(),
+        }
+    }
+
+    fn to_html(&self) -> String {
+        let mut parts = vec![];
+        if let Some(author) = &self.author {
+            parts.push(escape(author.as_str()).to_string());
+        }
+        if let Some(year) = &self.year {
+            parts.push(format!("({})", escape(year.as_str())));
+        }
+        if let Some(title) = &self.title {
+            parts.push(format!("<em>{}</em>.", escape(title.as_str())));
+        }
+        if let Some(container) = &self.container {
+            parts.push(format!("{}.", escape(container.as_str())));
+        }
+        // only web links are live, so a .bib can't sneak a javascript: link past safe mode
+        if let Some(url) = &self.url {
+            let escaped = escape(url.as_str());
+            match url::Url::parse(url) {
+                Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {
+                    parts.push(format!("<a href=\"{u}\">{u}</a>", u = escaped))
+                }
+                _ => parts.push(escaped.to_string()),
+            }
+        }
+        format!(
+            "<li id=\"ref-{}\">{}</li>",
+            escape(self.key.as_str()),
+            parts.join(" ")
+        )
+    }
+}
+
+// the first author's family name, from either "Last, First and ..." or "First Last and ..."
+fn surname(authors: &str) -> &str {
+    let first = authors.split(" and ").next().unwrap_or(authors).trim();
+    match first.split_once(',') {
+        Some((last, _first)) => last.trim(),
+        None => first.rsplit(' ').next().unwrap_or(first),
+    }
+}
+
+// the entries of a BibTeX file by key; only the fields a references list shows are kept
+pub fn parse_bibtex(buf: &str) -> BTreeMap<String, Reference> {
+    let mut references = BTreeMap::new();
+    for chunk in buf.split('@').skip(1) {
+        let body = match chunk.split_once('{') {
+            Some((_kind, body)) => body,
+            None => continue,
+        };
+        let (key, fields) = match body.split_once(',') {
+            Some((key, fields)) => (key.trim(), fields),
+            None => continue,
+        };
+        let mut reference = Reference {
+            key: key.to_string(),
+            ..Default::default()
+        };
+        for (name, value) in fields_of(fields) {
+            match name.to_lowercase().as_str() {
+                "author" => reference.author = Some(value),
+                "title" => reference.title = Some(value),
+                "year" => reference.year = Some(value),
+                "journal" | "booktitle" | "publisher" => {
+                    reference.container.get_or_insert(value);
+                }
+                "url" => reference.url = Some(value),
+                _ => (),
+            }
+        }
+        references.insert(key.to_string(), reference);
+    }
+    references
+}
+
+// `name = {value}`, `name = "value"` or `name = 2024` pairs, braces nesting
+fn fields_of(mut rest: &str) -> Vec<(String, String)> {
+    let mut fields = vec![];
+    while let Some((name, after)) = rest.split_once('=') {
+        let name = name.trim().trim_start_matches(',').trim().to_string();
+        let after = after.trim_start();
+        let (value, remaining) = match after.chars().next() {
+            Some('{') => braced(after),
+            Some('"') => match after[1..].split_once('"') {
+                Some((value, remaining)) => (value.to_string(), remaining),
+                None => (after[1..].to_string(), ""),
+            },
+            _ => {
+                let end = after.find([',', '}']).unwrap_or(after.len());
+                (after[..end].trim().to_string(), &after[end..])
+            }
+        };
+        fields.push((name, value.split_whitespace().collect::<Vec<_>>().join(" ")));
+        rest = remaining;
+    }
+    fields
+}
+
+fn braced(value: &str) -> (String, &str) {
+    let mut depth = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    let inner = value[1..i].replace(['{', '}'], "");
+                    return (inner, &value[i + 1..]);
+                }
+            }
+            _ => (),
+        }
+    }
+    (value.replace(['{', '}'], ""), "")
+}
+
+// replace `[@key]` and `[@a; @b]` markers with markdown links to the references list,
+// returning the keys cited in order of first use
+pub fn cite(body: &str, references: &BTreeMap<String, Reference>) -> (String, Vec<String>) {
+    let mut output = String::with_capacity(body.len());
+    let mut cited: Vec<String> = vec![];
+    let mut rest = body;
+    while let Some(start) = rest.find("[@") {
+        output.push_str(&rest[..start]);
+        let marker = &rest[start..];
+        let end = match marker.find(']') {
+            Some(end) => end,
+            None => break,
+        };
+        let keys: Vec<&str> = marker[1..end]
+            .split(';')
+            .map(|k| k.trim().trim_start_matches('@'))
+            .collect();
+        if keys.iter().all(|k| references.contains_key(*k)) {
+            let links: Vec<String> = keys
+                .iter()
+                .map(|k| format!("[{}](#ref-{})", references[*k].label(), k))
+                .collect();
+            output.push_str(&format!("({})", links.join("; ")));
+            for key in keys {
+                if !cited.iter().any(|c| c == key) {
+                    cited.push(key.to_string());
+                }
+            }
+        } else {
+            warn!("Unknown citation {}", &marker[..=end]);
+            output.push_str(&marker[..=end]);
+        }
+        rest = &marker[end + 1..];
+    }
+    output.push_str(rest);
+    (output, cited)
+}
+
+// the list of works a post cites, in citation order
+pub fn references_html(cited: &[String], references: &BTreeMap<String, Reference>) -> String {
+    if cited.is_empty() {
+        return String::new();
+    }
+    let items: String = cited
+        .iter()
+        .filter_map(|key| references.get(key))
+        .map(Reference::to_html)
+        .collect();
+    format!(
+        "<section class=\"references\"><h2>References</h2><ol>{}</ol></section>",
+        items
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BIB: &str = r#"
+@book{knuth84,
+  author = {Knuth, Donald E.},
+  title = {The {TeX}book},
+  publisher = "Addison-Wesley",
+  year = 1984,
+}
+@article{lamport,
+  author = {Leslie Lamport and Someone Else},
+  title = {Time, Clocks & the Ordering of Events},
+  journal = {CACM},
+  year = {1978},
+  url = {https://example.com/clocks}
+}
+"#;
+
+    #[test]
+    fn parses_bibtex_entries() {
+        let refs = parse_bibtex(BIB);
+        assert_eq!(refs.len(), 2);
+        assert_eq!(refs["knuth84"].title.as_deref(), Some("The TeXbook"));
+        assert_eq!(refs["knuth84"].year.as_deref(), Some("1984"));
+        assert_eq!(refs["knuth84"].container.as_deref(), Some("Addison-Wesley"));
+        assert_eq!(refs["lamport"].label(), "Lamport 1978");
+        assert_eq!(refs["knuth84"].label(), "Knuth 1984");
+    }
+
+    #[test]
+    fn links_citations_to_the_references_list() {
+        let refs = parse_bibtex(BIB);
+        let (body, cited) = cite("As shown [@lamport; @knuth84], and [@missing].", &refs);
+        assert_eq!(
+            body,
+            "As shown ([Lamport 1978](#ref-lamport); [Knuth 1984](#ref-knuth84)), and [@missing]."
+        );
+        assert_eq!(cited, vec!["lamport", "knuth84"]);
+
+        let html = references_html(&cited, &refs);
+        assert!(html.starts_with(
+            "<section class=\"references\"><h2>References</h2><ol><li id=\"ref-lamport\">"
+        ));
+        assert!(html.contains("<em>Time, Clocks &amp; the Ordering of Events</em>."));
+        assert_eq!(references_html(&[], &refs), "");
+    }
+
+    #[test]
+    fn only_links_web_urls() {
+        let reference = |url: &str| Reference {
+            key: "k".to_string(),
+            url: Some(url.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            reference("https://example.com/a?b&c").to_html(),
+            "<li id=\"ref-k\"><a href=\"https://example.com/a?b&amp;c\">\
+             https://example.com/a?b&amp;c</a></li>"
+        );
+        assert_eq!(
+            reference("javascript:alert(1)").to_html(),
+            "<li id=\"ref-k\">javascript:alert(1)</li>"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use color_eyre::Result;
+
+use crate::build_cache::{cache_path, LEGACY_CACHE_FILE};
+use crate::manifest::{manifest_path, Manifest};
+
+// delete the files builds wrote into dest, leaving anything else there alone; with stale_only
+// just the ones the last build no longer produced. returns the files removed
+pub fn clean(dest: &Path, cache_dir: &Path, stale_only: bool) -> Result<Vec<PathBuf>> {
+    let mut manifest = Manifest::load(dest);
+    let mut files: Vec<String> = manifest.stale.iter().cloned().collect();
+    if !stale_only {
+        files.extend(manifest.outputs.iter().cloned());
+    }
+
+    let mut removed = vec![];
+    for file in files.iter() {
+        let path = dest.join(file);
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                remove_empty_parents(&path, dest);
+                removed.push(path);
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => (),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    match stale_only {
+        true => {
+            manifest.stale.clear();
+            manifest.save(dest)?;
+        }
+        // nothing of the last build is left, so neither is what it knew
+        false => {
+            let caches = [cache_path(cache_dir, dest), dest.join(LEGACY_CACHE_FILE)];
+            for state in [manifest_path(dest)].into_iter().chain(caches) {
+                if state.exists() {
+                    fs::remove_file(state)?;
+                }
+            }
+        }
+    }
+    Ok(removed)
+}
+
+// directories emptied by removing file, up to but not including dest
+fn remove_empty_parents(file: &Path, dest: &Path) {
+    let mut dir = file.parent();
+    while let Some(d) = dir.filter(|d| *d != dest && d.starts_with(dest)) {
+        if fs::remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_only_generated_files() -> Result<()> {
+        let dest = std::env::temp_dir().join(format!("site-gen-clean-{}", std::process::id()));
+        fs::DirBuilder::new()
+            .recursive(true)
+            .create(dest.join("tags"))?;
+        for file in ["index.html", "old.html", "tags/a.html", "mine.txt"] {
+            fs::write(dest.join(file), "")?;
+        }
+        let mut manifest = Manifest::default();
+        let set = |files: &[&str]| files.iter().map(|f| f.to_string()).collect();
+        manifest.record_outputs(set(&["index.html", "old.html", "tags/a.html"]));
+        manifest.record_outputs(set(&["index.html", "tags/a.html"]));
+        manifest.save(&dest)?;
+
+        assert_eq!(
+            clean(&dest, &dest.join("cache"), true)?,
+            vec![dest.join("old.html")]
+        );
+        assert!(dest.join("index.html").exists());
+
+        assert_eq!(clean(&dest, &dest.join("cache"), false)?.len(), 2);
+        assert!(!dest.join("tags").exists());
+        assert!(!manifest_path(&dest).exists());
+        assert!(dest.join("mine.txt").exists());
+        fs::remove_dir_all(dest)?;
+        Ok(())
+    }
+}
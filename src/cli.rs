@@ -0,0 +1,965 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use clap::{CommandFactory, Parser, Subcommand};
+use color_eyre::Result;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::authors::AuthorConfig;
+use crate::budgets::BudgetConfig;
+use crate::builder::Builder;
+use crate::critical::CriticalConfig;
+use crate::deploy::DeployConfig;
+use crate::digest::DigestPeriod;
+use crate::embeds::EmbedProvider;
+use crate::fonts::FontConfig;
+use crate::front_matter::BareDate;
+use crate::images::ImageConfig;
+use crate::logger::{self, LogFormat};
+use crate::markdown::MarkdownConfig;
+use crate::meta::{MetaAction, PostFilter};
+use crate::planet::PlanetConfig;
+use crate::prune::PruneConfig;
+use crate::query::LandingPage;
+use crate::remote::{self, RemoteSource};
+use crate::schema::CustomField;
+use crate::search::SearchConfig;
+use crate::sections::SectionConfig;
+use crate::webmentions::WebmentionConfig;
+use crate::{
+    audit, clean, config, create, daemon, deploy, doctor, front_matter, generator, init, meta,
+    schema, serve, sites, tags, watch,
+};
+
+#[derive(Debug, Clone, Parser, Deserialize, Serialize)]
+#[command(version, about, long_about = None, disable_version_flag = true)]
+#[serde(deny_unknown_fields)]
+pub struct Opt {
+    #[command(subcommand)]
+    #[serde(skip)]
+    action: Option<Action>,
+
+    /// Path to config file
+    #[arg(short, long, global = true)]
+    pub config: Option<String>,
+
+    /// How many entries per page
+    #[arg(short, long, default_value = "10")]
+    #[serde(default = "default_entries")]
+    pub entries: u8,
+
+    /// Reading speed used to estimate how many minutes each post takes to read
+    #[arg(long, default_value = "200")]
+    #[serde(default = "default_words_per_minute")]
+    pub words_per_minute: usize,
+
+    /// Directory for templates
+    #[arg(short = 'p', long, default_value = "templates")]
+    #[serde(default = "default_template_dir")]
+    pub template_dir: String,
+
+    /// Directory of rhai scripts registered as template helpers, e.g. helpers/shout.rhai is
+    /// {{shout title}}; needs site-gen built with the scripting feature
+    #[arg(long, default_value = "helpers")]
+    #[serde(default = "default_helper_dir")]
+    pub helper_dir: String,
+
+    /// Directory of post templates for create --kind, e.g. archetypes/link.md
+    #[arg(long, default_value = "archetypes")]
+    #[serde(default = "default_archetype_dir")]
+    pub archetype_dir: String,
+
+    /// Source directory for markdown files
+    #[arg(default_value = "content")]
+    #[serde(default = "default_src")]
+    pub src: String,
+
+    /// Destination for HTML output
+    #[arg(default_value = "public")]
+    #[serde(default = "default_dest")]
+    pub dest: String,
+
+    /// Directory of stylesheets, fonts, images and other files to copy into dest as they are
+    #[arg(long)]
+    pub static_dir: Option<String>,
+
+    /// Directory of scss and sass stylesheets to compile into css in dest; _partials are
+    /// only imported
+    #[arg(long)]
+    pub styles_dir: Option<String>,
+
+    /// Put a content hash in the names of copied static files, resolved by {{asset "style.css"}}
+    #[arg(long)]
+    #[serde(default)]
+    pub fingerprint: bool,
+
+    /// Indent the generated html so it's readable and diffs cleanly
+    #[arg(long)]
+    #[serde(default)]
+    pub prettify: bool,
+
+    /// Strip comments and collapse whitespace in the generated html so pages are smaller
+    #[arg(long, conflicts_with = "prettify")]
+    #[serde(default)]
+    pub minify: bool,
+
+    /// Which kind of build this is, e.g. staging, as {{build.profile}} in templates; production
+    /// unless set, or development for serve
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    /// End each html page with a comment naming its source file, template, build time and
+    /// commit
+    #[arg(long)]
+    #[serde(default)]
+    pub source_comments: bool,
+
+    /// Title for the site
+    #[arg(short, long, default_value = "a blog")]
+    pub title: String,
+
+    /// How long should entries be in the RSS feed
+    #[arg(long)]
+    pub truncate: Option<u32>,
+
+    /// Description for the site
+    #[arg(long)]
+    pub description: Option<String>,
+
+    /// URL for the site
+    #[arg(short, long)]
+    pub url: Option<String>,
+
+    /// Author for site
+    #[arg(short, long)]
+    pub author: Option<String>,
+
+    /// Authors posts can name by key in their front matter, each with a page of their posts
+    /// when the templates have an author.hbs
+    #[arg(skip)]
+    #[serde(default)]
+    pub authors: BTreeMap<String, AuthorConfig>,
+
+    /// Language of the site's pages, e.g. en, unless a post sets its own
+    #[arg(long)]
+    pub lang: Option<String>,
+
+    /// Languages the site is written in; posts in each besides lang, set in their front
+    /// matter or named like post.de.md, get their own index and feeds under de/
+    #[arg(skip)]
+    #[serde(default)]
+    pub languages: Vec<String>,
+
+    /// Text direction of the site's pages, ltr or rtl, unless a post sets its own
+    #[arg(long)]
+    pub dir: Option<String>,
+
+    /// Image shown when a page is shared, e.g. /images/card.png, unless the page has its own
+    #[arg(long)]
+    pub share_image: Option<String>,
+
+    /// URL of the license content is published under, unless a post sets its own
+    #[arg(long)]
+    pub license: Option<String>,
+
+    /// Use cached remote data instead of fetching it
+    #[arg(long)]
+    #[serde(default)]
+    pub offline: bool,
+
+    /// Build untrusted content: no raw HTML in posts, no remote data or webmentions, and planet
+    /// feeds only from the cache
+    #[arg(long)]
+    #[serde(default)]
+    pub sandbox: bool,
+
+    /// Print the version
+    #[arg(short = 'V', long)]
+    #[serde(skip)]
+    pub version: bool,
+
+    /// With --version, print the version and the optional features and commands this build
+    /// has as JSON
+    #[arg(long, requires = "version")]
+    #[serde(skip)]
+    pub json: bool,
+
+    /// Add <meta name="generator"> naming site-gen and its version to every page
+    #[arg(long)]
+    #[serde(default)]
+    pub generator_meta: bool,
+
+    /// Ignore the build cache and render every page again
+    #[arg(long, global = true)]
+    #[serde(skip)]
+    pub force: bool,
+
+    /// Only print warnings and errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    #[serde(skip)]
+    pub quiet: bool,
+
+    /// Also print every post parsed and file written
+    #[arg(short, long, global = true)]
+    #[serde(skip)]
+    pub verbose: bool,
+
+    /// How progress, warnings and errors are printed to stderr
+    #[arg(long, value_enum, global = true, default_value_t)]
+    #[serde(skip)]
+    pub log_format: LogFormat,
+
+    #[arg(skip)]
+    #[serde(skip)]
+    pub dry_run: bool,
+
+    /// Keep building after a page fails to render and report every failure at the end
+    #[arg(long, global = true)]
+    #[serde(default)]
+    pub keep_going: bool,
+
+    /// Fail the build when a page is over one of the [budgets] or has a broken link rather
+    /// than only warning
+    #[arg(long)]
+    #[serde(default)]
+    pub strict: bool,
+
+    /// After building, report links to pages and #anchors on the site that don't exist
+    #[arg(long, global = true)]
+    #[serde(default)]
+    pub check_links: bool,
+
+    /// Write the context a page (e.g. index.html) is rendered with to debug-context.json
+    #[arg(long, global = true)]
+    #[serde(skip)]
+    pub debug_context: Option<String>,
+
+    /// Accept posts without front matter, dated by their file's mtime or first git commit
+    #[arg(long, value_enum)]
+    pub bare_posts: Option<BareDate>,
+
+    /// Title posts whose header has no title after the heading that opens their body
+    #[arg(long)]
+    #[serde(default)]
+    pub title_from_heading: bool,
+
+    /// Write posts from subdirectories of src to the same subdirectories of dest instead of
+    /// flattening them into dest
+    #[arg(long)]
+    #[serde(default)]
+    pub nested_output: bool,
+
+    /// File recording every url each post with an `id:` has had, e.g. slugs.lock; old urls
+    /// redirect to the current one
+    #[arg(long)]
+    pub slugs_lock: Option<String>,
+
+    /// Pattern for post urls using {year}, {month}, {day} and {slug} (the file name without its
+    /// extension), e.g. "{year}/{month}/{slug}.html" or "{slug}/"
+    #[arg(long)]
+    pub permalink: Option<String>,
+
+    /// Render `[^name]` footnotes as sidenotes in the margin beside the text citing them
+    #[arg(long)]
+    #[serde(default)]
+    pub sidenotes: bool,
+
+    /// Keep the last two words of headings and short words like "v" in Czech off their own line
+    #[arg(long)]
+    #[serde(default)]
+    pub no_widows: bool,
+
+    /// Directory for cached remote data
+    #[arg(long)]
+    pub cache_dir: Option<String>,
+
+    /// Besides a page per year, give each month of posts its own archive page,
+    /// archive/2024/03.html; archives are built when there's an archive template
+    #[arg(long)]
+    #[serde(default)]
+    pub archive_months: bool,
+
+    /// Generate digest pages summarizing posts per period
+    #[arg(long, value_enum)]
+    pub digest: Option<DigestPeriod>,
+
+    /// Generate a page and feed of posts added or revised since earlier builds
+    #[arg(long)]
+    #[serde(default)]
+    pub changes: bool,
+
+    /// TOML data file of bookmarks to render as a link list and feed
+    #[arg(long)]
+    pub bookmarks: Option<String>,
+
+    /// Highlight fenced code blocks at build time with this theme, e.g. base16-ocean.dark
+    #[arg(long)]
+    pub highlight_theme: Option<String>,
+
+    /// BibTeX file of works posts can cite with [@key]
+    #[arg(long)]
+    pub bibliography: Option<String>,
+
+    /// Directory of per-post comment files
+    #[arg(long)]
+    pub comments_dir: Option<String>,
+
+    /// Tags to rename to a canonical tag, e.g. recipe = "recipes"
+    #[arg(skip)]
+    #[serde(default)]
+    pub tag_aliases: BTreeMap<String, String>,
+
+    /// Abbreviations to explain wherever they appear in posts, e.g. HTML = "HyperText Markup
+    /// Language"; a post can add its own with `*[HTML]: HyperText Markup Language` lines
+    #[arg(skip)]
+    #[serde(default)]
+    pub abbreviations: BTreeMap<String, String>,
+
+    /// Share images for a tag's page and its posts without their own, e.g. recipes =
+    /// "/images/recipes.png"
+    #[arg(skip)]
+    #[serde(default)]
+    pub tag_share_images: BTreeMap<String, String>,
+
+    /// Words that mustn't end a line, by language, e.g. cs = ["a", "i", "k"], replacing the
+    /// built in rules for that language
+    #[arg(skip)]
+    #[serde(default)]
+    pub short_words: BTreeMap<String, Vec<String>>,
+
+    #[arg(skip)]
+    #[serde(default)]
+    pub remote: BTreeMap<String, RemoteSource>,
+
+    #[arg(skip)]
+    pub webmentions: Option<WebmentionConfig>,
+
+    /// External feeds to aggregate into planet.html and planet.rss
+    #[arg(skip)]
+    pub planet: Option<PlanetConfig>,
+
+    /// Where the deploy command uploads dest
+    #[arg(skip)]
+    pub deploy: Option<DeployConfig>,
+
+    /// Templates, listing sizes and feed settings for sections, keyed by the section's
+    /// directory under src
+    #[arg(skip)]
+    #[serde(default)]
+    pub sections: BTreeMap<String, SectionConfig>,
+
+    /// Front matter keys of the site's own, for the schema printed by `schema`
+    #[arg(skip)]
+    #[serde(default)]
+    pub fields: BTreeMap<String, CustomField>,
+
+    /// Write an iCalendar file of published, draft and future dated posts here, for
+    /// editorial calendars; it lists drafts, so keep it out of dest unless that's fine
+    #[arg(long)]
+    pub calendar: Option<String>,
+
+    /// Which markdown extensions posts are parsed with
+    #[arg(skip)]
+    #[serde(default)]
+    pub markdown: MarkdownConfig,
+
+    /// Third-party embeds to replace with click-to-load placeholders, keyed by provider
+    #[arg(skip)]
+    #[serde(default)]
+    pub embeds: BTreeMap<String, EmbedProvider>,
+
+    /// Inline the stylesheet rules each page needs before scrolling and load the rest
+    /// without blocking rendering
+    #[arg(skip)]
+    pub critical_css: Option<CriticalConfig>,
+
+    /// Drop the stylesheet rules no generated page uses from production builds
+    #[arg(skip)]
+    pub prune_css: Option<PruneConfig>,
+
+    /// Largest each built page's html, and everything it loads from the site, may be
+    #[arg(skip)]
+    pub budgets: Option<BudgetConfig>,
+
+    /// Fonts to subset to the characters the site uses and serve from dest as woff2
+    #[arg(skip)]
+    #[serde(default)]
+    pub fonts: Vec<FontConfig>,
+
+    /// Resize images from the static dir that posts use, and give them srcsets
+    #[arg(skip)]
+    pub images: Option<ImageConfig>,
+
+    /// Write a search index of every post for client-side search
+    #[arg(skip)]
+    pub search: Option<SearchConfig>,
+
+    /// Pages built from post queries rather than the paginated index, keyed by name
+    #[arg(skip)]
+    #[serde(default)]
+    pub pages: BTreeMap<String, LandingPage>,
+}
+
+impl Opt {
+    /// Read a config file, returning a config per site when it has a `[sites]` table and
+    /// just the one otherwise.
+    pub fn load(path: &Path) -> Result<Vec<Opt>> {
+        let table: toml::Table =
+            toml::from_str(&fs::read_to_string(path)?).map_err(config::explain)?;
+        let sites = sites::resolve(table, &[])?;
+        Ok(sites.into_iter().map(|(_name, opts)| opts).collect())
+    }
+
+    /// A single site's config from TOML, with the defaults a config file gets.
+    pub fn from_toml(data: &str) -> Result<Opt> {
+        config::parse_opts(toml::from_str(data).map_err(config::explain)?)
+    }
+}
+
+fn default_entries() -> u8 {
+    10
+}
+
+fn default_words_per_minute() -> usize {
+    200
+}
+
+fn default_template_dir() -> String {
+    "templates".to_string()
+}
+
+fn default_helper_dir() -> String {
+    "helpers".to_string()
+}
+
+fn default_archetype_dir() -> String {
+    "archetypes".to_string()
+}
+
+fn default_src() -> String {
+    "content".to_string()
+}
+
+fn default_dest() -> String {
+    "public".to_string()
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum Action {
+    /// Build the site (the default when no command is given)
+    Build {
+        /// Only build these sites from the config's [sites] table
+        #[arg(long)]
+        site: Vec<String>,
+
+        /// Rebuild whenever a post, template or the config changes
+        #[arg(long)]
+        watch: bool,
+
+        /// Render everything but only report which files would be created, changed or
+        /// left stale
+        #[arg(long, conflicts_with = "watch")]
+        dry_run: bool,
+    },
+
+    /// Create a starter project: a config, working templates and a sample post
+    Init {
+        /// Directory to create the project in
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+
+    /// Delete the files builds wrote into dest, leaving anything else there alone
+    Clean {
+        /// Only delete files the last build no longer wrote, e.g. pages of renamed posts
+        #[arg(long)]
+        stale: bool,
+
+        /// Only clean these sites from the config's [sites] table
+        #[arg(long)]
+        site: Vec<String>,
+    },
+
+    /// Upload dest to the target in the config's [deploy] section
+    Deploy {
+        /// Only deploy these sites from the config's [sites] table
+        #[arg(long)]
+        site: Vec<String>,
+    },
+
+    /// Check the config, directories, templates and posts for problems before a build
+    Doctor,
+
+    /// Validate the config, templates and every post's front matter without writing
+    /// anything, printing only problems and failing if there are any, e.g. in a pre-commit hook
+    Check,
+
+    /// List every third-party domain the built site in dest loads scripts, stylesheets,
+    /// fonts, images or frames from
+    Audit {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Only audit these sites from the config's [sites] table
+        #[arg(long)]
+        site: Vec<String>,
+    },
+
+    /// Build the site, serve it locally and rebuild with live reload as files change
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = serve::DEFAULT_ADDR)]
+        addr: String,
+
+        /// Which site from the config's [sites] table to serve
+        #[arg(long)]
+        site: Option<String>,
+    },
+
+    /// Keep the site loaded and answer render, preview and metadata requests from editors
+    /// as newline delimited JSON-RPC over a local socket
+    Daemon {
+        /// Address to listen on
+        #[arg(long, default_value = daemon::DEFAULT_ADDR)]
+        addr: String,
+
+        /// Which site from the config's [sites] table to load
+        #[arg(long)]
+        site: Option<String>,
+    },
+
+    /// Validate the config file and print the effective configuration
+    ConfigCheck,
+
+    /// Show or edit the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigAction,
+    },
+
+    /// Create a new post, prompting for its details when no title is given
+    Create {
+        title: Option<String>,
+
+        /// Start from archetypes/<kind>.md instead of archetypes/post.md or the default header
+        #[arg(long)]
+        kind: Option<String>,
+
+        /// Which site from the config's [sites] table to add the post to
+        #[arg(long)]
+        site: Option<String>,
+    },
+
+    /// List every tag with how many posts use it
+    Tags {
+        /// Print the list as JSON for editor integrations
+        #[arg(long)]
+        json: bool,
+
+        /// Report tags that differ only by case, plural or a typo
+        #[arg(long)]
+        duplicates: bool,
+
+        /// Rewrite source files using the configured tag_aliases
+        #[arg(long)]
+        fix: bool,
+
+        /// Which site from the config's [sites] table to read
+        #[arg(long)]
+        site: Option<String>,
+    },
+
+    /// Edit a front matter key across every post matching the filters
+    Meta {
+        #[command(subcommand)]
+        command: MetaAction,
+
+        #[command(flatten)]
+        filter: PostFilter,
+
+        /// List the files that would change without writing them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Which site from the config's [sites] table to edit
+        #[arg(long)]
+        site: Option<String>,
+    },
+
+    /// Print a JSON Schema of the front matter posts accept, for editors to validate with
+    Schema {
+        /// Which site from the config's [sites] table to describe
+        #[arg(long)]
+        site: Option<String>,
+    },
+
+    /// Print a shell completion script
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+
+    /// Print a man page
+    Manpage,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum ConfigAction {
+    /// Print the effective config as TOML
+    Print,
+
+    /// Set a (dotted) key in the config file, preserving comments
+    Set { key: String, value: String },
+}
+
+#[derive(Debug, Error)]
+enum ProgramError {
+    #[error("You must provide a url in either the config or the command-line options")]
+    MissingOption,
+
+    #[error("This command needs a config file, pass one with --config")]
+    NoConfig,
+
+    #[error("{0} site(s) failed to build")]
+    BuildFailed(usize),
+}
+
+// everything the site-gen binary does, from parsing its arguments on
+pub fn main() -> Result<()> {
+    let mut opts = Opt::parse();
+    let action = opts.action.take();
+    let level = match (opts.quiet, opts.verbose) {
+        (true, _) => log::LevelFilter::Warn,
+        (_, true) => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Info,
+    };
+    logger::init(level, opts.log_format)?;
+
+    if opts.version {
+        print_version(opts.json)?;
+        return Ok(());
+    }
+
+    match &action {
+        Some(Action::Completions { shell }) => {
+            let mut cmd = Opt::command();
+            let name = cmd.get_name().to_string();
+            clap_complete::generate(*shell, &mut cmd, name, &mut io::stdout());
+            return Ok(());
+        }
+        Some(Action::Manpage) => {
+            clap_mangen::Man::new(Opt::command()).render(&mut io::stdout())?;
+            return Ok(());
+        }
+        Some(Action::Init { dir }) => {
+            for file in init::init(dir)? {
+                println!("Created {:?}", file);
+            }
+            println!(
+                "Build the site with: site-gen --config {} build",
+                init::CONFIG_FILE
+            );
+            return Ok(());
+        }
+        _ => (),
+    }
+
+    if let Some(Action::Config {
+        command: ConfigAction::Set { key, value },
+    }) = &action
+    {
+        let path = opts.config.as_ref().ok_or(ProgramError::NoConfig)?;
+        let updated = config::set(&fs::read_to_string(path)?, key, value)?;
+        // refuse to write a config that would no longer load
+        sites::resolve(toml::from_str(&updated).map_err(config::explain)?, &[])?;
+        fs::write(path, updated)?;
+        info!("Set {} in {}", key, path);
+        return Ok(());
+    }
+
+    match &action {
+        Some(Action::Doctor) => return doctor::run(opts, doctor::Mode::Doctor),
+        Some(Action::Check) => return doctor::run(opts, doctor::Mode::Check),
+        _ => (),
+    }
+
+    let only = match &action {
+        Some(Action::Build { site, .. })
+        | Some(Action::Clean { site, .. })
+        | Some(Action::Audit { site, .. })
+        | Some(Action::Deploy { site }) => site.clone(),
+        Some(Action::Create { site, .. })
+        | Some(Action::Tags { site, .. })
+        | Some(Action::Meta { site, .. })
+        | Some(Action::Serve { site, .. })
+        | Some(Action::Daemon { site, .. })
+        | Some(Action::Schema { site }) => site.iter().cloned().collect(),
+        _ => vec![],
+    };
+
+    let serve = match &action {
+        Some(Action::Serve { addr, .. }) => Some(addr.clone()),
+        _ => None,
+    };
+    if serve.is_none() && !matches!(&action, Some(Action::Build { watch: true, .. })) {
+        run(opts, &action, &only)?;
+        return Ok(());
+    }
+
+    let version = Arc::new(AtomicUsize::new(0));
+    let mut serving = false;
+    let mut watched = vec![];
+    loop {
+        match run(opts.clone(), &action, &only) {
+            Ok(built) => {
+                watched = opts.config.iter().map(PathBuf::from).collect();
+                for site in built.iter() {
+                    watched.push(PathBuf::from(&site.src));
+                    watched.push(PathBuf::from(&site.template_dir));
+                    watched.push(PathBuf::from(&site.helper_dir));
+                    watched.extend(site.static_dir.iter().map(PathBuf::from));
+                    watched.extend(site.styles_dir.iter().map(PathBuf::from));
+                }
+                if let (Some(addr), Some(site), false) = (&serve, built.first(), serving) {
+                    serve::start(PathBuf::from(&site.dest), addr, version.clone())?;
+                    serving = true;
+                }
+                version.fetch_add(1, Ordering::SeqCst);
+            }
+            // keep watching, the next save may fix a broken config or template
+            Err(e) => error!("{:?}", e),
+        }
+        if watched.is_empty() {
+            watched = opts
+                .config
+                .iter()
+                .chain([&opts.src, &opts.template_dir])
+                .map(PathBuf::from)
+                .collect();
+        }
+        for file in watch::wait_for_change(&watched) {
+            info!("{:?} changed, rebuilding", file);
+        }
+    }
+}
+
+// the version, or for tooling everything this build can do as json
+fn print_version(json: bool) -> Result<()> {
+    if !json {
+        println!("{} {}", generator::NAME, generator::VERSION);
+        return Ok(());
+    }
+    let commands: Vec<String> = Opt::command()
+        .get_subcommands()
+        .map(|c| c.get_name().to_string())
+        .collect();
+    let version = serde_json::json!({
+        "name": generator::NAME,
+        "version": generator::VERSION,
+        "features": generator::features(),
+        "commands": commands,
+    });
+    println!("{}", serde_json::to_string_pretty(&version)?);
+    Ok(())
+}
+
+// run an action against every configured site, returning the options of each site built
+fn run(opts: Opt, action: &Option<Action>, only: &[String]) -> Result<Vec<Opt>> {
+    let offline = opts.offline;
+    let sandbox = opts.sandbox;
+    let debug_context = opts.debug_context.clone();
+    let force = opts.force;
+    let strict = opts.strict;
+    let keep_going = opts.keep_going;
+    let check_links = opts.check_links;
+    let mut built = vec![];
+    let mut failed = 0;
+
+    let mut peers = vec![];
+    let configs = if let Some(config) = &opts.config {
+        let data = fs::read_to_string(config)?;
+        let table: toml::Table = toml::from_str(&data).map_err(config::explain)?;
+        peers = sites::peers(&table)?;
+        sites::resolve(table, only)?
+    } else {
+        vec![(None, opts)]
+    };
+
+    for (name, mut config_data) in configs {
+        config_data.offline |= offline;
+        config_data.sandbox |= sandbox;
+        config_data.debug_context = debug_context.clone();
+        config_data.force = force;
+        config_data.strict |= strict;
+        config_data.keep_going |= keep_going;
+        config_data.check_links |= check_links;
+        config_data.dry_run = matches!(action, Some(Action::Build { dry_run: true, .. }));
+        if let (Some(Action::Serve { .. } | Action::Daemon { .. }), None) =
+            (action, &config_data.profile)
+        {
+            config_data.profile = Some("development".to_string());
+        }
+
+        if let Some(Action::Create { title, kind, .. }) = action {
+            let archetype =
+                create::archetype(Path::new(&config_data.archetype_dir), kind.as_deref())?;
+            let path = create::create(
+                Path::new(&config_data.src),
+                title.clone(),
+                archetype.as_deref(),
+            )?;
+            info!("Created {:?}", path);
+            return Ok(built);
+        }
+
+        if let Some(Action::Tags {
+            json,
+            duplicates,
+            fix,
+            ..
+        }) = action
+        {
+            let src = Path::new(&config_data.src);
+            if *fix {
+                for file in tags::fix(src, &config_data.tag_aliases)? {
+                    info!("Updated tags in {:?}", file);
+                }
+            }
+            let counts = front_matter::tag_counts(src)?;
+            if *duplicates {
+                println!("{}", tags::duplicates_report(&counts));
+            } else {
+                println!("{}", tags::report(&counts, *json)?);
+            }
+            return Ok(built);
+        }
+
+        if let Some(Action::Deploy { .. }) = action {
+            deploy::deploy(config_data.deploy.as_ref(), &config_data.dest)?;
+            continue;
+        }
+
+        if let Some(Action::Clean { stale, .. }) = action {
+            let cache_dir = remote::cache_dir(config_data.cache_dir.as_deref());
+            for file in clean::clean(Path::new(&config_data.dest), &cache_dir, *stale)? {
+                info!("Removed {:?}", file);
+            }
+            continue;
+        }
+
+        if let Some(Action::Meta {
+            command,
+            filter,
+            dry_run,
+            ..
+        }) = action
+        {
+            let verb = if *dry_run { "Would update" } else { "Updated" };
+            for file in meta::run(Path::new(&config_data.src), command, filter, *dry_run)? {
+                info!("{} {:?}", verb, file);
+            }
+            return Ok(built);
+        }
+
+        if let Some(Action::Schema { .. }) = action {
+            let schema = schema::front_matter(&config_data.title, &config_data.fields);
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+            return Ok(built);
+        }
+
+        if config_data.url.is_none() {
+            return Err(ProgramError::MissingOption.into());
+        }
+
+        if let Some(Action::ConfigCheck)
+        | Some(Action::Config {
+            command: ConfigAction::Print,
+        }) = action
+        {
+            if let Some(name) = &name {
+                println!("# site: {}", name);
+            }
+            println!("{}", toml::to_string_pretty(&config_data)?);
+            continue;
+        }
+
+        if let Some(Action::Audit { json, .. }) = action {
+            let url = url::Url::parse(config_data.url.as_deref().unwrap_or_default())?;
+            let host = url.host_str().unwrap_or_default();
+            let found = audit::audit(Path::new(&config_data.dest), host)?;
+            if let Some(name) = &name {
+                println!("# site: {}", name);
+            }
+            println!("{}", audit::report(&found, *json)?);
+            continue;
+        }
+
+        if let Some(Action::Daemon { addr, .. }) = action {
+            daemon::run(config_data, addr)?;
+            return Ok(built);
+        }
+
+        if let Some(name) = &name {
+            info!("Building site {}", name);
+        }
+        built.push(config_data.clone());
+        let dry_run = config_data.dry_run;
+        let mut b = Builder::new(config_data)?;
+        if let Some(name) = name {
+            b.syndicate_from(name, peers.clone());
+        }
+
+        match b.build() {
+            Ok(_a) if dry_run => info!("Dry run finished, nothing was written"),
+            Ok(_a) => info!("Blog built!"),
+            Err(e) => {
+                error!("{:?}", e);
+                failed += 1;
+            }
+        };
+    }
+    // watching carries on after a failed build, but a one-off build has to say it failed
+    let watching = matches!(
+        action,
+        Some(Action::Serve { .. }) | Some(Action::Build { watch: true, .. })
+    );
+    if failed > 0 && !watching {
+        return Err(ProgramError::BuildFailed(failed).into());
+    }
+    if let Some(Action::ConfigCheck) = action {
+        println!("Config OK");
+    }
+    Ok(built)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Opt::command().debug_assert();
+    }
+
+    #[test]
+    fn build_flags_follow_the_subcommand() -> Result<()> {
+        let opts = Opt::try_parse_from([
+            "site-gen",
+            "-c",
+            "site.toml",
+            "build",
+            "--keep-going",
+            "--check-links",
+        ])?;
+        assert!(opts.keep_going);
+        assert!(opts.check_links);
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use color_eyre::Result;
+use comrak::{markdown_to_html, ComrakOptions};
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::{get_entries, parse_date};
+
+pub const DEFAULT_COMMENTS_DIR: &str = "comments";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comment {
+    pub author: String,
+    pub url: Option<String>,
+    pub date: Option<String>,
+    /// Where the comment came from, e.g. "email" or "webmention"
+    pub source: Option<String>,
+    pub content: String,
+}
+
+// comments live in <dir>/<post slug>/*.toml, one comment per file
+pub fn load_all(dir: &Path) -> Result<BTreeMap<String, Vec<Comment>>> {
+    let mut comments = BTreeMap::new();
+    if !dir.is_dir() {
+        return Ok(comments);
+    }
+
+    for post_dir in fs::read_dir(dir)? {
+        let post_dir = post_dir?;
+        if !post_dir.file_type()?.is_dir() {
+            continue;
+        }
+        let slug = post_dir.file_name().to_string_lossy().to_string();
+        let mut post_comments = vec![];
+        for file in get_entries(&post_dir.path())? {
+            if file.extension().and_then(|e| e.to_str()) != Some("toml") {
+                continue;
+            }
+            post_comments.push(parse(&fs::read_to_string(&file)?)?);
+        }
+        post_comments.sort_by_key(|c| c.date.as_deref().map(parse_date));
+        comments.insert(slug, post_comments);
+    }
+    Ok(comments)
+}
+
+// comment bodies are untrusted, so raw HTML is always escaped
+fn parse(data: &str) -> Result<Comment> {
+    let mut comment: Comment = toml::from_str(data)?;
+    comment.content = markdown_to_html(&comment.content, &ComrakOptions::default());
+    Ok(comment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_comment_files() -> Result<()> {
+        let comment = parse(
+            r#"
+            author = "Jane"
+            url = "https://jane.example"
+            date = "2024-03-12T10:00:00-07:00"
+            source = "email"
+            content = "Nice post!"
+            "#,
+        )?;
+        assert_eq!(comment.author, "Jane");
+        assert_eq!(comment.source.as_deref(), Some("email"));
+        assert!(comment.content.contains("Nice post!"));
+        Ok(())
+    }
+
+    #[test]
+    fn missing_directory_has_no_comments() -> Result<()> {
+        let comments = load_all(Path::new("fixtures/no-comments-here"))?;
+        assert!(comments.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use color_eyre::Result;
+use thiserror::Error;
+use toml_edit::DocumentMut;
+
+use crate::Opt;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Unknown config key `{key}`{hint}")]
+    UnknownKey { key: String, hint: String },
+
+    #[error("`{0}` is not a table in the config")]
+    NotATable(String),
+
+    #[error("An empty key can't be set")]
+    EmptyKey,
+}
+
+// deserialize a config table, turning serde's unknown field errors into a did-you-mean hint
+pub fn parse_opts(table: toml::Table) -> Result<Opt> {
+    match toml::Value::Table(table).try_into() {
+        Ok(opts) => Ok(opts),
+        Err(e) => Err(explain(e)),
+    }
+}
+
+pub fn explain(e: toml::de::Error) -> color_eyre::Report {
+    let message = e.message().to_string();
+    match unknown_field(&message) {
+        Some((key, expected)) => {
+            let hint = match suggest(&key, &expected) {
+                Some(s) => format!(", did you mean `{}`?", s),
+                None => format!(", expected one of: {}", expected.join(", ")),
+            };
+            ConfigError::UnknownKey { key, hint }.into()
+        }
+        None => e.into(),
+    }
+}
+
+// pull the key and the accepted keys out of "unknown field `x`, expected one of `a`, `b`"
+fn unknown_field(message: &str) -> Option<(String, Vec<String>)> {
+    let rest = message.strip_prefix("unknown field `")?;
+    let (key, rest) = rest.split_once('`')?;
+    let expected = rest
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .map(String::from)
+        .collect();
+    Some((key.to_string(), expected))
+}
+
+fn suggest<'a>(key: &str, candidates: &'a [String]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|c| (strsim::damerau_levenshtein(key, c), c))
+        .filter(|(distance, c)| *distance <= (c.len() / 3).max(2))
+        .min_by_key(|(distance, _c)| *distance)
+        .map(|(_distance, c)| c.as_str())
+}
+
+// set a dotted key (e.g. `sites.blog.title`) in a config file, leaving comments and layout alone
+pub fn set(data: &str, key: &str, value: &str) -> Result<String> {
+    let mut doc = data.parse::<DocumentMut>()?;
+    // bare words are strings, anything else that parses as TOML keeps its type
+    let value = match value.parse::<toml_edit::Value>() {
+        Ok(v) => v,
+        Err(_e) => toml_edit::Value::from(value),
+    };
+
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let last = parts
+        .pop()
+        .filter(|k| !k.is_empty())
+        .ok_or(ConfigError::EmptyKey)?;
+    let mut table = doc.as_table_mut();
+    for part in parts {
+        table = table
+            .entry(part)
+            .or_insert(toml_edit::table())
+            .as_table_mut()
+            .ok_or(ConfigError::NotATable(part.to_string()))?;
+    }
+
+    match table.get_mut(last).and_then(|i| i.as_value_mut()) {
+        Some(existing) => {
+            let decor = existing.decor().clone();
+            *existing = value;
+            *existing.decor_mut() = decor;
+        }
+        None => {
+            table.insert(last, toml_edit::value(value));
+        }
+    }
+    Ok(doc.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggests_close_keys() {
+        let table: toml::Table = toml::from_str("templat_dir = \"theme\"").unwrap();
+        let err = parse_opts(table).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "Unknown config key `templat_dir`, did you mean `template_dir`?"
+        );
+    }
+
+    #[test]
+    fn lists_keys_when_nothing_is_close() {
+        let expected = vec!["title".to_string(), "url".to_string()];
+        assert_eq!(suggest("zzzzzzzz", &expected), None);
+        assert_eq!(suggest("titel", &expected), Some("title"));
+    }
+
+    #[test]
+    fn sets_values_and_keeps_comments() -> Result<()> {
+        let data = "# my blog\ntitle = \"old\" # the title\nentries = 10\n";
+        let updated = set(data, "title", "new")?;
+        assert_eq!(
+            updated,
+            "# my blog\ntitle = \"new\" # the title\nentries = 10\n"
+        );
+        let updated = set(&updated, "entries", "5")?;
+        assert!(updated.contains("entries = 5\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn sets_nested_keys() -> Result<()> {
+        let updated = set(
+            "title = \"blog\"\n",
+            "sites.blog.url",
+            "https://example.com",
+        )?;
+        let table: toml::Table = toml::from_str(&updated)?;
+        assert_eq!(
+            table["sites"]["blog"]["url"].as_str(),
+            Some("https://example.com")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parses_serde_messages() {
+        let (key, expected) =
+            unknown_field("unknown field `tittle`, expected one of `title`, `url`").unwrap();
+        assert_eq!(key, "tittle");
+        assert_eq!(expected, vec!["title", "url"]);
+    }
+}
@@ -0,0 +1,191 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, FixedOffset, Local, SecondsFormat};
+use color_eyre::Result;
+use inquire::autocompletion::{Autocomplete, Replacement};
+use inquire::{Confirm, CustomUserError, Text};
+use slug::slugify;
+use thiserror::Error;
+
+use crate::front_matter::{self, FrontMatter};
+use crate::meta::{self, MetaAction};
+
+#[derive(Debug, Error)]
+enum CreateError {
+    #[error("{0:?} already exists")]
+    Exists(PathBuf),
+
+    #[error("A post needs a title")]
+    NoTitle,
+
+    #[error("There's no archetype {0:?}")]
+    NoArchetype(PathBuf),
+}
+
+// the kind of post written when create isn't given one
+const DEFAULT_KIND: &str = "post";
+
+// the template a new post of kind starts from: the one it names, else post.md if there is one
+pub fn archetype(dir: &Path, kind: Option<&str>) -> Result<Option<String>> {
+    let path = dir.join(format!("{}.md", kind.unwrap_or(DEFAULT_KIND)));
+    match (fs::read_to_string(&path), kind) {
+        (Ok(archetype), _) => Ok(Some(archetype)),
+        (Err(_e), Some(_kind)) => Err(CreateError::NoArchetype(path).into()),
+        (Err(_e), None) => Ok(None),
+    }
+}
+
+// an archetype with its {title}, {date}, {slug} and {id} filled in, given an id if it has a
+// header without one
+fn fill(archetype: &str, title: &str, date: &DateTime<FixedOffset>) -> String {
+    let post = archetype
+        .replace("{title}", title)
+        .replace("{date}", &date.to_rfc3339_opts(SecondsFormat::Secs, false))
+        .replace("{slug}", &slugify(title))
+        .replace("{id}", &front_matter::new_id());
+    meta::edit(&post, &MetaAction::AssignIds).unwrap_or(post)
+}
+
+// write a new post into src, prompting for its metadata when no title is given; an archetype
+// only needs a title
+pub fn create(src: &Path, title: Option<String>, archetype: Option<&str>) -> Result<PathBuf> {
+    let date = DateTime::<FixedOffset>::from(Local::now());
+    if let Some(archetype) = archetype {
+        let title = match title {
+            Some(title) => title,
+            None => Text::new("Title:").prompt()?,
+        };
+        if title.trim().is_empty() {
+            return Err(CreateError::NoTitle.into());
+        }
+        return write_post(src, title.trim(), &fill(archetype, title.trim(), &date));
+    }
+
+    let front_matter = match title {
+        Some(title) => FrontMatter {
+            title,
+            date: Some(date),
+            id: Some(front_matter::new_id()),
+            ..Default::default()
+        },
+        None => {
+            let tags = front_matter::tag_counts(src).unwrap_or_default();
+            let mut front_matter = wizard(tags.into_keys().collect())?;
+            front_matter.date = Some(date);
+            front_matter.id = Some(front_matter::new_id());
+            front_matter
+        }
+    };
+
+    write_post(src, &front_matter.title, &(front_matter.to_header() + "\n"))
+}
+
+fn write_post(src: &Path, title: &str, contents: &str) -> Result<PathBuf> {
+    let path = src.join(format!("{}.md", slugify(title)));
+    if path.exists() {
+        return Err(CreateError::Exists(path).into());
+    }
+    fs::DirBuilder::new().recursive(true).create(src)?;
+    fs::write(&path, contents)?;
+    Ok(path)
+}
+
+fn wizard(tags: Vec<String>) -> Result<FrontMatter> {
+    let title = Text::new("Title:").prompt()?;
+    if title.trim().is_empty() {
+        return Err(CreateError::NoTitle.into());
+    }
+    let tag_list = Text::new("Tags:")
+        .with_help_message("comma separated, tab to complete existing tags")
+        .with_autocomplete(TagCompleter { tags })
+        .prompt()?;
+    let description = Text::new("Description:").prompt()?;
+    let draft = Confirm::new("Draft?").with_default(true).prompt()?;
+
+    Ok(FrontMatter {
+        title: title.trim().to_string(),
+        tags: front_matter::list(&tag_list),
+        description: Some(description).filter(|d| !d.trim().is_empty()),
+        draft,
+        ..Default::default()
+    })
+}
+
+#[derive(Clone)]
+struct TagCompleter {
+    tags: Vec<String>,
+}
+
+impl Autocomplete for TagCompleter {
+    fn get_suggestions(&mut self, input: &str) -> Result<Vec<String>, CustomUserError> {
+        Ok(complete_tags(input, &self.tags))
+    }
+
+    fn get_completion(
+        &mut self,
+        _input: &str,
+        highlighted_suggestion: Option<String>,
+    ) -> Result<Replacement, CustomUserError> {
+        Ok(highlighted_suggestion)
+    }
+}
+
+// suggest the whole input with its last, partially typed tag completed
+fn complete_tags(input: &str, tags: &[String]) -> Vec<String> {
+    let (done, current) = match input.rsplit_once(',') {
+        Some((done, current)) => (format!("{}, ", done.trim_end()), current.trim()),
+        None => (String::new(), input.trim()),
+    };
+    let used = front_matter::list(&done);
+    tags.iter()
+        .filter(|t| t.starts_with(current) && !used.contains(t))
+        .map(|t| format!("{}{}", done, t))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_archetypes() -> Result<()> {
+        let link = "---\ntitle: {title}\ndate: {date}\ntags: links\n---\n[{title}](/{slug})\n";
+        let date = crate::helpers::parse_date("2024-03-05T10:00:00-07:00");
+        let post = fill(link, "A Link", &date);
+        let id = front_matter::parse(&post).id.unwrap();
+        assert_eq!(
+            post,
+            format!(
+                "---\ntitle: A Link\ndate: 2024-03-05T10:00:00-07:00\ntags: links\nid: {}\n---\n[A Link](/a-link)\n",
+                id
+            )
+        );
+        assert_eq!(
+            fill("---\nid: {id}\n---\n", "x", &date)
+                .matches("id:")
+                .count(),
+            1
+        );
+
+        let dir = Path::new("no-archetypes-here");
+        assert_eq!(archetype(dir, None)?, None);
+        assert!(archetype(dir, Some("link")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn completes_the_last_tag() {
+        let tags = vec![
+            "recipes".to_string(),
+            "rust".to_string(),
+            "travel".to_string(),
+        ];
+        assert_eq!(complete_tags("ru", &tags), vec!["rust"]);
+        assert_eq!(
+            complete_tags("travel, r", &tags),
+            vec!["travel, recipes", "travel, rust"]
+        );
+        assert_eq!(complete_tags("rust,r", &tags), vec!["rust, recipes"]);
+    }
+}
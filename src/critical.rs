@@ -0,0 +1,247 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::prettify::{attribute, tag_end, tag_name};
+
+// the `[critical_css]` table: writing it, even empty, turns inlining on
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CriticalConfig {
+    /// How many bytes from the start of each page's <body> count as above the fold
+    #[serde(default = "default_fold")]
+    pub fold: usize,
+}
+
+fn default_fold() -> usize {
+    10_000
+}
+
+// the elements, classes and ids some html uses, plus names to count as used regardless
+#[derive(Debug, Default)]
+pub struct UsedSelectors {
+    tags: BTreeSet<String>,
+    classes: BTreeSet<String>,
+    ids: BTreeSet<String>,
+    // names that are always used; one ending in * is a prefix
+    safelist: Vec<String>,
+}
+
+impl UsedSelectors {
+    pub fn new(safelist: &[String]) -> UsedSelectors {
+        let mut used = UsedSelectors {
+            safelist: safelist.to_vec(),
+            ..Default::default()
+        };
+        used.tags.extend(["html", "body"].map(String::from));
+        used
+    }
+
+    // the part of a page shown before scrolling
+    fn above_the_fold(html: &str, fold: usize) -> UsedSelectors {
+        let start = html.find("<body").unwrap_or(0);
+        let mut end = (start + fold).min(html.len());
+        while !html.is_char_boundary(end) {
+            end -= 1;
+        }
+        let mut found = UsedSelectors::new(&[]);
+        found.add_html(&html[start..end]);
+        found
+    }
+
+    pub fn add_html(&mut self, html: &str) {
+        let mut rest = html;
+        while let Some(open) = rest.find('<') {
+            rest = &rest[open..];
+            let tag = &rest[..tag_end(rest)];
+            rest = &rest[tag.len()..];
+            let name = tag_name(tag);
+            if name.is_empty() || name.starts_with('/') {
+                continue;
+            }
+            self.tags.insert(name);
+            let classes = attribute(tag, "class").unwrap_or_default();
+            self.classes
+                .extend(classes.split_whitespace().map(String::from));
+            self.ids.extend(attribute(tag, "id").map(String::from));
+        }
+    }
+
+    fn has(&self, found: &BTreeSet<String>, name: &str) -> bool {
+        found.contains(name)
+            || self
+                .safelist
+                .iter()
+                .any(|safe| match safe.strip_suffix('*') {
+                    Some(prefix) => name.starts_with(prefix),
+                    None => name == safe,
+                })
+    }
+
+    // whether the element a selector styles could be in the html; combinators, pseudo
+    // classes and attribute selectors are ignored, so this errs towards keeping a rule
+    fn matches(&self, selector: &str) -> bool {
+        let subject = selector
+            .rsplit(|c: char| c.is_whitespace() || c == '>' || c == '+' || c == '~')
+            .find(|s| !s.is_empty())
+            .unwrap_or_default();
+        let subject = subject.split([':', '[']).next().unwrap_or_default();
+
+        let parts = subject.split_inclusive(['.', '#']);
+        let mut kind = ' ';
+        // "div.post#top" splits into "div." "post#" "top"
+        for part in parts {
+            let name = part.trim_end_matches(['.', '#']);
+            let found = match kind {
+                '.' => self.has(&self.classes, name),
+                '#' => self.has(&self.ids, name),
+                _ => name.is_empty() || name == "*" || self.has(&self.tags, &name.to_lowercase()),
+            };
+            if !found {
+                return false;
+            }
+            kind = part.chars().last().unwrap_or(' ');
+        }
+        true
+    }
+}
+
+// the rules of css that style something used, keeping @media and @supports blocks that
+// still have any and every other at-rule, like @font-face and @keyframes
+pub fn used_rules(css: &str, used: &UsedSelectors) -> String {
+    let mut out = String::new();
+    let mut rest = css.trim();
+    while let Some(open) = rest.find(['{', ';']) {
+        // @import and @charset
+        if rest[open..].starts_with(';') {
+            out.push_str(&rest[..=open]);
+            rest = rest[open + 1..].trim_start();
+            continue;
+        }
+        let prelude = rest[..open].trim();
+        let close = closing_brace(rest, open);
+        let block = &rest[open + 1..close];
+        if ["@media", "@supports", "@layer"]
+            .iter()
+            .any(|at| prelude.starts_with(at))
+        {
+            let inner = used_rules(block, used);
+            if !inner.is_empty() {
+                out.push_str(&format!("{}{{{}}}", prelude, inner));
+            }
+        } else if prelude.starts_with('@') || prelude.split(',').any(|s| used.matches(s)) {
+            out.push_str(&format!("{}{{{}}}", prelude, block.trim()));
+        }
+        rest = rest.get(close + 1..).unwrap_or_default().trim_start();
+    }
+    out
+}
+
+fn closing_brace(css: &str, open: usize) -> usize {
+    let mut depth = 0;
+    for (i, c) in css[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 1 => return open + i,
+            '}' => depth -= 1,
+            _ => (),
+        }
+    }
+    css.len()
+}
+
+pub fn without_comments(css: &str) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        out.push_str(&rest[..start]);
+        rest = rest[start + 2..]
+            .find("*/")
+            .map_or("", |end| &rest[start + 2 + end + 2..]);
+    }
+    out.push_str(rest);
+    out
+}
+
+// inline the rules a page needs before scrolling from each local stylesheet it links to, and
+// load the full stylesheets without blocking rendering. stylesheets are keyed by their path
+// under dest
+pub fn inline(
+    html: &str,
+    stylesheets: &BTreeMap<String, String>,
+    site_url: &str,
+    config: &CriticalConfig,
+) -> String {
+    let fold = UsedSelectors::above_the_fold(html, config.fold);
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    let mut critical = String::new();
+    let mut style_at = None;
+    while let Some(start) = rest.find("<link") {
+        let tag = &rest[start..start + tag_end(&rest[start..])];
+        out.push_str(&rest[..start]);
+        rest = &rest[start + tag.len()..];
+
+        let is_stylesheet = attribute(tag, "rel")
+            .is_some_and(|rel| rel.split_whitespace().any(|r| r == "stylesheet"));
+        let href = attribute(tag, "href").filter(|_| is_stylesheet);
+        let css = href.and_then(|href| {
+            let path = href.strip_prefix(site_url).unwrap_or(href);
+            let path = path.split(['?', '#']).next().unwrap_or_default();
+            stylesheets.get(path.trim_start_matches('/'))
+        });
+        let (href, css) = match (href, css) {
+            (Some(href), Some(css)) => (href, css),
+            _ => {
+                out.push_str(tag);
+                continue;
+            }
+        };
+        style_at.get_or_insert(out.len());
+        critical.push_str(&used_rules(&without_comments(css), &fold));
+        out.push_str(&format!(
+            "<link rel=\"preload\" href=\"{0}\" as=\"style\" \
+             onload=\"this.onload=null;this.rel='stylesheet'\">\
+             <noscript><link rel=\"stylesheet\" href=\"{0}\"></noscript>",
+            href
+        ));
+    }
+    out.push_str(rest);
+    if let Some(at) = style_at {
+        out.insert_str(at, &format!("<style>{}</style>", critical));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inlines_rules_for_what_is_above_the_fold() {
+        let css = "/* base */ body { margin: 0 } .post h2 { color: red } .footer { color: blue }\n\
+                   @media (max-width: 600px) { #top { padding: 0 } .footer { margin: 0 } }\n\
+                   a:hover, .later::before { color: green }";
+        let stylesheets = BTreeMap::from([("css/site.css".to_string(), css.to_string())]);
+        let html = "<html><head><link rel=\"stylesheet\" href=\"/css/site.css\"></head>\
+                    <body><div id=\"top\" class=\"post\"><h2><a href=\"/\">Hi</a></h2></div>\
+                    <footer class=\"footer\">bye</footer></body></html>";
+        let config = CriticalConfig { fold: 75 };
+
+        assert_eq!(
+            inline(html, &stylesheets, "https://example.com", &config),
+            "<html><head><style>body{margin: 0}.post h2{color: red}\
+             @media (max-width: 600px){#top{padding: 0}}a:hover, .later::before{color: green}\
+             </style><link rel=\"preload\" href=\"/css/site.css\" as=\"style\" \
+             onload=\"this.onload=null;this.rel='stylesheet'\"><noscript><link rel=\"stylesheet\" \
+             href=\"/css/site.css\"></noscript></head><body><div id=\"top\" class=\"post\">\
+             <h2><a href=\"/\">Hi</a></h2></div><footer class=\"footer\">bye</footer></body></html>"
+        );
+        // pages without a local stylesheet are left alone
+        let other = html.replace("/css/site.css", "https://cdn.example.net/x.css");
+        assert_eq!(
+            inline(&other, &stylesheets, "https://example.com", &config),
+            other
+        );
+    }
+}
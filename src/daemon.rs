@@ -0,0 +1,258 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use color_eyre::{Report, Result};
+use log::{info, warn};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::builder::Builder;
+use crate::cli::Opt;
+use crate::watch;
+
+pub const DEFAULT_ADDR: &str = "127.0.0.1:8090";
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    // notifications have no id and get no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileParams {
+    path: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct PreviewParams {
+    path: PathBuf,
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataParams {
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl From<Report> for RpcError {
+    fn from(e: Report) -> RpcError {
+        RpcError {
+            code: INTERNAL_ERROR,
+            message: e.to_string(),
+        }
+    }
+}
+
+// the parsed site, kept between requests and loaded again once a watched file changes
+struct Site {
+    opts: Opt,
+    builder: Option<Builder<'static>>,
+    stale: Arc<AtomicBool>,
+}
+
+impl Site {
+    fn builder(&mut self) -> Result<&mut Builder<'static>> {
+        let stale = self.stale.swap(false, Ordering::SeqCst);
+        let builder = match self.builder.take() {
+            Some(builder) if !stale => builder,
+            _ => {
+                let mut builder = Builder::new(self.opts.clone())?;
+                builder.load()?;
+                builder
+            }
+        };
+        Ok(self.builder.insert(builder))
+    }
+}
+
+// answer render, preview and metadata requests for editors, one JSON-RPC message per line
+// over a local socket, until the process is stopped
+pub fn run(opts: Opt, addr: &str) -> Result<()> {
+    let mut watched: Vec<PathBuf> = opts.config.iter().map(PathBuf::from).collect();
+    watched.extend([&opts.src, &opts.template_dir, &opts.helper_dir].map(PathBuf::from));
+    watched.extend(opts.static_dir.iter().map(PathBuf::from));
+    watched.extend(opts.styles_dir.iter().map(PathBuf::from));
+
+    let stale = Arc::new(AtomicBool::new(false));
+    let mut site = Site {
+        opts,
+        builder: None,
+        stale: stale.clone(),
+    };
+    let posts = site.builder()?.pages().len();
+
+    // parsing is cached, so reloading after a save only parses what changed
+    thread::spawn(move || loop {
+        for file in watch::wait_for_change(&watched) {
+            info!("{:?} changed, reloading on the next request", file);
+        }
+        stale.store(true, Ordering::SeqCst);
+    });
+
+    let listener = TcpListener::bind(addr)?;
+    info!("Loaded {} posts, answering JSON-RPC on {}", posts, addr);
+    // one client at a time, an editor keeps its connection open
+    for stream in listener.incoming() {
+        if let Err(e) = serve(stream?, &mut site) {
+            warn!("Closed connection: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn serve(stream: TcpStream, site: &mut Site) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = respond(site, &line) {
+            writeln!(writer, "{}", response)?;
+        }
+    }
+    Ok(())
+}
+
+fn respond(site: &mut Site, line: &str) -> Option<Value> {
+    let request = match serde_json::from_str::<Value>(line) {
+        Ok(value) => value,
+        Err(e) => return Some(error(Value::Null, PARSE_ERROR, e.to_string())),
+    };
+    let request: Request = match serde_json::from_value(request) {
+        Ok(request) => request,
+        Err(e) => return Some(error(Value::Null, INVALID_REQUEST, e.to_string())),
+    };
+    let result = call(site, &request.method, request.params);
+    let id = request.id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => error(id, e.code, e.message),
+    })
+}
+
+fn call(site: &mut Site, method: &str, params: Value) -> Result<Value, RpcError> {
+    match method {
+        // html for a saved file
+        "render" => {
+            let params: FileParams = parse_params(params)?;
+            Ok(site.builder()?.render_file(&params.path)?.into())
+        }
+        // html for an editor's unsaved text
+        "preview" => {
+            let params: PreviewParams = parse_params(params)?;
+            Ok(site
+                .builder()?
+                .render_text(&params.path, &params.text)?
+                .into())
+        }
+        // one post as it parses, or a summary of every post without a path
+        "metadata" => {
+            let params: MetadataParams = parse_params(params)?;
+            let builder = site.builder()?;
+            let metadata = match params.path {
+                Some(path) => serde_json::to_value(builder.page(&path, None)?),
+                None => serde_json::to_value(
+                    builder
+                        .pages()
+                        .iter()
+                        .map(|page| {
+                            json!({
+                                "title": page.title,
+                                "url": page.url,
+                                "tags": page.tags,
+                                "source": page.source,
+                                "modified": page.modified.to_rfc3339(),
+                            })
+                        })
+                        .collect::<Vec<_>>(),
+                ),
+            };
+            Ok(metadata.map_err(Report::from)?)
+        }
+        _ => Err(RpcError {
+            code: METHOD_NOT_FOUND,
+            message: format!("Unknown method {}", method),
+        }),
+    }
+}
+
+// missing params are an empty object, so methods whose params are all optional can omit them
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = match params {
+        Value::Null => json!({}),
+        params => params,
+    };
+    serde_json::from_value(params).map_err(|e| RpcError {
+        code: INVALID_PARAMS,
+        message: e.to_string(),
+    })
+}
+
+fn error(id: Value, code: i64, message: String) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_bad_requests_without_loading_the_site() -> Result<()> {
+        let mut site = Site {
+            opts: Opt::from_toml("title = \"Daemon\"\nsrc = \"missing\"")?,
+            builder: None,
+            stale: Arc::new(AtomicBool::new(false)),
+        };
+        let code = |response: Option<Value>| response.map(|r| r["error"]["code"].clone());
+
+        assert_eq!(code(respond(&mut site, "{")), Some(PARSE_ERROR.into()));
+        assert_eq!(
+            code(respond(&mut site, "[1]")),
+            Some(INVALID_REQUEST.into())
+        );
+        let unknown = r#"{"jsonrpc": "2.0", "id": 7, "method": "build"}"#;
+        assert_eq!(
+            respond(&mut site, unknown),
+            Some(error(
+                7.into(),
+                METHOD_NOT_FOUND,
+                "Unknown method build".into()
+            ))
+        );
+        let no_path = r#"{"jsonrpc": "2.0", "id": 8, "method": "render", "params": {}}"#;
+        assert_eq!(
+            code(respond(&mut site, no_path)),
+            Some(INVALID_PARAMS.into())
+        );
+        // a notification is never answered, even when it fails
+        assert_eq!(respond(&mut site, r#"{"method": "build"}"#), None);
+        assert!(site.builder.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use color_eyre::Result;
+use log::info;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::manifest::Manifest;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeployKind {
+    Rsync,
+    Sftp,
+    S3,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeployConfig {
+    pub kind: DeployKind,
+    /// user@host:/path for rsync and sftp, bucket or bucket/prefix for s3
+    pub target: String,
+    /// The endpoint of an S3-compatible service that isn't AWS
+    pub endpoint: Option<String>,
+    /// Remove files from the target that aren't in dest (rsync and s3)
+    #[serde(default)]
+    pub delete: bool,
+    /// Extra arguments for the tool doing the upload
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Error)]
+enum DeployError {
+    #[error("No [deploy] section in the config")]
+    NoConfig,
+
+    #[error("{0:?} doesn't exist, build the site before deploying it")]
+    NotBuilt(String),
+
+    #[error("sftp targets look like user@host:/path, not {0}")]
+    BadTarget(String),
+
+    #[error("{0} failed: {1}")]
+    Failed(String, String),
+}
+
+// the build cache and manifest only matter to the next build
+const STATE_FILES: &str = ".site-gen-*";
+
+fn is_state_file(file: &str) -> bool {
+    Path::new(file)
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with(".site-gen-"))
+}
+
+// a quoted argument for an sftp batch file
+fn sftp_quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// the command that uploads dest, and what to feed it on stdin
+#[derive(Debug, PartialEq)]
+struct Plan {
+    program: &'static str,
+    args: Vec<String>,
+    input: Option<String>,
+}
+
+// rsync, sftp and the aws cli already do uploads well, so deploying hands dest to one of them.
+// sftp can't exclude anything, so it's given the files the last build wrote, one by one
+fn plan(config: &DeployConfig, dest: &str, outputs: &BTreeSet<String>) -> Result<Plan> {
+    let dest = dest.trim_end_matches('/');
+    let mut args = vec![];
+    let plan = match config.kind {
+        DeployKind::Rsync => {
+            args.extend(["-az".to_string(), format!("--exclude={}", STATE_FILES)]);
+            if config.delete {
+                args.push("--delete".to_string());
+            }
+            args.extend(config.args.iter().cloned());
+            // the trailing slash copies dest's contents rather than dest itself
+            args.extend([format!("{}/", dest), config.target.clone()]);
+            Plan {
+                program: "rsync",
+                args,
+                input: None,
+            }
+        }
+        DeployKind::Sftp => {
+            let (host, path) = config
+                .target
+                .split_once(':')
+                .ok_or(DeployError::BadTarget(config.target.clone()))?;
+            args.extend(config.args.iter().cloned());
+            args.extend(["-b".to_string(), "-".to_string(), host.to_string()]);
+            let path = if path.is_empty() { "." } else { path };
+            let files: Vec<_> = outputs.iter().filter(|f| !is_state_file(f)).collect();
+            if files.is_empty() {
+                return Err(DeployError::NotBuilt(dest.to_string()).into());
+            }
+            // parents sort before what's in them; - lets mkdir fail when they already exist
+            let dirs: BTreeSet<_> = files
+                .iter()
+                .flat_map(|file| Path::new(file.as_str()).ancestors().skip(1))
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .map(|dir| dir.to_string_lossy().to_string())
+                .collect();
+            let mut input = format!("lcd {}\ncd {}\n", sftp_quote(dest), sftp_quote(path));
+            for dir in dirs {
+                input.push_str(&format!("-mkdir {}\n", sftp_quote(&dir)));
+            }
+            for file in files {
+                let file = sftp_quote(file);
+                input.push_str(&format!("put {} {}\n", file, file));
+            }
+            Plan {
+                program: "sftp",
+                args,
+                input: Some(input),
+            }
+        }
+        DeployKind::S3 => {
+            args.extend(["s3".to_string(), "sync".to_string(), dest.to_string()]);
+            args.push(format!(
+                "s3://{}",
+                config.target.trim_start_matches("s3://")
+            ));
+            args.extend(["--exclude".to_string(), STATE_FILES.to_string()]);
+            if config.delete {
+                args.push("--delete".to_string());
+            }
+            if let Some(endpoint) = &config.endpoint {
+                args.extend(["--endpoint-url".to_string(), endpoint.clone()]);
+            }
+            args.extend(config.args.iter().cloned());
+            Plan {
+                program: "aws",
+                args,
+                input: None,
+            }
+        }
+    };
+    Ok(plan)
+}
+
+// push everything in dest to the configured target
+pub fn deploy(config: Option<&DeployConfig>, dest: &str) -> Result<()> {
+    let config = config.ok_or(DeployError::NoConfig)?;
+    if !Path::new(dest).is_dir() {
+        return Err(DeployError::NotBuilt(dest.to_string()).into());
+    }
+    let outputs = Manifest::load(Path::new(dest)).outputs;
+    let plan = plan(config, dest, &outputs)?;
+    info!(
+        "Deploying {} with {} {}",
+        dest,
+        plan.program,
+        plan.args.join(" ")
+    );
+
+    let mut child = Command::new(plan.program)
+        .args(&plan.args)
+        .stdin(match plan.input {
+            Some(_) => Stdio::piped(),
+            None => Stdio::inherit(),
+        })
+        .spawn()
+        .map_err(|e| DeployError::Failed(plan.program.to_string(), e.to_string()))?;
+    if let (Some(input), Some(mut stdin)) = (&plan.input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(DeployError::Failed(plan.program.to_string(), status.to_string()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(kind: DeployKind, target: &str) -> DeployConfig {
+        DeployConfig {
+            kind,
+            target: target.to_string(),
+            endpoint: None,
+            delete: true,
+            args: vec![],
+        }
+    }
+
+    #[test]
+    fn plans_each_kind_of_upload() -> Result<()> {
+        let none = BTreeSet::new();
+        let rsync = plan(
+            &config(DeployKind::Rsync, "me@host:/var/www"),
+            "public/",
+            &none,
+        )?;
+        assert_eq!(rsync.program, "rsync");
+        assert_eq!(
+            rsync.args,
+            vec![
+                "-az",
+                "--exclude=.site-gen-*",
+                "--delete",
+                "public/",
+                "me@host:/var/www"
+            ]
+        );
+
+        let outputs = BTreeSet::from([
+            "index.html".to_string(),
+            "tags/c.html".to_string(),
+            ".site-gen-manifest.json".to_string(),
+        ]);
+        let sftp = plan(
+            &config(DeployKind::Sftp, "me@host:/var/www"),
+            "public",
+            &outputs,
+        )?;
+        assert_eq!(sftp.args, vec!["-b", "-", "me@host"]);
+        assert_eq!(
+            sftp.input.as_deref(),
+            Some(concat!(
+                "lcd \"public\"\ncd \"/var/www\"\n",
+                "-mkdir \"tags\"\n",
+                "put \"index.html\" \"index.html\"\n",
+                "put \"tags/c.html\" \"tags/c.html\"\n",
+            ))
+        );
+        assert!(plan(&config(DeployKind::Sftp, "host"), "public", &outputs).is_err());
+        // nothing built yet
+        assert!(plan(
+            &config(DeployKind::Sftp, "me@host:/var/www"),
+            "public",
+            &none
+        )
+        .is_err());
+
+        let mut s3 = config(DeployKind::S3, "bucket/site");
+        s3.endpoint = Some("https://s3.example.com".to_string());
+        assert_eq!(
+            plan(&s3, "public", &none)?.args,
+            vec![
+                "s3",
+                "sync",
+                "public",
+                "s3://bucket/site",
+                "--exclude",
+                ".site-gen-*",
+                "--delete",
+                "--endpoint-url",
+                "https://s3.example.com"
+            ]
+        );
+        Ok(())
+    }
+}
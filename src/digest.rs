@@ -0,0 +1,45 @@
+use chrono::{DateTime, Datelike, FixedOffset};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestPeriod {
+    Weekly,
+    Monthly,
+}
+
+// the name of the period a post falls into, used as the digest filename
+pub fn bucket_key(date: &DateTime<FixedOffset>, period: DigestPeriod) -> String {
+    match period {
+        DigestPeriod::Weekly => {
+            let week = date.iso_week();
+            format!("{}-W{:02}", week.year(), week.week())
+        }
+        DigestPeriod::Monthly => date.format("%Y-%m").to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::parse_date;
+
+    #[test]
+    fn buckets_by_iso_week() {
+        let date = parse_date("2024-03-20T12:00:00-07:00");
+        assert_eq!(bucket_key(&date, DigestPeriod::Weekly), "2024-W12");
+    }
+
+    #[test]
+    fn iso_week_can_belong_to_the_previous_year() {
+        let date = parse_date("2021-01-01T12:00:00-07:00");
+        assert_eq!(bucket_key(&date, DigestPeriod::Weekly), "2020-W53");
+    }
+
+    #[test]
+    fn buckets_by_month() {
+        let date = parse_date("2024-03-20T12:00:00-07:00");
+        assert_eq!(bucket_key(&date, DigestPeriod::Monthly), "2024-03");
+    }
+}
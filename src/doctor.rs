@@ -0,0 +1,353 @@
+use std::fs;
+use std::path::Path;
+
+use chrono::DateTime;
+use color_eyre::Result;
+use thiserror::Error;
+
+use crate::front_matter::{self, header_value, read_header, FIELDS};
+use crate::helpers::get_posts;
+use crate::{config, sites, Opt};
+
+// the templates every build renders; partials are templates too
+const REQUIRED_TEMPLATES: [&str; 5] = ["entry", "index", "tag-list", "style", "feeds"];
+
+#[derive(Debug, Error)]
+enum DoctorError {
+    #[error("{0} problem(s) found")]
+    Problems(usize),
+}
+
+// doctor explains everything it looks at; check only speaks up about problems and writes
+// nothing, so it suits a pre-commit hook
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Doctor,
+    Check,
+}
+
+// one thing that was checked, and what to do about it when it failed
+#[derive(Debug)]
+struct Finding {
+    message: String,
+    fix: Option<String>,
+}
+
+impl Finding {
+    fn ok(message: String) -> Finding {
+        Finding { message, fix: None }
+    }
+
+    fn problem(message: String, fix: String) -> Finding {
+        Finding {
+            message,
+            fix: Some(fix),
+        }
+    }
+}
+
+// check everything a first build needs, printing each result with a suggested fix
+pub fn run(opts: Opt, mode: Mode) -> Result<()> {
+    let mut findings = vec![];
+    let configs = match &opts.config {
+        Some(path) => match load(path) {
+            Ok(configs) => {
+                findings.push(Finding::ok(format!("config {} loads", path)));
+                configs
+            }
+            Err(e) => {
+                findings.push(Finding::problem(
+                    format!("config {} doesn't load: {}", path, e),
+                    "correct the config, `site-gen config-check` re-runs just this check"
+                        .to_string(),
+                ));
+                vec![]
+            }
+        },
+        None => vec![(None, opts)],
+    };
+
+    let mut problems = report(&findings, mode);
+    for (name, opts) in configs {
+        let findings = check(&opts, mode);
+        let site_problems = findings.iter().filter(|f| f.fix.is_some()).count();
+        if let Some(name) = name.filter(|_| mode == Mode::Doctor || site_problems > 0) {
+            println!("site {}:", name);
+        }
+        problems += report(&findings, mode);
+    }
+    match problems {
+        0 => Ok(()),
+        n => Err(DoctorError::Problems(n).into()),
+    }
+}
+
+// print each finding, or for check just the problems, returning how many were problems
+fn report(findings: &[Finding], mode: Mode) -> usize {
+    let mut problems = 0;
+    for finding in findings {
+        match &finding.fix {
+            None if mode == Mode::Check => (),
+            None => println!("ok    {}", finding.message),
+            Some(fix) => {
+                problems += 1;
+                println!("FAIL  {}\n      fix: {}", finding.message, fix);
+            }
+        }
+    }
+    problems
+}
+
+fn load(path: &str) -> Result<Vec<(Option<String>, Opt)>> {
+    let table: toml::Table = toml::from_str(&fs::read_to_string(path)?).map_err(config::explain)?;
+    sites::resolve(table, &[])
+}
+
+fn check(opts: &Opt, mode: Mode) -> Vec<Finding> {
+    let mut findings = vec![];
+
+    findings.push(match &opts.url {
+        Some(url) => match url::Url::parse(url) {
+            Ok(u) if u.host_str().is_some() => Finding::ok(format!("url {}", url)),
+            _ => Finding::problem(
+                format!("url {} isn't an absolute url", url),
+                "use the full address, e.g. https://example.com".to_string(),
+            ),
+        },
+        None => Finding::problem(
+            "no url is set".to_string(),
+            "add `url = \"https://...\"` to the config or pass --url".to_string(),
+        ),
+    });
+
+    let src = Path::new(&opts.src);
+    if src.is_dir() {
+        findings.push(Finding::ok(format!("src {} exists", opts.src)));
+        findings.extend(check_posts(src, opts));
+    } else {
+        findings.push(Finding::problem(
+            format!("src {} isn't a directory", opts.src),
+            format!(
+                "create it with `mkdir -p {}` or point src at your posts",
+                opts.src
+            ),
+        ));
+    }
+
+    let template_dir = Path::new(&opts.template_dir);
+    if template_dir.is_dir() {
+        findings.push(Finding::ok(format!(
+            "template_dir {} exists",
+            opts.template_dir
+        )));
+        findings.extend(check_templates(template_dir, opts));
+    } else {
+        findings.push(Finding::problem(
+            format!("template_dir {} isn't a directory", opts.template_dir),
+            "copy the templates directory from the site-gen repo or set template_dir".to_string(),
+        ));
+    }
+
+    if let Some(static_dir) = &opts.static_dir {
+        findings.push(match Path::new(static_dir).is_dir() {
+            true => Finding::ok(format!("static_dir {} exists", static_dir)),
+            false => Finding::problem(
+                format!("static_dir {} isn't a directory", static_dir),
+                "create it or remove static_dir from the config".to_string(),
+            ),
+        });
+    }
+
+    if mode == Mode::Doctor {
+        findings.push(check_writable(Path::new(&opts.dest)));
+    }
+    findings
+}
+
+fn check_templates(template_dir: &Path, opts: &Opt) -> Vec<Finding> {
+    let mut required = REQUIRED_TEMPLATES.to_vec();
+    if opts.digest.is_some() {
+        required.push("digest");
+    }
+    if opts.changes {
+        required.push("changes");
+    }
+    if opts.bookmarks.is_some() {
+        required.push("bookmarks");
+    }
+
+    let mut findings = vec![];
+    for name in required {
+        let path = template_dir.join(format!("{}.hbs", name));
+        let finding = match fs::read_to_string(&path) {
+            Ok(source) => match handlebars::Template::compile(&source) {
+                Ok(_t) => continue,
+                Err(e) => Finding::problem(
+                    format!("template {:?} doesn't parse: {}", path, e),
+                    "correct the handlebars syntax at the line shown".to_string(),
+                ),
+            },
+            Err(_e) => Finding::problem(
+                format!("template {} is missing", name),
+                format!("add {:?}", path),
+            ),
+        };
+        findings.push(finding);
+    }
+    if findings.is_empty() {
+        findings.push(Finding::ok("required templates are present".to_string()));
+    }
+    findings
+}
+
+fn check_posts(src: &Path, opts: &Opt) -> Vec<Finding> {
+    let mut findings = vec![];
+    for file in get_posts(src).unwrap_or_default() {
+        let header = match read_header(&file) {
+            Ok(h) => h,
+            Err(e) => {
+                findings.push(Finding::problem(
+                    format!("{:?} can't be read: {}", file, e),
+                    "posts must be UTF-8 text files".to_string(),
+                ));
+                continue;
+            }
+        };
+        // bare posts are dated and titled from the file instead
+        if header.is_empty() && opts.bare_posts.is_some() {
+            continue;
+        }
+        if header.is_empty() {
+            findings.push(Finding::problem(
+                format!("{:?} has no front matter", file),
+                "start it with a header between `---` lines, or set bare_posts".to_string(),
+            ));
+            continue;
+        }
+        findings.extend(check_header(&file, &header, opts));
+        match header_value(&header, "date") {
+            Some(date) if DateTime::parse_from_rfc3339(date).is_err() => {
+                findings.push(Finding::problem(
+                    format!("{:?} has an unparseable date `{}`", file, date),
+                    "use RFC 3339, e.g. 2024-03-12T10:00:00-07:00".to_string(),
+                ))
+            }
+            Some(_date) => (),
+            None => findings.push(Finding::problem(
+                format!("{:?} has no date and will be dated at build time", file),
+                "add a `date:` line to its header".to_string(),
+            )),
+        }
+    }
+    if findings.is_empty() {
+        findings.push(Finding::ok("post front matter parses".to_string()));
+    }
+    findings
+}
+
+// every header line is a `key: value` pair with a key posts can set
+fn check_header(file: &Path, header: &str, opts: &Opt) -> Vec<Finding> {
+    let mut findings = vec![];
+    let known = |key: &str| FIELDS.iter().any(|(k, ..)| *k == key) || opts.fields.contains_key(key);
+    for line in header.lines().filter(|l| !front_matter::is_delimiter(l)) {
+        let key = match line
+            .split_once(": ")
+            .or(line.strip_suffix(':').map(|k| (k, "")))
+        {
+            Some((key, _value)) if !key.contains(' ') => key,
+            _ if line.trim().is_empty() => continue,
+            _ => {
+                findings.push(Finding::problem(
+                    format!(
+                        "{:?} has a header line `{}` that isn't `key: value`",
+                        file, line
+                    ),
+                    "write it as `key: value`, with a space after the colon".to_string(),
+                ));
+                continue;
+            }
+        };
+        if !known(key) {
+            findings.push(Finding::problem(
+                format!("{:?} has an unknown header key `{}`", file, key),
+                "correct the spelling, or declare it under [fields] in the config".to_string(),
+            ));
+        }
+    }
+    if header_value(header, "title").is_none_or(|t| t.trim().is_empty()) {
+        findings.push(Finding::problem(
+            format!("{:?} has no title", file),
+            "add a `title:` line to its header".to_string(),
+        ));
+    }
+    if let Some(draft) = header_value(header, "draft").filter(|d| !["true", "false"].contains(d)) {
+        findings.push(Finding::problem(
+            format!(
+                "{:?} has draft `{}`, which is neither true nor false",
+                file, draft
+            ),
+            "use `draft: true` or `draft: false`".to_string(),
+        ));
+    }
+    findings
+}
+
+// write (and remove) a probe file in dest, or the directory it will be created in
+fn check_writable(dest: &Path) -> Finding {
+    let mut dir = dest;
+    while !dir.is_dir() {
+        dir = match dir.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+    }
+    let probe = dir.join(".site-gen-doctor");
+    match fs::write(&probe, b"") {
+        Ok(_) => {
+            let _ = fs::remove_file(probe);
+            Finding::ok(format!("dest {:?} is writable", dest))
+        }
+        Err(e) => Finding::problem(
+            format!("can't write to {:?}: {}", dir, e),
+            "fix the directory's permissions or choose another dest".to_string(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_missing_and_optional_templates() -> Result<()> {
+        let opts = config::parse_opts(toml::from_str("title = \"t\"\nchanges = true")?)?;
+        let findings = check_templates(Path::new("templates"), &opts);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].message, "required templates are present");
+
+        let findings = check_templates(Path::new("fixtures"), &opts);
+        assert!(findings
+            .iter()
+            .any(|f| f.fix.is_some() && f.message == "template changes is missing"));
+        Ok(())
+    }
+
+    #[test]
+    fn reports_malformed_headers() -> Result<()> {
+        let opts = config::parse_opts(toml::from_str(
+            "title = \"t\"\n[fields.mood]\ntype = \"text\"",
+        )?)?;
+        let header = "---\ntitle: Hi\nmood: fine\ntitel: typo\ndraft: yes\nnot a pair\n---\n";
+        let findings = check_header(Path::new("a.md"), header, &opts);
+        let messages: Vec<_> = findings.iter().map(|f| f.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "\"a.md\" has an unknown header key `titel`",
+                "\"a.md\" has a header line `not a pair` that isn't `key: value`",
+                "\"a.md\" has draft `yes`, which is neither true nor false",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::prettify::{attribute, tag_end};
+
+// seconds a fetched preview image is used before it's fetched again
+pub const PREVIEW_TTL: u64 = 7 * 24 * 3600;
+
+// swaps the placeholder for the embed it holds back, only once the reader asks for it
+const LOAD_SCRIPT: &str = "var p=this.parentNode;\
+p.replaceWith(p.querySelector('template').content.cloneNode(true))";
+
+// a third-party embed held back until a reader clicks to load it, e.g.
+// `[embeds.youtube] hosts = ["www.youtube.com"], preview = "https://img.youtube.com/vi/{id}/hqdefault.jpg"`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmbedProvider {
+    /// Hosts whose iframes are replaced by a placeholder
+    pub hosts: Vec<String>,
+    /// An image to show in the placeholder, fetched at build time and served from dest;
+    /// {id} is the last part of the iframe's path
+    pub preview: Option<String>,
+    /// The text of the button that loads the embed
+    pub label: Option<String>,
+}
+
+// one iframe a provider's placeholder will replace
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Embed {
+    pub provider: String,
+    pub id: String,
+}
+
+impl Embed {
+    // where the preview image is fetched from, if the provider has one
+    pub fn preview_url(&self, providers: &BTreeMap<String, EmbedProvider>) -> Option<String> {
+        let preview = providers.get(&self.provider)?.preview.as_ref()?;
+        Some(preview.replace("{id}", &self.id))
+    }
+
+    // where the preview image is written, relative to dest
+    pub fn preview_path(&self, preview_url: &str) -> String {
+        let ext = preview_url
+            .rsplit('/')
+            .next()
+            .and_then(|name| name.rsplit_once('.'))
+            .map_or("jpg", |(_name, ext)| ext);
+        format!("embeds/{}-{}.{}", self.provider, self.id, ext)
+    }
+}
+
+// every iframe in html from a configured provider
+pub fn find(html: &str, providers: &BTreeMap<String, EmbedProvider>) -> Vec<Embed> {
+    iframes(html)
+        .filter_map(|(tag, _end)| embed(tag, providers))
+        .collect()
+}
+
+// replace each provider's iframes with a click-to-load placeholder, showing the preview
+// image saved for it, if there is one
+pub fn hold_back(
+    html: &str,
+    providers: &BTreeMap<String, EmbedProvider>,
+    previews: &BTreeMap<Embed, String>,
+) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = 0;
+    for (tag, end) in iframes(html) {
+        let start = tag.as_ptr() as usize - html.as_ptr() as usize;
+        let found = match embed(tag, providers) {
+            Some(e) => e,
+            None => continue,
+        };
+        let provider = &providers[&found.provider];
+        out.push_str(&html[rest..start]);
+        out.push_str(&format!(
+            "<div class=\"embed-placeholder\" data-provider=\"{}\">",
+            found.provider
+        ));
+        if let Some(preview) = previews.get(&found) {
+            out.push_str(&format!("<img src=\"{}\" alt=\"\">", preview));
+        }
+        let label = provider
+            .label
+            .clone()
+            .unwrap_or(format!("Load content from {}", found.provider));
+        out.push_str(&format!(
+            "<button type=\"button\" onclick=\"{}\">{}</button><template>{}</template></div>",
+            LOAD_SCRIPT,
+            label,
+            &html[start..end]
+        ));
+        rest = end;
+    }
+    out.push_str(&html[rest..]);
+    out
+}
+
+// each <iframe> opening tag and where its closing tag ends
+fn iframes(html: &str) -> impl Iterator<Item = (&str, usize)> {
+    html.match_indices("<iframe").map(|(start, _)| {
+        let tag = &html[start..start + tag_end(&html[start..])];
+        let end = html[start..]
+            .find("</iframe>")
+            .map_or(start + tag.len(), |e| start + e + "</iframe>".len());
+        (tag, end)
+    })
+}
+
+fn embed(tag: &str, providers: &BTreeMap<String, EmbedProvider>) -> Option<Embed> {
+    let src = attribute(tag, "src")?;
+    let src = match src.starts_with("//") {
+        true => url::Url::parse(&format!("https:{}", src)).ok()?,
+        false => url::Url::parse(src).ok()?,
+    };
+    let host = src.host_str()?;
+    let (provider, _config) = providers
+        .iter()
+        .find(|(_name, p)| p.hosts.iter().any(|h| h == host))?;
+    let id = src.path_segments()?.rfind(|s| !s.is_empty())?;
+    Some(Embed {
+        provider: provider.clone(),
+        id: id.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holds_back_configured_providers_only() {
+        let providers: BTreeMap<String, EmbedProvider> = toml::from_str(
+            "[youtube]\nhosts = [\"www.youtube.com\"]\n\
+             preview = \"https://img.youtube.com/vi/{id}/hqdefault.jpg\"\nlabel = \"Play\"",
+        )
+        .unwrap();
+        let video = "<iframe src=\"https://www.youtube.com/embed/abc123\"></iframe>";
+        let html = format!("<p>{}</p><iframe src=\"/local.html\"></iframe>", video);
+
+        let found = find(&html, &providers);
+        let url = found[0].preview_url(&providers).unwrap();
+        assert_eq!(url, "https://img.youtube.com/vi/abc123/hqdefault.jpg");
+        assert_eq!(found[0].preview_path(&url), "embeds/youtube-abc123.jpg");
+
+        let previews = BTreeMap::from([(found.into_iter().next().unwrap(), "/p.jpg".to_string())]);
+        assert_eq!(
+            hold_back(&html, &providers, &previews),
+            format!(
+                "<p><div class=\"embed-placeholder\" data-provider=\"youtube\">\
+                 <img src=\"/p.jpg\" alt=\"\"><button type=\"button\" onclick=\"{}\">Play</button>\
+                 <template>{}</template></div></p><iframe src=\"/local.html\"></iframe>",
+                LOAD_SCRIPT, video
+            )
+        );
+    }
+}
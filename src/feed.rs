@@ -0,0 +1,303 @@
+use chrono::{DateTime, FixedOffset, Local};
+use quick_xml::escape::escape;
+use serde_json::{json, Value};
+
+use crate::helpers::without_nulls;
+
+const GENERATOR_URI: &str = "https://github.com/toddself/site-gen";
+
+#[derive(Debug)]
+pub struct FeedEntry {
+    pub title: String,
+    // absolute, and doubles as the entry's id when it has none of its own
+    pub url: String,
+    // survives the post being renamed, so readers never see it twice
+    pub id: Option<String>,
+    pub published: DateTime<FixedOffset>,
+    pub updated: DateTime<FixedOffset>,
+    pub author: String,
+    // plain text
+    pub summary: Option<String>,
+    // html, used over summary where a feed format can carry it
+    pub summary_html: Option<String>,
+    // html
+    pub contents: String,
+    // when it differs from the feed's
+    pub lang: Option<String>,
+    pub license: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Feed {
+    pub title: String,
+    pub site_url: String,
+    pub feed_url: String,
+    pub description: Option<String>,
+    pub updated: DateTime<FixedOffset>,
+    pub lang: Option<String>,
+    pub license: Option<String>,
+    pub entries: Vec<FeedEntry>,
+}
+
+impl FeedEntry {
+    fn guid(&self) -> &str {
+        self.id.as_deref().unwrap_or(&self.url)
+    }
+}
+
+impl Feed {
+    // a feed is as new as its newest entry, so an unchanged feed renders identically
+    pub fn new(
+        title: String,
+        site_url: String,
+        feed_url: String,
+        description: Option<String>,
+        entries: Vec<FeedEntry>,
+    ) -> Feed {
+        let updated = entries
+            .iter()
+            .map(|e| e.updated)
+            .max()
+            .unwrap_or_else(|| Local::now().fixed_offset());
+        Feed {
+            title,
+            site_url,
+            feed_url,
+            description,
+            updated,
+            lang: None,
+            license: None,
+            entries,
+        }
+    }
+
+    pub fn to_atom(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<feed xmlns=\"http://www.w3.org/2005/Atom\"{}>\n",
+            xml_lang(&self.lang)
+        ));
+        xml.push_str(&format!(
+            "  <title>{}</title>\n",
+            escape(self.title.as_str())
+        ));
+        xml.push_str(&format!(
+            "  <link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>\n",
+            escape(self.site_url.as_str())
+        ));
+        xml.push_str(&format!(
+            "  <link rel=\"self\" type=\"application/atom+xml\" href=\"{}\"/>\n",
+            escape(self.feed_url.as_str())
+        ));
+        xml.push_str(&format!("  <id>{}</id>\n", escape(self.feed_url.as_str())));
+        xml.push_str(&format!(
+            "  <updated>{}</updated>\n",
+            self.updated.to_rfc3339()
+        ));
+        if let Some(description) = &self.description {
+            xml.push_str(&format!(
+                "  <subtitle>{}</subtitle>\n",
+                escape(description.as_str())
+            ));
+        }
+        xml.push_str(&license_link(&self.license, "  "));
+        xml.push_str(&format!(
+            "  <generator uri=\"{}\">site-gen</generator>\n",
+            GENERATOR_URI
+        ));
+        for entry in self.entries.iter() {
+            xml.push_str(&self.atom_entry(entry));
+        }
+        xml.push_str("</feed>\n");
+        xml
+    }
+
+    // the same feed as JSON Feed 1.1, published at feed_url
+    pub fn to_json_feed(&self, feed_url: &str) -> Result<String, serde_json::Error> {
+        let items: Vec<Value> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                json!({
+                    "id": entry.guid(),
+                    "url": entry.url,
+                    "title": entry.title,
+                    "content_html": entry.contents,
+                    "summary": entry.summary,
+                    "date_published": entry.published.to_rfc3339(),
+                    "date_modified": entry.updated.to_rfc3339(),
+                    "authors": [{ "name": entry.author }],
+                    "language": entry.lang,
+                })
+            })
+            .collect();
+        let feed = json!({
+            "version": "https://jsonfeed.org/version/1.1",
+            "title": self.title,
+            "home_page_url": self.site_url,
+            "feed_url": feed_url,
+            "description": self.description,
+            "language": self.lang,
+            "items": items,
+        });
+        serde_json::to_string_pretty(&without_nulls(feed))
+    }
+
+    fn atom_entry(&self, entry: &FeedEntry) -> String {
+        let mut xml = format!("  <entry{}>\n", xml_lang(&entry.lang));
+        xml.push_str(&format!(
+            "    <title>{}</title>\n",
+            escape(entry.title.as_str())
+        ));
+        xml.push_str(&format!(
+            "    <link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>\n",
+            escape(entry.url.as_str())
+        ));
+        xml.push_str(&format!("    <id>{}</id>\n", escape(entry.guid())));
+        xml.push_str(&format!(
+            "    <published>{}</published>\n",
+            entry.published.to_rfc3339()
+        ));
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            entry.updated.to_rfc3339()
+        ));
+        xml.push_str(&format!(
+            "    <author><name>{}</name><uri>{}</uri></author>\n",
+            escape(entry.author.as_str()),
+            escape(self.site_url.as_str())
+        ));
+        xml.push_str(&license_link(&entry.license, "    "));
+        if let Some(summary) = &entry.summary_html {
+            xml.push_str(&format!(
+                "    <summary type=\"html\">{}</summary>\n",
+                escape(summary.as_str())
+            ));
+        } else if let Some(summary) = &entry.summary {
+            xml.push_str(&format!(
+                "    <summary>{}</summary>\n",
+                escape(summary.as_str())
+            ));
+        }
+        xml.push_str(&format!(
+            "    <content type=\"html\">{}</content>\n",
+            escape(entry.contents.as_str())
+        ));
+        xml.push_str("  </entry>\n");
+        xml
+    }
+}
+
+// atom license links, from RFC 4946
+fn license_link(license: &Option<String>, indent: &str) -> String {
+    match license {
+        Some(license) => format!(
+            "{}<link rel=\"license\" href=\"{}\"/>\n",
+            indent,
+            escape(license.as_str())
+        ),
+        None => String::new(),
+    }
+}
+
+fn xml_lang(lang: &Option<String>) -> String {
+    match lang {
+        Some(lang) => format!(" xml:lang=\"{}\"", escape(lang.as_str())),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::parse_date;
+    use quick_xml::events::Event;
+    use quick_xml::Reader;
+
+    #[test]
+    fn escapes_entries_and_dates_the_feed_by_its_newest() {
+        let entry = |url: &str, date: &str| FeedEntry {
+            title: "Fish & <chips>".to_string(),
+            url: url.to_string(),
+            id: None,
+            published: parse_date(date),
+            updated: parse_date(date),
+            author: "me".to_string(),
+            summary: Some("a < b".to_string()),
+            summary_html: None,
+            contents: "<p>hi</p>".to_string(),
+            lang: None,
+            license: Some("https://creativecommons.org/licenses/by/4.0/".to_string()),
+        };
+        let mut feed = Feed::new(
+            "A blog".to_string(),
+            "https://example.com".to_string(),
+            "https://example.com/index.rss".to_string(),
+            None,
+            vec![
+                entry("https://example.com/old.html", "2024-03-12T10:00:00-07:00"),
+                entry("https://example.com/new.html", "2024-04-01T10:00:00-07:00"),
+            ],
+        );
+        let xml = feed.to_atom();
+        assert!(xml.contains("<updated>2024-04-01T10:00:00-07:00</updated>\n  <generator"));
+        assert!(xml.contains("<title>Fish &amp; &lt;chips&gt;</title>"));
+        assert!(xml.contains("<id>https://example.com/old.html</id>"));
+        assert!(xml.contains("<summary>a &lt; b</summary>"));
+        assert!(xml.contains(
+            "<link rel=\"license\" href=\"https://creativecommons.org/licenses/by/4.0/\"/>"
+        ));
+        assert!(xml.contains("<content type=\"html\">&lt;p&gt;hi&lt;/p&gt;</content>"));
+
+        feed.entries[0].summary_html = Some("<p>a</p>".to_string());
+        assert!(feed
+            .to_atom()
+            .contains("<summary type=\"html\">&lt;p&gt;a&lt;/p&gt;</summary>"));
+
+        let mut reader = Reader::from_str(&xml);
+        loop {
+            match reader.read_event() {
+                Ok(Event::Eof) => break,
+                Ok(_event) => (),
+                Err(e) => panic!("invalid feed xml: {}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn builds_a_json_feed_without_nulls() -> Result<(), serde_json::Error> {
+        let feed = Feed::new(
+            "A blog".to_string(),
+            "https://example.com".to_string(),
+            "https://example.com/index.rss".to_string(),
+            None,
+            vec![FeedEntry {
+                title: "Post".to_string(),
+                url: "https://example.com/post.html".to_string(),
+                id: Some("tag:example.com,2024:abc".to_string()),
+                published: parse_date("2024-04-01T10:00:00-07:00"),
+                updated: parse_date("2024-04-01T10:00:00-07:00"),
+                author: "me".to_string(),
+                summary: None,
+                summary_html: None,
+                contents: "<p>hi</p>".to_string(),
+                lang: Some("de".to_string()),
+                license: None,
+            }],
+        );
+        let json: Value =
+            serde_json::from_str(&feed.to_json_feed("https://example.com/feed.json")?)?;
+        assert_eq!(json["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(json["feed_url"], "https://example.com/feed.json");
+        assert!(json.get("description").is_none());
+        assert_eq!(json["items"][0]["id"], "tag:example.com,2024:abc");
+        assert_eq!(json["items"][0]["url"], "https://example.com/post.html");
+        assert_eq!(
+            json["items"][0]["date_published"],
+            "2024-04-01T10:00:00-07:00"
+        );
+        assert!(json["items"][0].get("summary").is_none());
+        assert_eq!(json["items"][0]["language"], "de");
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+
+use color_eyre::Result;
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, XmlVersion};
+
+pub type FeedItem = BTreeMap<String, String>;
+
+// flatten every RSS <item> or Atom <entry> into a map of element name to text
+pub fn parse_feed(xml: &str) -> Result<Vec<FeedItem>> {
+    let mut reader = Reader::from_str(xml);
+    let mut items = vec![];
+    let mut current: Option<FeedItem> = None;
+    let mut text = String::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = e.local_name().as_ref().to_string();
+                if name == "item" || name == "entry" {
+                    current = Some(FeedItem::new());
+                } else if let Some(item) = current.as_mut() {
+                    read_link(item, &name, &e)?;
+                }
+                text.clear();
+            }
+            Event::Empty(e) => {
+                if let Some(item) = current.as_mut() {
+                    let name = e.local_name().as_ref().to_string();
+                    read_link(item, &name, &e)?;
+                }
+            }
+            Event::Text(t) => text.push_str(&t.xml10_content()),
+            Event::CData(c) => text.push_str(&c.xml10_content()),
+            Event::GeneralRef(r) => {
+                if let Some(c) = r.resolve_char_ref()? {
+                    text.push(c);
+                } else if let Some(s) = resolve_predefined_entity(&r.xml10_content()) {
+                    text.push_str(s);
+                }
+            }
+            Event::End(e) => {
+                let name = e.local_name().as_ref().to_string();
+                if name == "item" || name == "entry" {
+                    if let Some(item) = current.take() {
+                        items.push(item);
+                    }
+                } else if let Some(item) = current.as_mut() {
+                    let value = text.trim();
+                    if !value.is_empty() {
+                        item.entry(name).or_insert_with(|| value.to_string());
+                    }
+                }
+                text.clear();
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+    Ok(items)
+}
+
+// atom links, enclosures and thumbnails carry their value in an attribute
+fn read_link(item: &mut FeedItem, name: &str, e: &BytesStart) -> Result<()> {
+    if let Some(rel) = e.try_get_attribute("rel")? {
+        if rel.normalized_value(XmlVersion::Implicit1_0)? != "alternate" {
+            return Ok(());
+        }
+    }
+    let attr = match e.try_get_attribute("href")? {
+        Some(a) => Some(a),
+        None => e.try_get_attribute("url")?,
+    };
+    if let Some(attr) = attr {
+        let value = attr.normalized_value(XmlVersion::Implicit1_0)?;
+        item.entry(name.to_string())
+            .or_insert_with(|| value.to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_rss_items() -> Result<()> {
+        let xml = r#"<rss><channel><title>feed</title>
+            <item><title>Tom &amp; Jerry</title><link>https://example.com/1</link>
+            <description><![CDATA[<p>hi</p>]]></description></item>
+            <item><title>Second</title></item>
+            </channel></rss>"#;
+        let items = parse_feed(xml)?;
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0]["title"], "Tom & Jerry");
+        assert_eq!(items[0]["link"], "https://example.com/1");
+        assert_eq!(items[0]["description"], "<p>hi</p>");
+        Ok(())
+    }
+
+    #[test]
+    fn reads_atom_entries() -> Result<()> {
+        let xml = r#"<feed><entry><title>Atom</title>
+            <link rel="self" href="https://example.com/self"/>
+            <link rel="alternate" href="https://example.com/post"/>
+            <updated>2024-01-01T00:00:00Z</updated></entry></feed>"#;
+        let items = parse_feed(xml)?;
+        assert_eq!(items[0]["link"], "https://example.com/post");
+        assert_eq!(items[0]["updated"], "2024-01-01T00:00:00Z");
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use color_eyre::Result;
+use handlebars::{Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+enum FontsError {
+    #[cfg(feature = "fonts")]
+    #[error("Unable to subset {0:?}: {1}")]
+    Subset(std::path::PathBuf, String),
+
+    #[cfg(not(feature = "fonts"))]
+    #[error("Can't subset {0:?}, site-gen was built without the fonts feature")]
+    NoFonts(std::path::PathBuf),
+}
+
+// one `[[fonts]]` entry: a font file to cut down to the characters the site uses
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FontConfig {
+    /// The font-family templates and stylesheets refer to it by
+    pub family: String,
+    /// The ttf or otf file to subset
+    pub src: String,
+    #[serde(default = "default_weight")]
+    pub weight: String,
+    #[serde(default = "default_style")]
+    pub style: String,
+    #[serde(default = "default_display")]
+    pub display: String,
+    /// Characters to keep that only templates use, on top of the text of every post
+    #[serde(default)]
+    pub text: String,
+}
+
+fn default_weight() -> String {
+    "400".to_string()
+}
+
+fn default_style() -> String {
+    "normal".to_string()
+}
+
+fn default_display() -> String {
+    "swap".to_string()
+}
+
+impl FontConfig {
+    // where the subset is written, relative to dest
+    pub fn output_path(&self) -> String {
+        let stem = Path::new(&self.src)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or(slug::slugify(&self.family));
+        format!("fonts/{}.woff2", stem)
+    }
+
+    // the @font-face rule for the subset, served from url
+    pub fn font_face(&self, url: &str) -> String {
+        format!(
+            "@font-face {{ font-family: \"{}\"; src: url(\"{}\") format(\"woff2\"); \
+             font-weight: {}; font-style: {}; font-display: {}; }}",
+            self.family, url, self.weight, self.style, self.display
+        )
+    }
+}
+
+// the characters a subset needs: printable ascii, for what templates say, plus everything in
+// text
+pub fn characters<'a>(text: impl IntoIterator<Item = &'a str>) -> BTreeSet<char> {
+    let mut chars: BTreeSet<char> = (' '..='~').collect();
+    for text in text {
+        chars.extend(text.chars().filter(|c| !c.is_control()));
+    }
+    chars
+}
+
+// font cut down to chars, as woff2
+#[cfg(feature = "fonts")]
+pub fn subset(font: &Path, chars: &BTreeSet<char>) -> Result<Vec<u8>> {
+    use allsorts::binary::read::ReadScope;
+    use allsorts::font::{Font, MatchingPresentation};
+    use allsorts::font_data::FontData;
+
+    let failed = |e: String| FontsError::Subset(font.to_owned(), e);
+    let data = std::fs::read(font)?;
+    let file = ReadScope::new(&data)
+        .read::<FontData<'_>>()
+        .map_err(|e| failed(e.to_string()))?;
+    let provider = file.table_provider(0).map_err(|e| failed(e.to_string()))?;
+    let mut parsed = Font::new(provider).map_err(|e| failed(e.to_string()))?;
+
+    // .notdef always comes first
+    let mut glyphs = vec![0];
+    for c in chars {
+        let (glyph, _variation) =
+            parsed.lookup_glyph_index(*c, MatchingPresentation::NotRequired, None);
+        if glyph != 0 {
+            glyphs.push(glyph);
+        }
+    }
+    glyphs.sort();
+    glyphs.dedup();
+
+    let subset = allsorts::subset::subset(&parsed.font_table_provider, &glyphs)
+        .map_err(|e| failed(e.to_string()))?;
+    woofwoof::compress(&subset, [], 11, true)
+        .ok_or_else(|| failed("woff2 compression failed".to_string()).into())
+}
+
+#[cfg(not(feature = "fonts"))]
+pub fn subset(font: &Path, _chars: &BTreeSet<char>) -> Result<Vec<u8>> {
+    Err(FontsError::NoFonts(font.to_owned()).into())
+}
+
+// `<style>{{font_faces}}</style>` is the @font-face rules for every subset font
+#[derive(Debug, Default)]
+pub struct FontFacesHelper {
+    pub css: String,
+}
+
+impl HelperDef for FontFacesHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        _h: &Helper<'rc>,
+        _r: &'reg Handlebars<'reg>,
+        _ctx: &'rc Context,
+        _rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        out.write(&self.css)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_ascii_and_the_text_used() {
+        let chars = characters(["Café “quoted”", "naïve\n"]);
+        assert!(chars.contains(&'A') && chars.contains(&'~'));
+        assert!(chars.contains(&'é') && chars.contains(&'“') && chars.contains(&'ï'));
+        assert!(!chars.contains(&'\n') && !chars.contains(&'ü'));
+
+        let font: FontConfig =
+            toml::from_str("family = \"Body Serif\"\nsrc = \"fonts/BodySerif-Regular.ttf\"")
+                .unwrap();
+        assert_eq!(font.output_path(), "fonts/BodySerif-Regular.woff2");
+        assert_eq!(
+            font.font_face("/fonts/BodySerif-Regular.woff2"),
+            "@font-face { font-family: \"Body Serif\"; src: url(\"/fonts/BodySerif-Regular.woff2\") \
+             format(\"woff2\"); font-weight: 400; font-style: normal; font-display: swap; }"
+        );
+    }
+
+    #[cfg(feature = "fonts")]
+    #[test]
+    fn subsets_to_woff2() -> Result<()> {
+        // Open Sans cut down to ascii, see fixtures/fonts/OPEN-SANS-LICENSE.txt
+        let font = Path::new("fixtures/fonts/OpenSans-ascii.ttf");
+        let few = subset(font, &"Hi".chars().collect())?;
+        let all = subset(font, &characters([]))?;
+        assert!(few.starts_with(b"wOF2") && all.starts_with(b"wOF2"));
+        assert!(few.len() < all.len());
+
+        assert!(subset(Path::new("fixtures/data/file1.md"), &characters([])).is_err());
+        Ok(())
+    }
+}
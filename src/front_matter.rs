@@ -0,0 +1,558 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, FixedOffset, Local, SecondsFormat};
+use clap::ValueEnum;
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::{content_hash, get_posts, parse_date};
+
+pub const HEADER_DELIMITER: &str = "---";
+// hex digits in a post id
+const ID_LEN: usize = 12;
+
+// where a post without front matter takes its date from
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BareDate {
+    Mtime,
+    Git,
+}
+
+// the kind of value a front matter key takes
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldKind {
+    Text,
+    // comma separated, like tags
+    List,
+    // rfc 3339
+    Date,
+    Boolean,
+}
+
+// every key a post's header can set, for the front matter schema; a test keeps it in step
+// with parse
+pub const FIELDS: [(&str, FieldKind, &str); 16] = [
+    ("title", FieldKind::Text, "The post's title"),
+    (
+        "id",
+        FieldKind::Text,
+        "Stays the same when the post is renamed, unlike its url",
+    ),
+    ("date", FieldKind::Date, "When the post was published"),
+    ("tags", FieldKind::List, "Comma separated tags"),
+    (
+        "share_image",
+        FieldKind::Text,
+        "Image shown when the post is shared",
+    ),
+    ("hero_image", FieldKind::Text, "Image shown above the post"),
+    (
+        "description",
+        FieldKind::Text,
+        "Summary for feeds, shares and search engines",
+    ),
+    (
+        "author",
+        FieldKind::Text,
+        "A key into the config's [authors] table, or the author's name",
+    ),
+    ("lang", FieldKind::Text, "The post's language, e.g. en"),
+    (
+        "dir",
+        FieldKind::Text,
+        "The post's text direction, ltr or rtl",
+    ),
+    (
+        "content_warning",
+        FieldKind::Text,
+        "Shown in place of the post until a reader opens it",
+    ),
+    ("license", FieldKind::Text, "Url of the post's license"),
+    (
+        "syndicate",
+        FieldKind::List,
+        "Comma separated sites from [sites] that also publish the post",
+    ),
+    (
+        "syndication",
+        FieldKind::List,
+        "Comma separated urls of copies of the post posted elsewhere",
+    ),
+    (
+        "draft",
+        FieldKind::Boolean,
+        "true leaves the post out of builds",
+    ),
+    (
+        "references",
+        FieldKind::Text,
+        "A bibliography file next to the post",
+    ),
+];
+
+#[derive(Debug, Default)]
+pub struct FrontMatter {
+    // stays the same when a post is renamed, unlike its url
+    pub id: Option<String>,
+    pub date: Option<DateTime<FixedOffset>>,
+    pub tags: Vec<String>,
+    pub title: String,
+    pub share_image: Option<String>,
+    pub hero_image: Option<String>,
+    pub description: Option<String>,
+    // a key into [authors], or just a name
+    pub author: Option<String>,
+    pub lang: Option<String>,
+    pub dir: Option<String>,
+    pub content_warning: Option<String>,
+    pub license: Option<String>,
+    pub syndicate: Vec<String>,
+    // where copies of the post were posted elsewhere, e.g. a mastodon status
+    pub syndication: Vec<String>,
+    pub draft: bool,
+}
+
+impl FrontMatter {
+    /// Read the header of the post at path.
+    pub fn from_file(path: &Path) -> Result<FrontMatter> {
+        Ok(parse(&read_header(path)?))
+    }
+
+    // the header block for a new post, in the same `key: value` form parse reads
+    pub fn to_header(&self) -> String {
+        let mut lines = vec![HEADER_DELIMITER.to_string()];
+        lines.push(format!("title: {}", self.title));
+        if let Some(id) = &self.id {
+            lines.push(format!("id: {}", id));
+        }
+        if let Some(date) = &self.date {
+            lines.push(format!(
+                "date: {}",
+                date.to_rfc3339_opts(SecondsFormat::Secs, false)
+            ));
+        }
+        if !self.tags.is_empty() {
+            lines.push(format!("tags: {}", self.tags.join(", ")));
+        }
+        if let Some(description) = &self.description {
+            lines.push(format!("description: {}", description));
+        }
+        if let Some(author) = &self.author {
+            lines.push(format!("author: {}", author));
+        }
+        if self.draft {
+            lines.push("draft: true".to_string());
+        }
+        lines.push(HEADER_DELIMITER.to_string());
+        lines.join("\n") + "\n"
+    }
+}
+
+// a post split into its header block and the markdown after it
+#[derive(Debug, Default)]
+pub struct Source {
+    pub header: String,
+    pub body: String,
+}
+
+// read a post a line at a time until its header closes, then the body in one go,
+// so large posts are neither scanned for metadata nor copied a second time
+pub fn read(path: &Path) -> Result<Source> {
+    read_from(BufReader::new(File::open(path)?))
+}
+
+// a post that isn't saved yet, split the same way
+pub fn parse_source(text: &str) -> Result<Source> {
+    read_from(text.as_bytes())
+}
+
+fn read_from(mut reader: impl BufRead) -> Result<Source> {
+    let (header, mut body) = read_header_from(&mut reader)?;
+    reader.read_to_string(&mut body)?;
+    Ok(Source { header, body })
+}
+
+// just the header of a post, without reading any further into the file
+pub fn read_header(path: &Path) -> Result<String> {
+    Ok(read_header_from(&mut BufReader::new(File::open(path)?))?.0)
+}
+
+// the header plus whatever was read past it, which belongs to the body
+fn read_header_from(reader: &mut impl BufRead) -> Result<(String, String)> {
+    let mut header = String::new();
+    let mut line = String::new();
+    let mut sep_count = 0;
+    while sep_count < 2 && reader.read_line(&mut line)? > 0 {
+        if is_delimiter(&line) {
+            sep_count += 1;
+        } else if sep_count == 0 {
+            // no header, this line is already part of the body
+            break;
+        }
+        header.push_str(&line);
+        line.clear();
+    }
+
+    // a header that never closes is just markdown
+    match sep_count {
+        1 => Ok((String::new(), header + line.as_str())),
+        _ => Ok((header, line)),
+    }
+}
+
+// the leading header block and the body after it, scanning no further than the header
+pub fn split(buf: &str) -> (&str, &str) {
+    let mut end = 0;
+    let mut sep_count = 0;
+    for line in buf.split_inclusive('\n') {
+        if is_delimiter(line) {
+            sep_count += 1;
+        } else if sep_count == 0 {
+            break;
+        }
+        end += line.len();
+        if sep_count == 2 {
+            return buf.split_at(end);
+        }
+    }
+    ("", buf)
+}
+
+pub fn is_delimiter(line: &str) -> bool {
+    line.trim_end_matches(['\r', '\n']) == HEADER_DELIMITER
+}
+
+// the `key: value` lines of a post's header
+fn header_lines(buf: &str) -> impl Iterator<Item = &str> {
+    split(buf).0.lines().filter(|l| !is_delimiter(l))
+}
+
+// a short id for a new post, unique enough for one site
+pub fn new_id() -> String {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let count = COUNT.fetch_add(1, Ordering::SeqCst);
+    let seed = format!("{}{}{}", nanos, std::process::id(), count);
+    content_hash(seed.as_bytes())[..ID_LEN].to_string()
+}
+
+// extract metadata from the header of a post
+pub fn parse(buf: &str) -> FrontMatter {
+    let mut front_matter = FrontMatter::default();
+
+    for line in header_lines(buf) {
+        let elements: Vec<&str> = line.split(' ').collect();
+        let data_type = elements.first();
+        let data_value = elements[1..].join(" ");
+
+        match data_type {
+            Some(&"date:") => {
+                front_matter.date = Some(parse_date(data_value.as_str()));
+            }
+            Some(&"tags:") => {
+                front_matter.tags = list(&data_value);
+            }
+            Some(&"title:") => {
+                front_matter.title = data_value;
+            }
+            Some(&"id:") => {
+                front_matter.id = Some(data_value.trim().to_string());
+            }
+            Some(&"share_image:") => {
+                front_matter.share_image = Some(data_value);
+            }
+            Some(&"hero_image:") => {
+                front_matter.hero_image = Some(data_value);
+            }
+            Some(&"description:") => {
+                front_matter.description = Some(data_value);
+            }
+            Some(&"author:") => {
+                front_matter.author = Some(data_value);
+            }
+            Some(&"lang:") => {
+                front_matter.lang = Some(data_value);
+            }
+            Some(&"dir:") => {
+                front_matter.dir = Some(data_value);
+            }
+            Some(&"content_warning:") => {
+                front_matter.content_warning = Some(data_value);
+            }
+            Some(&"license:") => {
+                front_matter.license = Some(data_value);
+            }
+            Some(&"syndicate:") => {
+                front_matter.syndicate = list(&data_value);
+            }
+            Some(&"syndication:") => {
+                front_matter.syndication = list(&data_value);
+            }
+            Some(&"draft:") => {
+                front_matter.draft = data_value.trim() == "true";
+            }
+            _ => (),
+        }
+    }
+    front_matter
+}
+
+// metadata for a post without a header: its first heading (lifted out of the body) or file
+// name as the title, and the date the file was last modified or first committed
+pub fn bare_defaults(path: &Path, body: &str, date_from: BareDate) -> (FrontMatter, String) {
+    let (title, body) = match take_heading(body) {
+        Some(found) => found,
+        None => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            (stem.replace(['-', '_'], " "), body.to_string())
+        }
+    };
+
+    let date = match date_from {
+        BareDate::Git => git_date(path).or_else(|| modified_date(path)),
+        BareDate::Mtime => modified_date(path),
+    };
+    let front_matter = FrontMatter {
+        title,
+        date,
+        ..Default::default()
+    };
+    (front_matter, body)
+}
+
+// a `# Heading` opening the body, and the body without it
+pub fn take_heading(body: &str) -> Option<(String, String)> {
+    let heading = body
+        .lines()
+        .find(|l| !l.trim().is_empty())?
+        .strip_prefix("# ")
+        .filter(|h| !h.trim().is_empty())?;
+    // the heading is a slice of body, so its offset is where it sits in the body
+    let after = heading.as_ptr() as usize - body.as_ptr() as usize + heading.len();
+    Some((heading.trim().to_string(), body[after..].to_string()))
+}
+
+fn modified_date(path: &Path) -> Option<DateTime<FixedOffset>> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    Some(DateTime::<Local>::from(modified).fixed_offset())
+}
+
+// when the file was added to git, None outside a repo or for uncommitted files
+fn git_date(path: &Path) -> Option<DateTime<FixedOffset>> {
+    let output = Command::new("git")
+        .args([
+            "log",
+            "--diff-filter=A",
+            "--follow",
+            "--format=%aI",
+            "-1",
+            "--",
+        ])
+        .arg(path.file_name()?)
+        .current_dir(path.parent().filter(|p| !p.as_os_str().is_empty())?)
+        .output()
+        .ok()?;
+    let date = String::from_utf8_lossy(&output.stdout);
+    DateTime::parse_from_rfc3339(date.trim()).ok()
+}
+
+// the raw text of a header key, before any parsing
+pub fn header_value<'a>(buf: &'a str, key: &str) -> Option<&'a str> {
+    let prefix = format!("{}: ", key);
+    header_lines(buf).find_map(|line| line.strip_prefix(prefix.as_str()))
+}
+
+// how many posts in src use each tag
+pub fn tag_counts(src: &Path) -> Result<BTreeMap<String, usize>> {
+    let mut counts = BTreeMap::new();
+    for file in get_posts(src)? {
+        for tag in parse(&read_header(&file)?).tags {
+            *counts.entry(tag).or_insert(0) += 1;
+        }
+    }
+    Ok(counts)
+}
+
+// map aliased tags to their canonical name, dropping any resulting repeats
+pub fn apply_aliases(tags: Vec<String>, aliases: &BTreeMap<String, String>) -> Vec<String> {
+    let mut resolved: Vec<String> = vec![];
+    for tag in tags {
+        let tag = aliases.get(&tag).cloned().unwrap_or(tag);
+        if !resolved.contains(&tag) {
+            resolved.push(tag);
+        }
+    }
+    resolved
+}
+
+// rewrite the tags line of a post's header using the alias map, None if nothing changed
+pub fn rewrite_tags(buf: &str, aliases: &BTreeMap<String, String>) -> Option<String> {
+    let (header, body) = split(buf);
+    let mut changed = false;
+    let mut output = String::with_capacity(buf.len());
+    for line in header.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+        match content.strip_prefix("tags: ") {
+            Some(value) if !changed => {
+                let tags = list(value);
+                let resolved = apply_aliases(tags.clone(), aliases);
+                if resolved != tags {
+                    changed = true;
+                    output.push_str(&format!("tags: {}", resolved.join(", ")));
+                    output.push_str(&line[content.len()..]);
+                    continue;
+                }
+                output.push_str(line);
+            }
+            _ => output.push_str(line),
+        }
+    }
+    output.push_str(body);
+    changed.then_some(output)
+}
+
+pub fn list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|e| String::from(e.trim()))
+        .filter(|e| !e.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_field_is_parsed() {
+        let unset = format!("{:?}", FrontMatter::default());
+        for (key, kind, _description) in FIELDS {
+            let value = match kind {
+                FieldKind::Text | FieldKind::List => "something",
+                FieldKind::Date => "2024-05-04T10:00:00-07:00",
+                FieldKind::Boolean => "true",
+            };
+            let header = format!("---\n{}: {}\n---\n", key, value);
+            // references is read by the builder, straight from the header
+            let parsed = match key {
+                "references" => header_value(&header, key).map(str::to_string),
+                _ => Some(format!("{:?}", parse(&header))).filter(|p| *p != unset),
+            };
+            assert!(parsed.is_some(), "{} isn't parsed", key);
+        }
+    }
+
+    #[test]
+    fn rewrites_aliased_tags() {
+        let aliases = BTreeMap::from([
+            ("recipe".to_string(), "recipes".to_string()),
+            ("Rust".to_string(), "rust".to_string()),
+        ]);
+        let post = "---\r\ntitle: x\r\ntags: recipe, Rust, recipes\r\n---\r\ntags: recipe\r\n";
+        assert_eq!(
+            rewrite_tags(post, &aliases).unwrap(),
+            "---\r\ntitle: x\r\ntags: recipes, rust\r\n---\r\ntags: recipe\r\n"
+        );
+        assert_eq!(rewrite_tags("---\ntags: rust\n---\n", &aliases), None);
+    }
+
+    #[test]
+    fn reads_headers_apart_from_bodies() -> Result<()> {
+        let post = "---\ntitle: x\n---\n# Hi\n\n---\n";
+        let source = read_from(post.as_bytes())?;
+        assert_eq!(source.header, "---\ntitle: x\n---\n");
+        assert_eq!(source.body, "# Hi\n\n---\n");
+
+        let source = read_from("# No header\n---\ntitle: x\n".as_bytes())?;
+        assert_eq!(source.header, "");
+        assert_eq!(source.body, "# No header\n---\ntitle: x\n");
+
+        let source = read_from("---\ntitle: x\n".as_bytes())?;
+        assert_eq!(source.header, "");
+        assert_eq!(source.body, "---\ntitle: x\n");
+        Ok(())
+    }
+
+    #[test]
+    fn splits_only_a_leading_header() {
+        let post = "---\r\ntitle: x\r\n---\r\nbody\n";
+        assert_eq!(split(post), ("---\r\ntitle: x\r\n---\r\n", "body\n"));
+        assert_eq!(split("body\n---\ntitle: x\n---\n").0, "");
+        assert_eq!(header_value("text\ndate: now\n", "date"), None);
+        assert_eq!(header_value("---\ndate: now\n---\n", "date"), Some("now"));
+    }
+
+    #[test]
+    fn ignores_delimiters_in_the_body() {
+        let post = "---\ntitle: Real\ntags: rust\n---\n\n```yaml\n---\ntitle: Fake\n---\n```\n\n---\n\ntags: fake\n";
+        let front_matter = parse(post);
+        assert_eq!(front_matter.title, "Real");
+        assert_eq!(front_matter.tags, vec!["rust"]);
+        assert!(split(post).1.starts_with("\n```yaml\n---\n"));
+
+        // a thematic break in a post without a header isn't the start of one
+        let post = "Intro\n\n---\ntitle: Fake\ntags: fake\n---\n";
+        let front_matter = parse(post);
+        assert_eq!(front_matter.title, "");
+        assert!(front_matter.tags.is_empty());
+
+        let aliases = BTreeMap::from([("fake".to_string(), "real".to_string())]);
+        assert_eq!(rewrite_tags(post, &aliases), None);
+    }
+
+    #[test]
+    fn bare_posts_take_their_title_from_a_heading_or_file_name() {
+        let path = Path::new("fixtures/data/file1.md");
+        let (front_matter, body) = bare_defaults(path, "\n# A note\n\nText\n", BareDate::Mtime);
+        assert_eq!(front_matter.title, "A note");
+        assert_eq!(body, "\n\nText\n");
+        assert!(front_matter.date.is_some());
+
+        let (front_matter, body) = bare_defaults(path, "Text\n# Later\n", BareDate::Mtime);
+        assert_eq!(front_matter.title, "file1");
+        assert_eq!(body, "Text\n# Later\n");
+    }
+
+    #[test]
+    fn takes_only_an_opening_heading() {
+        assert_eq!(
+            take_heading("\n# Title \nText\n"),
+            Some(("Title".to_string(), "\nText\n".to_string()))
+        );
+        assert_eq!(take_heading("Text\n# Later\n"), None);
+        assert_eq!(take_heading("## Subheading\n"), None);
+        assert_eq!(take_heading("#  \nText\n"), None);
+    }
+
+    #[test]
+    fn round_trips_headers() {
+        let front_matter = FrontMatter {
+            title: "A post".to_string(),
+            date: Some(parse_date("2024-03-12T10:00:00-07:00")),
+            tags: vec!["rust".to_string(), "blog".to_string()],
+            draft: true,
+            ..Default::default()
+        };
+        let header = front_matter.to_header();
+        assert_eq!(
+            header,
+            "---\ntitle: A post\ndate: 2024-03-12T10:00:00-07:00\ntags: rust, blog\ndraft: true\n---\n"
+        );
+
+        let parsed = parse(&header);
+        assert_eq!(parsed.title, "A post");
+        assert_eq!(parsed.tags, vec!["rust", "blog"]);
+        assert!(parsed.draft);
+    }
+}
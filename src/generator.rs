@@ -0,0 +1,55 @@
+use serde::Serialize;
+
+pub const NAME: &str = env!("CARGO_PKG_NAME");
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// what made the site, as `generator` in every template
+#[derive(Debug, Serialize)]
+pub struct Generator {
+    pub name: &'static str,
+    pub version: &'static str,
+}
+
+pub const GENERATOR: Generator = Generator {
+    name: NAME,
+    version: VERSION,
+};
+
+// optional cargo features this binary was built with
+pub fn features() -> Vec<&'static str> {
+    let mut features = vec![];
+    if cfg!(feature = "scripting") {
+        features.push("scripting");
+    }
+    features
+}
+
+// add <meta name="generator"> to a page's head, leaving pages without one alone
+pub fn with_meta_tag(html: &str) -> String {
+    let tag = format!(
+        "<meta name=\"generator\" content=\"{} {}\">",
+        GENERATOR.name, GENERATOR.version
+    );
+    match html.find("</head>") {
+        Some(end) if !html[..end].contains("name=\"generator\"") => {
+            format!("{}{}\n{}", &html[..end], tag, &html[end..])
+        }
+        _ => html.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_the_meta_tag_once() {
+        let page = with_meta_tag("<html><head><title>x</title></head><body></body></html>");
+        assert!(page.contains(&format!(
+            "<title>x</title><meta name=\"generator\" content=\"site-gen {}\">\n</head>",
+            VERSION
+        )));
+        assert_eq!(with_meta_tag(&page), page);
+        assert_eq!(with_meta_tag("<p>no head</p>"), "<p>no head</p>");
+    }
+}
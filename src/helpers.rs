@@ -1,16 +1,20 @@
 use std::{
     fs,
     path::{Path, PathBuf},
+    thread,
 };
 
 use chrono::{DateTime, FixedOffset, Local};
 use color_eyre::Result;
+use log::warn;
+use serde::Serialize;
+use serde_json::{json, Value};
 
 pub fn parse_date(date: &str) -> DateTime<FixedOffset> {
     match DateTime::parse_from_rfc3339(date) {
         Ok(d) => d,
         Err(_e) => {
-            println!("Unable to parse {} as a date", date);
+            warn!("Unable to parse {} as a date", date);
             DateTime::<FixedOffset>::from(Local::now())
         }
     }
@@ -27,6 +31,302 @@ pub fn get_entries(src: &Path) -> Result<Vec<PathBuf>> {
     Ok(entries)
 }
 
+// elements whose text is never rewritten: code stays literal and links/abbrs are already marked
+const LITERAL_ELEMENTS: [&str; 6] = ["code", "pre", "a", "abbr", "script", "style"];
+
+// rewrite the text of rendered html with f, leaving tags and the text of literal elements alone
+pub fn map_text<F: Fn(&str) -> String>(html: &str, f: F) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut literal: usize = 0;
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        let text = &rest[..start];
+        match literal {
+            0 => output.push_str(&f(text)),
+            _ => output.push_str(text),
+        }
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end + 1,
+            None => rest.len(),
+        };
+        let tag = &rest[start..end];
+        let name = tag
+            .trim_start_matches(['<', '/'])
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+        if LITERAL_ELEMENTS.contains(&name.as_str()) {
+            match tag.starts_with("</") {
+                true => literal = literal.saturating_sub(1),
+                false => literal += 1,
+            }
+        }
+        output.push_str(tag);
+        rest = &rest[end..];
+    }
+    match literal {
+        0 => output.push_str(&f(rest)),
+        _ => output.push_str(rest),
+    }
+    output
+}
+
+// the extensions a file in src needs to be read as a post
+const POST_EXTENSIONS: [&str; 2] = ["md", "markdown"];
+
+// the subdirectory of the template dir holding partials rather than page templates
+pub const PARTIALS_DIR: &str = "partials";
+
+// every post under src, including those in subdirectories, in a stable order
+pub fn get_posts(src: &Path) -> Result<Vec<PathBuf>> {
+    let mut posts = vec![];
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            posts.extend(get_posts(&path)?);
+        } else if path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| POST_EXTENSIONS.contains(&e))
+        {
+            posts.push(path);
+        }
+    }
+    posts.sort();
+    Ok(posts)
+}
+
+// templates to register as partials, by the name `{{> name}}` uses: `_header.hbs` in the
+// template dir is header and `partials/nav/menu.hbs` is nav/menu
+pub fn get_partials(template_dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut partials = vec![];
+    for path in get_entries(template_dir).unwrap_or_default() {
+        let name = path.file_stem().map(|s| s.to_string_lossy().to_string());
+        if let Some(name) = name.as_deref().and_then(|n| n.strip_prefix('_')) {
+            partials.push((name.to_string(), path));
+        }
+    }
+    let dir = template_dir.join(PARTIALS_DIR);
+    if dir.is_dir() {
+        collect_partials(&dir, "", &mut partials)?;
+    }
+    partials.sort();
+    Ok(partials)
+}
+
+fn collect_partials(dir: &Path, prefix: &str, partials: &mut Vec<(String, PathBuf)>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        if entry.file_type()?.is_dir() {
+            let prefix = format!("{}{}/", prefix, entry.file_name().to_string_lossy());
+            collect_partials(&path, &prefix, partials)?;
+        } else {
+            partials.push((format!("{}{}", prefix, name), path));
+        }
+    }
+    Ok(())
+}
+
+// match a file name against a shell style pattern where * is any run of characters and ? is one
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // where to resume after the last *, if the text after it stops matching
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+// file name of a page in a paginated listing: index.html, index1.html, ...
+pub fn page_url(prefix: &str, index: usize) -> String {
+    match index {
+        0 => format!("{}.html", prefix),
+        _ => format!("{}{}.html", prefix, index),
+    }
+}
+
+// a post's url from a permalink pattern like "{year}/{month}/{slug}.html" or "{slug}/"
+pub fn permalink(pattern: &str, slug: &str, date: &DateTime<FixedOffset>) -> String {
+    pattern
+        .replace("{year}", &date.format("%Y").to_string())
+        .replace("{month}", &date.format("%m").to_string())
+        .replace("{day}", &date.format("%d").to_string())
+        .replace("{slug}", slug)
+        .trim_start_matches('/')
+        .to_string()
+}
+
+// links to every page of a listing, empty when everything fits on one page
+pub fn pagination(prefix: &str, first: &str, num_items: usize, per_page: usize) -> Vec<Value> {
+    if per_page == 0 || num_items <= per_page {
+        return vec![];
+    }
+    let num_pages = num_items.div_ceil(per_page);
+    (0..num_pages)
+        .map(|index| {
+            let name = match index {
+                0 => first.to_string(),
+                _ => format!("page {}", index),
+            };
+            json!({
+                "name": name,
+                "url": page_url(prefix, index),
+            })
+        })
+        .collect()
+}
+
+// where a page of a listing lives and the pages either side of it, for canonical and
+// rel="prev"/"next" links
+#[derive(Debug, Serialize, PartialEq)]
+pub struct PageLinks {
+    pub canonical: String,
+    pub rel_prev: Option<String>,
+    pub rel_next: Option<String>,
+}
+
+impl PageLinks {
+    pub fn new(site_url: &str, prefix: &str, index: usize, num_pages: usize) -> PageLinks {
+        let link = |index: usize| match page_url(prefix, index).as_str() {
+            "index.html" => format!("{}/", site_url),
+            page => format!("{}/{}", site_url, page),
+        };
+        PageLinks {
+            canonical: link(index),
+            rel_prev: index.checked_sub(1).map(link),
+            rel_next: (index + 1 < num_pages).then(|| link(index + 1)),
+        }
+    }
+}
+
+// FNV-1a, which unlike DefaultHasher is stable across Rust releases
+pub fn content_hash(data: &[u8]) -> String {
+    content_hash_parts(&[data])
+}
+
+// hash several buffers as if they were joined, without copying them into one
+pub fn content_hash_parts(parts: &[&[u8]]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in parts.iter().flat_map(|p| p.iter()) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+// drop null fields, for formats that leave out what they don't know rather than send null
+pub fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_k, v)| !v.is_null())
+                .map(|(k, v)| (k, without_nulls(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(without_nulls).collect()),
+        other => other,
+    }
+}
+
+// map f over items on every available core, keeping results in the same order as items
+pub fn parallel_map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let workers = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    if workers < 2 || items.len() < 2 {
+        return items.iter().map(f).collect();
+    }
+    let chunk_size = items.len().div_ceil(workers);
+    let f = &f;
+    thread::scope(|scope| {
+        let chunks: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<R>>()))
+            .collect();
+        chunks
+            .into_iter()
+            .flat_map(|chunk| chunk.join().unwrap())
+            .collect()
+    })
+}
+
+// whether path, with its symlinks and ..s resolved, is an existing file or dir inside dir
+pub fn within(path: &Path, dir: &Path) -> bool {
+    match (path.canonicalize(), dir.canonicalize()) {
+        (Ok(path), Ok(dir)) => path.starts_with(dir),
+        _ => false,
+    }
+}
+
+// the path a site lives under, like /blog for https://example.com/blog/, or nothing
+pub fn site_path(site_url: &str) -> String {
+    url::Url::parse(site_url)
+        .map(|url| url.path().trim_end_matches('/').to_string())
+        .unwrap_or_default()
+}
+
+// where under dest a url referenced from the file at from points, unless it's elsewhere
+pub fn dest_file(from: &Path, url: &str, site_url: &str) -> Option<PathBuf> {
+    let base = site_path(site_url);
+    let url = match url.strip_prefix(site_url) {
+        Some(rest) if !site_url.is_empty() => format!("/{}", rest.trim_start_matches('/')),
+        // root-relative links start with the path the site lives under
+        _ => match url.strip_prefix(base.as_str()) {
+            Some(rest) if !base.is_empty() && rest.starts_with('/') => rest.to_string(),
+            _ => url.to_string(),
+        },
+    };
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    if path.is_empty() || path.starts_with("//") || path.contains(':') {
+        return None;
+    }
+    let joined = match path.strip_prefix('/') {
+        Some(rooted) => PathBuf::from(rooted),
+        None => from.parent().unwrap_or(Path::new("")).join(path),
+    };
+    // fold away ..s so the same file is only counted once
+    let mut file = PathBuf::new();
+    for part in joined.components() {
+        match part {
+            std::path::Component::ParentDir => {
+                file.pop();
+            }
+            std::path::Component::Normal(part) => file.push(part),
+            _ => (),
+        }
+    }
+    Some(file)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -51,6 +351,34 @@ mod tests {
         assert_eq!(display_local, display_parsed);
     }
 
+    #[test]
+    fn paginates_listings() {
+        assert!(pagination("index", "home", 20, 20).is_empty());
+        let pages = pagination("index", "home", 41, 20);
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[0]["name"], "home");
+        assert_eq!(pages[0]["url"], "index.html");
+        assert_eq!(pages[2]["url"], "index2.html");
+    }
+
+    #[test]
+    fn hashes_are_stable() {
+        assert_eq!(content_hash(b""), "cbf29ce484222325");
+        assert_eq!(content_hash(b"a"), "af63dc4c8601ec8c");
+        assert_ne!(content_hash(b"post"), content_hash(b"posts"));
+        assert_eq!(content_hash_parts(&[b"po", b"st"]), content_hash(b"post"));
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(wildcard_match("2024-*.md", "2024-03-hello.md"));
+        assert!(wildcard_match("*.md", ".md"));
+        assert!(wildcard_match("post-?.md", "post-1.md"));
+        assert!(wildcard_match("*a*b", "xaxxab"));
+        assert!(!wildcard_match("2024-*.md", "2023-03-hello.md"));
+        assert!(!wildcard_match("post-?.md", "post-10.md"));
+    }
+
     #[test]
     fn reads_only_files() -> Result<()> {
         let mut fixtures = PathBuf::new();
@@ -59,4 +387,116 @@ mod tests {
         assert_eq!(entries.len(), 3);
         Ok(())
     }
+
+    #[test]
+    fn finds_posts_in_subdirectories() -> Result<()> {
+        let posts = get_posts(Path::new("fixtures"))?;
+        assert_eq!(posts.len(), 3);
+        assert_eq!(posts[0], PathBuf::from("fixtures/data/file1.md"));
+        Ok(())
+    }
+
+    #[test]
+    fn finds_underscored_and_nested_partials() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("site-gen-partials-{}", std::process::id()));
+        fs::create_dir_all(dir.join("partials/nav"))?;
+        for file in [
+            "_header.hbs",
+            "entry.hbs",
+            "partials/footer.hbs",
+            "partials/nav/menu.hbs",
+        ] {
+            fs::write(dir.join(file), "")?;
+        }
+        let names: Vec<_> = get_partials(&dir)?.into_iter().map(|(n, _p)| n).collect();
+        assert_eq!(names, vec!["footer", "header", "nav/menu"]);
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_paths_within_a_dir() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("site-gen-within-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/posts"))?;
+        fs::write(dir.join("src/posts/refs.bib"), "")?;
+        fs::write(dir.join("secret.bib"), "")?;
+        let src = dir.join("src");
+        assert!(within(&src.join("posts/../posts/refs.bib"), &src));
+        assert!(!within(&src.join("posts/../../secret.bib"), &src));
+        assert!(!within(&src.join("posts/missing.bib"), &src));
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn fills_permalink_patterns() {
+        let date = parse_date("2024-03-05T10:00:00-07:00");
+        assert_eq!(
+            permalink("/{year}/{month}/{day}/{slug}.html", "hello", &date),
+            "2024/03/05/hello.html"
+        );
+        assert_eq!(permalink("{slug}/", "hello", &date), "hello/");
+    }
+
+    #[test]
+    fn links_pages_of_a_listing() {
+        let first = PageLinks::new("https://example.com", "index", 0, 3);
+        assert_eq!(first.canonical, "https://example.com/");
+        assert_eq!(first.rel_prev, None);
+        assert_eq!(
+            first.rel_next.as_deref(),
+            Some("https://example.com/index1.html")
+        );
+        let last = PageLinks::new("https://example.com", "planet", 2, 3);
+        assert_eq!(last.canonical, "https://example.com/planet2.html");
+        assert_eq!(
+            last.rel_prev.as_deref(),
+            Some("https://example.com/planet1.html")
+        );
+        assert_eq!(last.rel_next, None);
+    }
+
+    #[test]
+    fn parallel_map_keeps_input_order() {
+        let items: Vec<usize> = (0..1000).collect();
+        let doubled = parallel_map(&items, |i| i * 2);
+        assert_eq!(doubled, items.iter().map(|i| i * 2).collect::<Vec<_>>());
+        assert!(parallel_map(&[] as &[usize], |i| *i).is_empty());
+    }
+
+    #[test]
+    fn resolves_urls_under_dest() {
+        let page = Path::new("posts/hello.html");
+        let site = "https://example.com";
+        assert_eq!(
+            dest_file(page, "/css/site.css?v=2", site),
+            Some(PathBuf::from("css/site.css"))
+        );
+        assert_eq!(
+            dest_file(page, "https://example.com/img/a.png", site),
+            Some(PathBuf::from("img/a.png"))
+        );
+        assert_eq!(
+            dest_file(page, "../img/a.png", site),
+            Some(PathBuf::from("img/a.png"))
+        );
+        assert_eq!(
+            dest_file(page, "b.png", site),
+            Some(PathBuf::from("posts/b.png"))
+        );
+        assert_eq!(dest_file(page, "https://cdn.example.net/a.js", site), None);
+        assert_eq!(dest_file(page, "//cdn.example.net/a.js", site), None);
+        assert_eq!(dest_file(page, "data:image/png;base64,AAAA", site), None);
+
+        let nested = "https://example.com/blog";
+        assert_eq!(site_path(nested), "/blog");
+        assert_eq!(
+            dest_file(page, "/blog/de/hello.html", nested),
+            Some(PathBuf::from("de/hello.html"))
+        );
+        assert_eq!(
+            dest_file(page, "https://example.com/blog/tags.html", nested),
+            Some(PathBuf::from("tags.html"))
+        );
+    }
 }
@@ -0,0 +1,102 @@
+use color_eyre::Result;
+use quick_xml::escape::unescape;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::SyntaxSet;
+use thiserror::Error;
+
+// how comrak opens a fenced code block with an info string
+const CODE_OPEN: &str = "<pre><code class=\"language-";
+const CODE_CLOSE: &str = "</code></pre>";
+
+#[derive(Debug, Error)]
+enum HighlightError {
+    #[error("Unknown highlight_theme {0}, choose one of: {1}")]
+    UnknownTheme(String, String),
+}
+
+// colors fenced code blocks at build time, so pages don't need a highlighting script
+#[derive(Debug)]
+pub struct Highlighter {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+impl Highlighter {
+    pub fn new(theme_name: &str) -> Result<Highlighter> {
+        let mut themes = ThemeSet::load_defaults().themes;
+        let theme = match themes.remove(theme_name) {
+            Some(t) => t,
+            None => {
+                let names: Vec<_> = themes.keys().cloned().collect();
+                return Err(
+                    HighlightError::UnknownTheme(theme_name.to_string(), names.join(", ")).into(),
+                );
+            }
+        };
+        Ok(Highlighter {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme,
+        })
+    }
+
+    // highlight every code block in rendered html whose language syntect knows
+    pub fn highlight_html(&self, html: &str) -> String {
+        let mut output = String::with_capacity(html.len());
+        let mut rest = html;
+        while let Some(start) = rest.find(CODE_OPEN) {
+            output.push_str(&rest[..start]);
+            let block = &rest[start..];
+            let end = match block.find(CODE_CLOSE) {
+                Some(e) => e + CODE_CLOSE.len(),
+                None => break,
+            };
+            output.push_str(&self.highlight_block(&block[..end]));
+            rest = &block[end..];
+        }
+        output.push_str(rest);
+        output
+    }
+
+    fn highlight_block(&self, block: &str) -> String {
+        let inner = &block[CODE_OPEN.len()..block.len() - CODE_CLOSE.len()];
+        let (lang, code) = match inner.split_once("\">") {
+            Some(parts) => parts,
+            None => return block.to_string(),
+        };
+        let syntax = match self.syntaxes.find_syntax_by_token(lang) {
+            Some(s) => s,
+            None => return block.to_string(),
+        };
+        let code = match unescape(code) {
+            Ok(c) => c,
+            Err(_e) => return block.to_string(),
+        };
+        highlighted_html_for_string(&code, &self.syntaxes, syntax, &self.theme)
+            .unwrap_or_else(|_e| block.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlights_known_languages_only() -> Result<()> {
+        let highlighter = Highlighter::new("base16-ocean.dark")?;
+        let html =
+            "<p>hi</p>\n<pre><code class=\"language-rust\">let x = &quot;a&quot;;\n</code></pre>\n\
+                    <pre><code class=\"language-nonsense\">plain &lt;text&gt;\n</code></pre>\n";
+        let highlighted = highlighter.highlight_html(html);
+        assert!(highlighted.starts_with("<p>hi</p>\n<pre style=\""));
+        assert!(highlighted.contains("<span style="));
+        assert!(highlighted.contains(" x = &quot;</span>"));
+        assert!(highlighted.ends_with(
+            "<pre><code class=\"language-nonsense\">plain &lt;text&gt;\n</code></pre>\n"
+        ));
+
+        let err = Highlighter::new("nope").unwrap_err().to_string();
+        assert!(err.starts_with("Unknown highlight_theme nope, choose one of: "));
+        Ok(())
+    }
+}
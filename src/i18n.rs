@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+
+use handlebars::{
+    html_escape, Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::site_path;
+
+/// The same post in another of the site's languages.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Translation {
+    pub lang: String,
+    pub title: String,
+    pub url: String,
+}
+
+// "de" for post.de.md, when de is one of the site's languages
+pub fn filename_lang(file: &Path, languages: &[String]) -> Option<String> {
+    let stem = Path::new(file.file_stem()?);
+    let suffix = stem.extension()?.to_str()?;
+    languages
+        .iter()
+        .any(|lang| lang == suffix)
+        .then(|| suffix.to_string())
+}
+
+// post.de.md is written as if it were post.md, so translations share a slug
+pub fn without_lang(file: &Path, languages: &[String]) -> PathBuf {
+    match (filename_lang(file, languages), file.extension()) {
+        (Some(_lang), Some(ext)) => file.with_extension("").with_extension(ext),
+        _ => file.to_owned(),
+    }
+}
+
+// what a post and its translations have in common: their path without the language suffix
+// or extension
+pub fn translation_key(file: &Path, languages: &[String]) -> PathBuf {
+    without_lang(file, languages).with_extension("")
+}
+
+// posts in the site's other languages live under <lang>/
+pub fn prefixed(url: String, lang: Option<&str>, site_lang: &str, languages: &[String]) -> String {
+    match lang {
+        Some(lang) if lang != site_lang && languages.iter().any(|l| l == lang) => {
+            format!("{}/{}", lang, url)
+        }
+        _ => url,
+    }
+}
+
+// a page of the site linked from the path the site lives under, so the link works from pages
+// under <lang>/ and in serve previews. http(s) urls to other sites are left as they are, and
+// anything else with a scheme, like javascript:, isn't linked at all
+pub fn link(base: &str, url: &str) -> Option<String> {
+    if url.starts_with("//") {
+        return Some(url.to_string());
+    }
+    match url::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Some(url.to_string()),
+        Ok(_parsed) => None,
+        Err(_e) => Some(format!("{}/{}", base, url.trim_start_matches('/'))),
+    }
+}
+
+// `{{link this.url}}` is a root-relative link to a page of the site, escaped for an attribute
+#[derive(Debug, Default)]
+pub struct LinkHelper {
+    // the site url's path without its trailing slash, like /blog
+    pub base: String,
+}
+
+impl LinkHelper {
+    pub fn new(site_url: Option<&str>) -> LinkHelper {
+        LinkHelper {
+            base: site_url.map(site_path).unwrap_or_default(),
+        }
+    }
+}
+
+impl HelperDef for LinkHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _r: &'reg Handlebars<'reg>,
+        _ctx: &'rc Context,
+        _rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let url = h
+            .param(0)
+            .and_then(|p| p.value().as_str())
+            .unwrap_or_default();
+        if let Some(link) = link(&self.base, url) {
+            out.write(&html_escape(&link))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_languages_from_file_names() {
+        let languages = vec!["en".to_string(), "de".to_string()];
+        let german = Path::new("posts/hello.de.md");
+        assert_eq!(filename_lang(german, &languages), Some("de".to_string()));
+        assert_eq!(
+            without_lang(german, &languages),
+            Path::new("posts/hello.md")
+        );
+        assert_eq!(
+            translation_key(german, &languages),
+            translation_key(Path::new("posts/hello.md"), &languages)
+        );
+
+        // a dotted name that isn't a language stays as it is
+        let version = Path::new("posts/release.v2.md");
+        assert_eq!(filename_lang(version, &languages), None);
+        assert_eq!(without_lang(version, &languages), version);
+
+        let url = "hello.html".to_string();
+        assert_eq!(
+            prefixed(url.clone(), Some("de"), "en", &languages),
+            "de/hello.html"
+        );
+        assert_eq!(
+            prefixed(url.clone(), Some("en"), "en", &languages),
+            "hello.html"
+        );
+        assert_eq!(prefixed(url, Some("fr"), "en", &languages), "hello.html");
+    }
+
+    #[test]
+    fn links_site_pages_from_the_site_root() {
+        let root = LinkHelper::new(Some("https://example.com/"));
+        let link = |base: &str, url: &str| link(base, url).unwrap_or_default();
+        assert_eq!(link(&root.base, "2023/hello.html"), "/2023/hello.html");
+        assert_eq!(link(&root.base, "/tags.html"), "/tags.html");
+
+        let nested = LinkHelper::new(Some("https://example.com/blog/"));
+        assert_eq!(link(&nested.base, "de/hello.html"), "/blog/de/hello.html");
+
+        // planet items link to other sites
+        let remote = "https://remote.example/post.html";
+        assert_eq!(link(&root.base, remote), remote);
+        assert_eq!(link(&root.base, "//cdn.example/x"), "//cdn.example/x");
+        assert_eq!(LinkHelper::new(None).base, "");
+    }
+
+    #[test]
+    fn escapes_links_and_drops_other_schemes() -> color_eyre::Result<()> {
+        let mut hbs = Handlebars::new();
+        hbs.register_helper("link", Box::new(LinkHelper::new(None)));
+        hbs.register_template_string("a", "<a href=\"{{link url}}\">")?;
+        let render = |url: &str| hbs.render("a", &serde_json::json!({ "url": url }));
+        assert_eq!(
+            render("/x\"onmouseover=\"alert(1)")?,
+            "<a href=\"/x&quot;onmouseover&#x3D;&quot;alert(1)\">"
+        );
+        assert_eq!(render("javascript:alert(1)")?, "<a href=\"\">");
+        assert_eq!(render("data:text/html,hi")?, "<a href=\"\">");
+        Ok(())
+    }
+}
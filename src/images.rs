@@ -0,0 +1,324 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path};
+
+use color_eyre::Result;
+use handlebars::{Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::builder::FileEntry;
+use crate::prettify::{attribute, tag_end};
+
+#[derive(Debug, Error)]
+enum ImagesError {
+    #[cfg(feature = "images")]
+    #[error("Unable to resize {0}: {1}")]
+    Resize(String, String),
+
+    #[cfg(not(feature = "images"))]
+    #[error("Can't resize {0}, site-gen was built without the images feature")]
+    NoImages(String),
+}
+
+// the `[images]` table: writing it, even empty, turns resizing on
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImageConfig {
+    /// Widths in pixels to resize images posts use to; images are never made larger
+    #[serde(default = "default_widths")]
+    pub widths: Vec<u32>,
+}
+
+fn default_widths() -> Vec<u32> {
+    vec![480, 960, 1600]
+}
+
+// one resized copy of an image, its path relative to dest
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub width: u32,
+    pub path: String,
+}
+
+// the images from the static dir a post uses: its hero and share images and every <img> in
+// its html, relative to the site root
+pub fn referenced(entry: &FileEntry) -> Vec<String> {
+    let mut paths: Vec<_> = entry
+        .hero_image
+        .iter()
+        .chain(entry.share_image.iter())
+        .filter_map(|src| local_path("", src))
+        .collect();
+    let dir = page_dir(&entry.url);
+    paths.extend(
+        img_tags(&entry.contents)
+            .filter_map(|tag| attribute(tag, "src"))
+            .filter_map(|src| local_path(dir, src)),
+    );
+    paths
+}
+
+// the dir a page's relative links resolve against: 2024 for 2024/post.html, post for post/
+fn page_dir(url: &str) -> &str {
+    url.rsplit_once('/').map_or("", |(dir, _file)| dir)
+}
+
+// where under the site root src points from a page in dir. remote and inline images aren't
+// ours to resize, and neither is anything that climbs out with ..
+fn local_path(dir: &str, src: &str) -> Option<String> {
+    if src.contains("://") || src.starts_with("//") || src.starts_with("data:") {
+        return None;
+    }
+    let path = match src.strip_prefix('/') {
+        Some(rooted) => Path::new(rooted).to_owned(),
+        None => Path::new(dir).join(src),
+    };
+    let normal = path.components().all(|c| matches!(c, Component::Normal(_)));
+    let path = path.to_str()?;
+    (normal && !path.is_empty()).then(|| path.to_string())
+}
+
+fn img_tags(html: &str) -> impl Iterator<Item = &str> {
+    html.match_indices("<img").filter_map(|(start, _)| {
+        let rest = &html[start..];
+        rest[4..]
+            .starts_with(|c: char| c.is_whitespace())
+            .then(|| &rest[..tag_end(rest)])
+    })
+}
+
+// images/hero.jpg at 480 pixels wide is images/hero-480.jpg
+fn variant_path(path: &str, width: u32) -> String {
+    match path.rsplit_once('.') {
+        Some((stem, ext)) if !stem.ends_with('/') => format!("{}-{}.{}", stem, width, ext),
+        _ => format!("{}-{}", path, width),
+    }
+}
+
+// resized copies of source for each width narrower than it, written next to where the
+// original goes in dest; copies newer than the source are left alone. images that can't be
+// decoded, like svgs, get none
+pub fn resize(
+    source: &Path,
+    path: &str,
+    dest: &Path,
+    widths: &[u32],
+    dry_run: bool,
+) -> Result<Vec<Variant>> {
+    let original_width = match image_width(source, path)? {
+        Some(width) => width,
+        None => return Ok(vec![]),
+    };
+    let mut variants = vec![];
+    for &width in widths.iter().filter(|w| **w < original_width) {
+        let variant = Variant {
+            width,
+            path: variant_path(path, width),
+        };
+        let output = dest.join(&variant.path);
+        if !dry_run && is_stale(source, &output) {
+            if let Some(parent) = output.parent() {
+                fs::DirBuilder::new().recursive(true).create(parent)?;
+            }
+            if !resize_to(source, path, &output, width)? {
+                return Ok(vec![]);
+            }
+        }
+        variants.push(variant);
+    }
+    Ok(variants)
+}
+
+fn is_stale(source: &Path, output: &Path) -> bool {
+    let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    match (modified(source), modified(output)) {
+        (Some(source), Some(output)) => source > output,
+        _ => true,
+    }
+}
+
+// None for a format image can't read, which is left as it is
+#[cfg(feature = "images")]
+fn image_width(source: &Path, path: &str) -> Result<Option<u32>> {
+    match image::image_dimensions(source) {
+        Ok((width, _height)) => Ok(Some(width)),
+        Err(e) => {
+            log::warn!("Not resizing {}: {}", path, e);
+            Ok(None)
+        }
+    }
+}
+
+// false when source turns out not to decode after all
+#[cfg(feature = "images")]
+fn resize_to(source: &Path, path: &str, output: &Path, width: u32) -> Result<bool> {
+    let image = match image::open(source) {
+        Ok(image) => image,
+        Err(e) => {
+            log::warn!("Not resizing {}: {}", path, e);
+            return Ok(false);
+        }
+    };
+    image
+        .resize(width, u32::MAX, image::imageops::FilterType::Lanczos3)
+        .save(output)
+        .map_err(|e| ImagesError::Resize(path.to_string(), e.to_string()))?;
+    Ok(true)
+}
+
+#[cfg(not(feature = "images"))]
+fn image_width(_source: &Path, path: &str) -> Result<Option<u32>> {
+    Err(ImagesError::NoImages(path.to_string()).into())
+}
+
+#[cfg(not(feature = "images"))]
+fn resize_to(_source: &Path, path: &str, _output: &Path, _width: u32) -> Result<bool> {
+    Err(ImagesError::NoImages(path.to_string()).into())
+}
+
+// "images/a-480.jpg 480w, images/a-960.jpg 960w", relative the way src is
+fn srcset(src: &str, variants: &[Variant]) -> String {
+    let sizes: Vec<_> = variants
+        .iter()
+        .map(|v| format!("{} {}w", variant_path(src, v.width), v.width))
+        .collect();
+    sizes.join(", ")
+}
+
+// give every <img> with resized copies in the html of the page at url a srcset, unless it
+// already has one
+pub fn add_srcset(html: &str, url: &str, images: &BTreeMap<String, Vec<Variant>>) -> String {
+    let dir = page_dir(url);
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    for tag in img_tags(html) {
+        let start = tag.as_ptr() as usize - rest.as_ptr() as usize;
+        out.push_str(&rest[..start]);
+        rest = &rest[start + tag.len()..];
+
+        let variants = attribute(tag, "src")
+            .filter(|_src| attribute(tag, "srcset").is_none())
+            .and_then(|src| Some((src, images.get(&local_path(dir, src)?)?)))
+            .filter(|(_src, variants)| !variants.is_empty());
+        let (src, variants) = match variants {
+            Some(found) => found,
+            None => {
+                out.push_str(tag);
+                continue;
+            }
+        };
+        let end = tag.trim_end_matches('>').trim_end_matches('/').trim_end();
+        out.push_str(end);
+        out.push_str(&format!(" srcset=\"{}\"", srcset(src, variants)));
+        out.push_str(&tag[end.len()..]);
+    }
+    out.push_str(rest);
+    out
+}
+
+// `{{srcset hero_image}}` is the srcset for an image, or nothing when it has no resized copies
+#[derive(Debug, Default)]
+pub struct SrcsetHelper {
+    pub images: BTreeMap<String, Vec<Variant>>,
+}
+
+impl HelperDef for SrcsetHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _r: &'reg Handlebars<'reg>,
+        _ctx: &'rc Context,
+        _rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let src = h
+            .param(0)
+            .and_then(|p| p.value().as_str())
+            .unwrap_or_default();
+        if let Some(variants) = local_path("", src).and_then(|path| self.images.get(&path)) {
+            out.write(&srcset(src, variants))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_srcsets_to_resized_images_only() {
+        let images = BTreeMap::from([(
+            "images/a.jpg".to_string(),
+            vec![
+                Variant {
+                    width: 480,
+                    path: variant_path("images/a.jpg", 480),
+                },
+                Variant {
+                    width: 960,
+                    path: variant_path("images/a.jpg", 960),
+                },
+            ],
+        )]);
+        let html = "<p><img src=\"/images/a.jpg\" alt=\"a\"></p><img src='images/a.jpg'/>\
+                    <img src=\"b.png\"><img src=\"https://x.com/a.jpg\"><imgs>";
+        assert_eq!(
+            add_srcset(html, "post.html", &images),
+            "<p><img src=\"/images/a.jpg\" alt=\"a\" srcset=\"/images/a-480.jpg 480w, \
+             /images/a-960.jpg 960w\"></p><img src='images/a.jpg' srcset=\"images/a-480.jpg \
+             480w, images/a-960.jpg 960w\"/><img src=\"b.png\"><img src=\"https://x.com/a.jpg\">\
+             <imgs>"
+        );
+        assert_eq!(
+            img_tags(html)
+                .filter_map(|t| attribute(t, "src"))
+                .filter_map(|src| local_path("", src))
+                .collect::<Vec<_>>(),
+            vec!["images/a.jpg", "images/a.jpg", "b.png"]
+        );
+    }
+
+    #[test]
+    fn resolves_relative_srcs_against_the_page() {
+        let images = BTreeMap::from([(
+            "2024/pic.jpg".to_string(),
+            vec![Variant {
+                width: 480,
+                path: variant_path("2024/pic.jpg", 480),
+            }],
+        )]);
+        assert_eq!(
+            add_srcset("<img src=\"pic.jpg\">", "2024/post.html", &images),
+            "<img src=\"pic.jpg\" srcset=\"pic-480.jpg 480w\">"
+        );
+        assert_eq!(
+            local_path("post", "pic.jpg"),
+            Some("post/pic.jpg".to_string())
+        );
+        assert_eq!(local_path("2024", "/pic.jpg"), Some("pic.jpg".to_string()));
+
+        // nothing outside the static dir
+        assert_eq!(local_path("2024", "../../x.jpg"), None);
+        assert_eq!(local_path("", "/images/../../x.jpg"), None);
+        assert_eq!(local_path("", "./x.jpg"), None);
+    }
+
+    #[cfg(feature = "images")]
+    #[test]
+    fn skips_images_it_cannot_decode() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("site-gen-images-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let svg = dir.join("logo.svg");
+        fs::write(&svg, "<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>")?;
+        let broken = dir.join("broken.jpg");
+        fs::write(&broken, "not a jpeg")?;
+        for (source, path) in [(&svg, "logo.svg"), (&broken, "broken.jpg")] {
+            assert!(resize(source, path, &dir.join("out"), &[480], false)?.is_empty());
+        }
+        assert!(!dir.join("out").exists());
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, SecondsFormat};
+use color_eyre::Result;
+use thiserror::Error;
+
+pub const CONFIG_FILE: &str = ".site-gen.toml";
+
+// what a new project starts with, by path; the templates are the ones this repo builds with
+// except tag-list, which has nothing site specific in the starter
+const STARTER_FILES: [(&str, &str); 8] = [
+    (CONFIG_FILE, include_str!("starter/site-gen.toml")),
+    (
+        "templates/entry.hbs",
+        include_str!("../templates/entry.hbs"),
+    ),
+    (
+        "templates/index.hbs",
+        include_str!("../templates/index.hbs"),
+    ),
+    ("templates/tag.hbs", include_str!("../templates/tag.hbs")),
+    (
+        "templates/archive.hbs",
+        include_str!("../templates/archive.hbs"),
+    ),
+    (
+        "templates/tag-list.hbs",
+        include_str!("starter/tag-list.hbs"),
+    ),
+    (
+        "templates/style.hbs",
+        include_str!("../templates/style.hbs"),
+    ),
+    (
+        "templates/feeds.hbs",
+        include_str!("../templates/feeds.hbs"),
+    ),
+];
+
+const SAMPLE_POST: (&str, &str) = ("src/hello-world.md", include_str!("starter/post.md"));
+
+#[derive(Debug, Error)]
+enum InitError {
+    #[error("{0:?} already exists, init only creates new projects")]
+    Exists(PathBuf),
+}
+
+// write a starter config, templates and post into dir, returning the files created
+pub fn init(dir: &Path) -> Result<Vec<PathBuf>> {
+    let date = Local::now().to_rfc3339_opts(SecondsFormat::Secs, false);
+    let post = SAMPLE_POST.1.replace("{date}", &date);
+    let mut files: Vec<(PathBuf, &str)> = STARTER_FILES
+        .iter()
+        .map(|(path, contents)| (dir.join(path), *contents))
+        .collect();
+    files.push((dir.join(SAMPLE_POST.0), &post));
+
+    // check everything first so a half written project is never left behind
+    if let Some((path, _contents)) = files.iter().find(|(path, _contents)| path.exists()) {
+        return Err(InitError::Exists(path.clone()).into());
+    }
+    for (path, contents) in files.iter() {
+        if let Some(parent) = path.parent() {
+            fs::DirBuilder::new().recursive(true).create(parent)?;
+        }
+        fs::write(path, contents)?;
+    }
+    Ok(files.into_iter().map(|(path, _contents)| path).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::front_matter;
+
+    #[test]
+    fn creates_a_project_once() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("site-gen-init-{}", std::process::id()));
+        let files = init(&dir)?;
+        assert_eq!(files.len(), 9);
+        assert!(dir.join("templates/entry.hbs").is_file());
+        let post = front_matter::parse(&front_matter::read(&dir.join(SAMPLE_POST.0))?.header);
+        assert_eq!(post.title, "Hello, world");
+        assert!(post.date.is_some());
+        let config: toml::Value = toml::from_str(&fs::read_to_string(dir.join(CONFIG_FILE))?)?;
+        assert_eq!(config["src"].as_str(), Some("src"));
+
+        assert!(init(&dir).is_err());
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
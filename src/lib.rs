@@ -0,0 +1,101 @@
+//! Build a static site from markdown posts and handlebars templates.
+//!
+//! The site-gen binary is a thin wrapper around [`cli::main`]; other tools can load a
+//! [`Config`] and run a [`Builder`] themselves:
+//!
+//! ```no_run
+//! use site_gen::{Builder, Config};
+//!
+//! # fn main() -> color_eyre::Result<()> {
+//! for config in Config::load("site.toml".as_ref())? {
+//!     let mut builder = Builder::new(config)?;
+//!     builder.build()?;
+//!     for page in builder.pages() {
+//!         println!("{} -> {}", page.title, page.url);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Editor integrations can preview a single post with [`Builder::render_file`], which
+//! renders it with the site's templates and data without writing anything.
+
+mod abbreviations;
+mod assets;
+mod audit;
+mod authors;
+mod bookmarks;
+mod budgets;
+mod build_cache;
+mod builder;
+mod calendar;
+mod citations;
+mod clean;
+pub mod cli;
+mod comments;
+mod config;
+mod create;
+mod critical;
+mod daemon;
+mod deploy;
+mod digest;
+mod doctor;
+mod embeds;
+mod feed;
+mod feed_reader;
+mod fonts;
+mod front_matter;
+mod generator;
+mod helpers;
+mod highlight;
+mod i18n;
+mod images;
+mod init;
+mod links;
+mod logger;
+mod manifest;
+mod markdown;
+mod meta;
+mod minify;
+mod now;
+mod planet;
+mod prettify;
+mod prune;
+mod query;
+mod remote;
+mod render_cache;
+mod schema;
+mod search;
+mod sections;
+mod serve;
+mod sidenotes;
+mod sitemap;
+mod sites;
+mod slug_history;
+mod styles;
+mod tags;
+mod toc;
+mod typography;
+mod watch;
+mod webmentions;
+mod writer;
+
+/// Who wrote a [`PageData`].
+pub use crate::authors::Author;
+/// Renders a site from its [`Config`].
+pub use crate::builder::Builder;
+/// A parsed and rendered post.
+pub use crate::builder::FileEntry as PageData;
+/// Another post sharing tags with a [`PageData`].
+pub use crate::builder::RelatedPost;
+/// Everything a site is built with, from a config file or the command line.
+pub use crate::cli::Opt as Config;
+/// The header of a post.
+pub use crate::front_matter::FrontMatter as PageMetadata;
+/// A [`PageData`] in another of the site's languages.
+pub use crate::i18n::Translation;
+/// A heading in a [`PageData`]'s table of contents.
+pub use crate::toc::TocEntry;
+
+use crate::cli::Opt;
@@ -0,0 +1,189 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use color_eyre::Result;
+
+use crate::helpers::dest_file;
+use crate::prettify::{attribute, tag_end, tag_name};
+use crate::serve::percent_decode;
+
+// a link on a page under dest to a page, or a part of one, the site doesn't have
+#[derive(Debug, PartialEq)]
+pub struct BrokenLink {
+    pub page: PathBuf,
+    pub href: String,
+    pub missing_anchor: bool,
+}
+
+impl fmt::Display for BrokenLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let problem = match self.missing_anchor {
+            true => "an anchor that isn't on the page",
+            false => "a page that doesn't exist",
+        };
+        write!(
+            f,
+            "{} links to {}, {}",
+            self.page.display(),
+            self.href,
+            problem
+        )
+    }
+}
+
+// every link between the html pages under dest that goes nowhere, #fragments included
+pub fn broken(dest: &Path, site_url: &str) -> Result<Vec<BrokenLink>> {
+    let mut pages = vec![];
+    collect(dest, Path::new(""), &mut pages)?;
+    pages.sort();
+
+    // the ids on each page linked to with a fragment, read once
+    let mut anchors: BTreeMap<PathBuf, BTreeSet<String>> = BTreeMap::new();
+    let mut found = vec![];
+    for page in pages {
+        let html = fs::read_to_string(dest.join(&page))?;
+        for href in links(&html) {
+            let (path, fragment) = href.split_once('#').unwrap_or((&href, ""));
+            let target = match path.split('?').next().unwrap_or_default() {
+                "" => Some(page.clone()),
+                _ => match dest_file(&page, &percent_decode(path), site_url) {
+                    Some(file) => existing(dest, file),
+                    // somewhere else entirely
+                    None => continue,
+                },
+            };
+            let target = match target {
+                Some(target) => target,
+                None => {
+                    found.push(BrokenLink {
+                        page: page.clone(),
+                        href: href.clone(),
+                        missing_anchor: false,
+                    });
+                    continue;
+                }
+            };
+            let fragment = percent_decode(fragment);
+            let is_html = target
+                .extension()
+                .is_some_and(|e| e == "html" || e == "htm");
+            // an empty fragment and #top go to the top of any page
+            if fragment.is_empty() || fragment == "top" || !is_html {
+                continue;
+            }
+            if !anchors.contains_key(&target) {
+                let html = fs::read_to_string(dest.join(&target)).unwrap_or_default();
+                anchors.insert(target.clone(), page_anchors(&html));
+            }
+            if !anchors[&target].contains(&fragment) {
+                found.push(BrokenLink {
+                    page: page.clone(),
+                    href: href.clone(),
+                    missing_anchor: true,
+                });
+            }
+        }
+    }
+    Ok(found)
+}
+
+fn collect(dir: &Path, relative: &Path, pages: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir.join(relative))? {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            collect(dir, &path, pages)?;
+        } else if path.extension().is_some_and(|e| e == "html" || e == "htm") {
+            pages.push(path);
+        }
+    }
+    Ok(())
+}
+
+// the file a link to file serves, the way serve finds it: a directory serves its
+// index.html, and a path without an extension may be a page written as .html
+fn existing(dest: &Path, file: PathBuf) -> Option<PathBuf> {
+    let mut candidates = vec![file.join("index.html"), file.clone()];
+    if file.extension().is_none() {
+        candidates.push(file.with_extension("html"));
+    }
+    candidates
+        .into_iter()
+        .find(|candidate| dest.join(candidate).is_file())
+}
+
+// the hrefs of a page's links, leaving out scripts and styles
+pub fn links(html: &str) -> Vec<String> {
+    let mut found = vec![];
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let tag = &rest[..tag_end(rest)];
+        rest = &rest[tag.len()..];
+        let name = tag_name(tag);
+        match name.as_str() {
+            "script" | "style" => {
+                let close = rest.find(&format!("</{}", name)).unwrap_or(rest.len());
+                rest = &rest[close..];
+            }
+            "a" | "area" => found.extend(attribute(tag, "href").map(String::from)),
+            _ => (),
+        }
+    }
+    found
+}
+
+// what a #fragment can point at: any element's id, or an old style <a name>
+pub fn page_anchors(html: &str) -> BTreeSet<String> {
+    let mut found = BTreeSet::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let tag = &rest[..tag_end(rest)];
+        rest = &rest[tag.len()..];
+        found.extend(attribute(tag, "id").map(String::from));
+        if tag_name(tag) == "a" {
+            found.extend(attribute(tag, "name").map(String::from));
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_links_to_missing_pages_and_anchors() -> Result<()> {
+        let dest = std::env::temp_dir().join(format!("site-gen-links-{}", std::process::id()));
+        fs::create_dir_all(dest.join("posts"))?;
+        fs::write(
+            dest.join("index.html"),
+            "<a href=\"posts/hello.html#setup\">ok</a><a href=\"/posts/hello#gone\">moved</a>\
+             <a href=\"https://example.com/posts/\">ok</a><a href=\"#top\">ok</a>\
+             <a href=\"/missing.html\">missing</a><a href=\"https://elsewhere.com/#x\">ok</a>\
+             <script>var a = '<a href=\"/nope.html\">';</script>",
+        )?;
+        fs::write(
+            dest.join("posts/hello.html"),
+            "<h2 id=\"setup\">Setup</h2><a name=\"old\"></a><a href=\"#old\">ok</a>\
+             <a href=\"../index.html#nowhere\">gone</a>",
+        )?;
+        fs::write(dest.join("posts/index.html"), "")?;
+
+        let found = broken(&dest, "https://example.com")?;
+        fs::remove_dir_all(&dest)?;
+        let found: Vec<_> = found.iter().map(|b| b.to_string()).collect();
+        assert_eq!(
+            found,
+            vec![
+                "index.html links to /posts/hello#gone, an anchor that isn't on the page",
+                "index.html links to /missing.html, a page that doesn't exist",
+                "posts/hello.html links to ../index.html#nowhere, an anchor that isn't on the page",
+            ]
+        );
+        Ok(())
+    }
+}
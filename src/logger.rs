@@ -0,0 +1,118 @@
+use std::io::Write;
+
+use chrono::{Local, SecondsFormat};
+use clap::ValueEnum;
+use color_eyre::Result;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde_json::json;
+
+// how build progress is written to stderr
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Plain lines, warnings and errors marked as such
+    #[default]
+    Text,
+    /// One JSON object per line, for CI to parse
+    Json,
+}
+
+struct Logger {
+    level: LevelFilter,
+    format: LogFormat,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let time = Local::now().to_rfc3339_opts(SecondsFormat::Millis, false);
+        let line = line(
+            self.format,
+            record.level(),
+            record.target(),
+            &record.args().to_string(),
+            &time,
+        );
+        let _ = writeln!(std::io::stderr().lock(), "{}", line);
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+fn line(format: LogFormat, level: Level, target: &str, message: &str, time: &str) -> String {
+    match (format, level) {
+        (LogFormat::Json, _) => json!({
+            "time": time,
+            "level": level.as_str().to_lowercase(),
+            "target": target,
+            "message": message,
+        })
+        .to_string(),
+        (LogFormat::Text, Level::Error | Level::Warn) => {
+            format!("{}: {}", level.as_str().to_lowercase(), message)
+        }
+        (LogFormat::Text, _) => message.to_string(),
+    }
+}
+
+// send everything logged at level or above to stderr; only the first call takes effect
+pub fn init(level: LevelFilter, format: LogFormat) -> Result<()> {
+    if log::set_boxed_logger(Box::new(Logger { level, format })).is_ok() {
+        log::set_max_level(level);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_text_and_json_lines() {
+        let time = "2024-03-12T10:00:00.000-07:00";
+        assert_eq!(
+            line(
+                LogFormat::Text,
+                Level::Info,
+                "site_gen::builder",
+                "Wrote 3 pages",
+                time
+            ),
+            "Wrote 3 pages"
+        );
+        assert_eq!(
+            line(
+                LogFormat::Text,
+                Level::Warn,
+                "site_gen::builder",
+                "No preview",
+                time
+            ),
+            "warn: No preview"
+        );
+        let json: serde_json::Value = serde_json::from_str(&line(
+            LogFormat::Json,
+            Level::Debug,
+            "site_gen::builder",
+            "Parsed \"a.md\"",
+            time,
+        ))
+        .unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "time": time,
+                "level": "debug",
+                "target": "site_gen::builder",
+                "message": "Parsed \"a.md\"",
+            })
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use color_eyre::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::content_hash;
+
+pub const MANIFEST_FILE: &str = ".site-gen-manifest.json";
+const MAX_CHANGES: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Updated,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub title: String,
+    pub hash: String,
+    pub added: String,
+    pub updated: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    pub url: String,
+    pub title: String,
+    pub kind: ChangeKind,
+    pub date: String,
+}
+
+// what the previous builds produced, keyed by entry url
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub entries: BTreeMap<String, ManifestEntry>,
+
+    // newest first
+    #[serde(default)]
+    pub changes: Vec<Change>,
+
+    // every file under dest the last build wrote or would have, relative to dest
+    #[serde(default)]
+    pub outputs: BTreeSet<String>,
+
+    // files earlier builds wrote that the last one didn't, e.g. pages of renamed posts
+    #[serde(default)]
+    pub stale: BTreeSet<String>,
+
+    #[serde(skip)]
+    is_new: bool,
+}
+
+impl Manifest {
+    pub fn load(dest: &Path) -> Manifest {
+        match fs::read_to_string(manifest_path(dest)) {
+            Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+                warn!("Unable to read build manifest, starting over: {}", e);
+                Manifest::fresh()
+            }),
+            Err(_e) => Manifest::fresh(),
+        }
+    }
+
+    fn fresh() -> Manifest {
+        Manifest {
+            is_new: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn save(&self, dest: &Path) -> Result<()> {
+        fs::write(manifest_path(dest), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    // note the current state of an entry, returning what changed since the last build
+    pub fn record(&mut self, url: &str, title: &str, text: &str, date: &str) -> Option<ChangeKind> {
+        // whitespace-only edits aren't worth telling readers about
+        let normalized: Vec<&str> = text.split_whitespace().collect();
+        let hash = content_hash(normalized.join(" ").as_bytes());
+
+        let kind = match self.entries.get_mut(url) {
+            Some(existing) if existing.hash == hash => None,
+            Some(existing) => {
+                existing.hash = hash;
+                existing.title = title.to_string();
+                existing.updated = date.to_string();
+                Some(ChangeKind::Updated)
+            }
+            None => {
+                self.entries.insert(
+                    url.to_string(),
+                    ManifestEntry {
+                        title: title.to_string(),
+                        hash,
+                        added: date.to_string(),
+                        updated: date.to_string(),
+                    },
+                );
+                Some(ChangeKind::Added)
+            }
+        };
+
+        // the first build would otherwise report the entire site as new
+        if self.is_new {
+            return None;
+        }
+
+        if let Some(kind) = kind {
+            self.changes.insert(
+                0,
+                Change {
+                    url: url.to_string(),
+                    title: title.to_string(),
+                    kind,
+                    date: date.to_string(),
+                },
+            );
+            self.changes.truncate(MAX_CHANGES);
+        }
+        kind
+    }
+
+    // replace the outputs of the last build with this one's, keeping track of what it dropped
+    pub fn record_outputs(&mut self, outputs: BTreeSet<String>) {
+        let previous = std::mem::take(&mut self.outputs);
+        self.stale.extend(previous);
+        self.stale.retain(|file| !outputs.contains(file));
+        self.outputs = outputs;
+    }
+}
+
+pub fn manifest_path(dest: &Path) -> PathBuf {
+    dest.join(MANIFEST_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_build_reports_nothing() {
+        let mut manifest = Manifest::fresh();
+        assert_eq!(manifest.record("a.html", "A", "text", "now"), None);
+        assert!(manifest.changes.is_empty());
+        assert_eq!(manifest.entries.len(), 1);
+    }
+
+    #[test]
+    fn reports_additions_and_updates() {
+        let mut manifest = Manifest::default();
+        assert_eq!(
+            manifest.record("a.html", "A", "text", "1"),
+            Some(ChangeKind::Added)
+        );
+        assert_eq!(manifest.record("a.html", "A", "text", "2"), None);
+        assert_eq!(
+            manifest.record("a.html", "A", "new text", "3"),
+            Some(ChangeKind::Updated)
+        );
+        assert_eq!(manifest.changes.len(), 2);
+        assert_eq!(manifest.changes[0].kind, ChangeKind::Updated);
+        assert_eq!(manifest.entries["a.html"].added, "1");
+    }
+
+    #[test]
+    fn outputs_the_last_build_dropped_are_stale() {
+        let mut manifest = Manifest::default();
+        let set = |files: &[&str]| files.iter().map(|f| f.to_string()).collect();
+        manifest.record_outputs(set(&["a.html", "b.html"]));
+        manifest.record_outputs(set(&["a.html", "c.html"]));
+        assert_eq!(manifest.stale, set(&["b.html"]));
+        manifest.record_outputs(set(&["b.html"]));
+        assert_eq!(manifest.stale, set(&["a.html", "c.html"]));
+        assert_eq!(manifest.outputs, set(&["b.html"]));
+    }
+
+    #[test]
+    fn ignores_whitespace_changes() {
+        let mut manifest = Manifest::default();
+        manifest.record("a.html", "A", "some text", "1");
+        assert_eq!(manifest.record("a.html", "A", "some\n  text ", "2"), None);
+    }
+}
@@ -0,0 +1,89 @@
+use comrak::ComrakOptions;
+use serde::{Deserialize, Serialize};
+
+// the `[markdown]` table: which comrak extensions posts are parsed with
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarkdownConfig {
+    /// Pass html in posts through; never on for sandboxed builds
+    pub raw_html: bool,
+    /// Escape html tags that are unsafe even when raw html is allowed, like <script>; always
+    /// on for sandboxed builds
+    pub tagfilter: bool,
+    pub tables: bool,
+    pub footnotes: bool,
+    /// - [ ] and - [x] list items as checkboxes
+    pub tasklist: bool,
+    /// Bare urls and email addresses as links
+    pub autolink: bool,
+    /// ^superscript^
+    pub superscript: bool,
+    /// ~~strikethrough~~
+    pub strikethrough: bool,
+    pub description_lists: bool,
+    /// Curly quotes, dashes and ellipses
+    pub smart_punctuation: bool,
+    /// An id on every heading made from its text, so links can go straight to a section; on
+    /// regardless when a template shows the table of contents
+    pub heading_ids: bool,
+}
+
+impl Default for MarkdownConfig {
+    fn default() -> MarkdownConfig {
+        MarkdownConfig {
+            raw_html: true,
+            tagfilter: false,
+            tables: false,
+            footnotes: false,
+            tasklist: false,
+            autolink: false,
+            superscript: false,
+            strikethrough: true,
+            description_lists: true,
+            smart_punctuation: true,
+            heading_ids: false,
+        }
+    }
+}
+
+impl MarkdownConfig {
+    // untrusted posts can't embed raw html, whatever the config says
+    pub fn options(&self, sandbox: bool) -> ComrakOptions {
+        let mut options = ComrakOptions::default();
+        options.render.unsafe_ = self.raw_html && !sandbox;
+        options.parse.smart = self.smart_punctuation;
+        options.extension.tagfilter = self.tagfilter || sandbox;
+        options.extension.table = self.tables;
+        options.extension.footnotes = self.footnotes;
+        options.extension.tasklist = self.tasklist;
+        options.extension.autolink = self.autolink;
+        options.extension.superscript = self.superscript;
+        options.extension.strikethrough = self.strikethrough;
+        options.extension.description_lists = self.description_lists;
+        options.extension.header_ids = self.heading_ids.then(String::new);
+        options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sandbox_overrides_raw_html() {
+        let config: MarkdownConfig = toml::from_str("tables = true\nfootnotes = true").unwrap();
+        assert_eq!(
+            config,
+            MarkdownConfig {
+                tables: true,
+                footnotes: true,
+                ..Default::default()
+            }
+        );
+        let options = config.options(false);
+        assert!(options.render.unsafe_ && !options.extension.tagfilter);
+        assert!(options.extension.table && options.extension.footnotes);
+        let sandboxed = config.options(true);
+        assert!(!sandboxed.render.unsafe_ && sandboxed.extension.tagfilter);
+    }
+}
@@ -0,0 +1,255 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, NaiveDate};
+use clap::{Args, Subcommand};
+use color_eyre::Result;
+use log::warn;
+use thiserror::Error;
+
+use crate::front_matter::{self, HEADER_DELIMITER};
+use crate::helpers::{get_posts, wildcard_match};
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum MetaAction {
+    /// Set a key, adding it to headers that don't have it
+    Set { key: String, value: String },
+
+    /// Remove a key
+    Remove { key: String },
+
+    /// Rename a key, keeping its value
+    Rename { from: String, to: String },
+
+    /// Give every post without an id one, for feeds and url history that survive renames
+    AssignIds,
+}
+
+// which posts a bulk edit applies to; all filters must match
+#[derive(Debug, Clone, Args)]
+pub struct PostFilter {
+    /// Only posts with this tag
+    #[arg(long)]
+    pub tag: Option<String>,
+
+    /// Only posts dated on or after this day (YYYY-MM-DD)
+    #[arg(long)]
+    pub since: Option<String>,
+
+    /// Only posts dated on or before this day (YYYY-MM-DD)
+    #[arg(long)]
+    pub until: Option<String>,
+
+    /// Only files whose name matches this pattern, e.g. "2024-*.md"
+    #[arg(long)]
+    pub glob: Option<String>,
+}
+
+#[derive(Debug, Error)]
+enum MetaError {
+    #[error("{0} is not a date, use YYYY-MM-DD")]
+    BadDate(String),
+}
+
+fn parse_day(day: &str) -> Result<NaiveDate> {
+    match NaiveDate::parse_from_str(day, "%Y-%m-%d") {
+        Ok(d) => Ok(d),
+        Err(_e) => match DateTime::parse_from_rfc3339(day) {
+            Ok(d) => Ok(d.date_naive()),
+            Err(_e) => Err(MetaError::BadDate(day.to_string()).into()),
+        },
+    }
+}
+
+impl PostFilter {
+    fn matches(&self, file: &Path, buf: &str) -> Result<bool> {
+        if let Some(pattern) = &self.glob {
+            let name = file.file_name().map(|n| n.to_string_lossy().to_string());
+            if !wildcard_match(pattern, &name.unwrap_or_default()) {
+                return Ok(false);
+            }
+        }
+
+        let front_matter = front_matter::parse(buf);
+        if let Some(tag) = &self.tag {
+            if !front_matter.tags.contains(tag) {
+                return Ok(false);
+            }
+        }
+
+        if self.since.is_some() || self.until.is_some() {
+            let day = match front_matter.date {
+                Some(d) => d.date_naive(),
+                None => return Ok(false),
+            };
+            if let Some(since) = &self.since {
+                if day < parse_day(since)? {
+                    return Ok(false);
+                }
+            }
+            if let Some(until) = &self.until {
+                if day > parse_day(until)? {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+// apply an edit to every matching post, returning the files that changed (or would change)
+pub fn run(
+    src: &Path,
+    action: &MetaAction,
+    filter: &PostFilter,
+    dry_run: bool,
+) -> Result<Vec<PathBuf>> {
+    let mut changed = vec![];
+    for file in get_posts(src)? {
+        let buf = fs::read_to_string(&file)?;
+        if !filter.matches(&file, &buf)? {
+            continue;
+        }
+        if let Some(updated) = edit(&buf, action) {
+            if !dry_run {
+                fs::write(&file, updated)?;
+            }
+            changed.push(file);
+        }
+    }
+    Ok(changed)
+}
+
+fn line_key(line: &str) -> Option<&str> {
+    line.split(' ').next()?.strip_suffix(':')
+}
+
+// edit one header, touching only the affected line; None when nothing changes
+pub fn edit(buf: &str, action: &MetaAction) -> Option<String> {
+    let newline = if buf.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut lines: Vec<String> = buf.split_inclusive('\n').map(String::from).collect();
+
+    // the index of the closing delimiter
+    let end = match front_matter::split(buf).0.split_inclusive('\n').count() {
+        count if count > 0 => count - 1,
+        // a post without a header only gains one when setting a key; bare posts keep their
+        // defaults rather than get an id
+        _ => {
+            return match action {
+                MetaAction::Set { key, value } => Some(format!(
+                    "{d}{n}{k}: {v}{n}{d}{n}{b}",
+                    d = HEADER_DELIMITER,
+                    n = newline,
+                    k = key,
+                    v = value,
+                    b = buf
+                )),
+                _ => None,
+            }
+        }
+    };
+
+    let find = |key: &str| (1..end).find(|i| line_key(&lines[*i]) == Some(key));
+    let line_ending = |line: &str| line[line.trim_end_matches(['\r', '\n']).len()..].to_string();
+
+    match action {
+        MetaAction::Set { key, value } => {
+            let line = format!("{}: {}", key, value);
+            match find(key) {
+                Some(i) => {
+                    let ending = line_ending(&lines[i]);
+                    if lines[i] == format!("{}{}", line, ending) {
+                        return None;
+                    }
+                    lines[i] = format!("{}{}", line, ending);
+                }
+                None => lines.insert(end, format!("{}{}", line, newline)),
+            }
+        }
+        MetaAction::AssignIds => {
+            if find("id").is_some() {
+                return None;
+            }
+            let line = format!("id: {}{}", front_matter::new_id(), newline);
+            lines.insert(end, line);
+        }
+        MetaAction::Remove { key } => {
+            lines.remove(find(key)?);
+        }
+        MetaAction::Rename { from, to } => {
+            if find(to).is_some() {
+                warn!("Not renaming {} to {}, {} already exists", from, to, to);
+                return None;
+            }
+            let i = find(from)?;
+            let rest = lines[i][from.len() + 1..].to_string();
+            lines[i] = format!("{}:{}", to, rest);
+        }
+    }
+    Some(lines.concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POST: &str = "---\ntitle: A post\ntags: rust\n---\n\nbody: text\n";
+
+    #[test]
+    fn sets_existing_and_new_keys() {
+        let set = |key: &str, value: &str| MetaAction::Set {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        assert_eq!(
+            edit(POST, &set("title", "Renamed")).unwrap(),
+            "---\ntitle: Renamed\ntags: rust\n---\n\nbody: text\n"
+        );
+        assert_eq!(
+            edit(POST, &set("lang", "en")).unwrap(),
+            "---\ntitle: A post\ntags: rust\nlang: en\n---\n\nbody: text\n"
+        );
+        assert_eq!(edit(POST, &set("title", "A post")), None);
+        assert_eq!(
+            edit("just text\n", &set("title", "x")).unwrap(),
+            "---\ntitle: x\n---\njust text\n"
+        );
+    }
+
+    #[test]
+    fn removes_and_renames_keys() {
+        let remove = MetaAction::Remove {
+            key: "tags".to_string(),
+        };
+        assert_eq!(
+            edit(POST, &remove).unwrap(),
+            "---\ntitle: A post\n---\n\nbody: text\n"
+        );
+        let rename = MetaAction::Rename {
+            from: "tags".to_string(),
+            to: "categories".to_string(),
+        };
+        assert_eq!(
+            edit(POST, &rename).unwrap(),
+            "---\ntitle: A post\ncategories: rust\n---\n\nbody: text\n"
+        );
+        // keys in the body are never touched
+        let remove_body = MetaAction::Remove {
+            key: "body".to_string(),
+        };
+        assert_eq!(edit(POST, &remove_body), None);
+        let ruled = "Intro\n\n---\nbody: text\n---\n";
+        assert_eq!(edit(ruled, &remove_body), None);
+    }
+
+    #[test]
+    fn assigns_ids_once() {
+        let with_id = edit(POST, &MetaAction::AssignIds).unwrap();
+        let id = front_matter::parse(&with_id).id.unwrap();
+        assert_eq!(id.len(), 12);
+        assert!(with_id.starts_with(&format!("---\ntitle: A post\ntags: rust\nid: {}\n---", id)));
+        assert_eq!(edit(&with_id, &MetaAction::AssignIds), None);
+        assert_eq!(edit("just text\n", &MetaAction::AssignIds), None);
+        assert_ne!(front_matter::new_id(), front_matter::new_id());
+    }
+}
@@ -0,0 +1,85 @@
+use crate::prettify::{tag_end, tag_name, BLOCK_ELEMENTS, RAW_ELEMENTS};
+
+// shrink rendered html without changing how it displays: comments go, except ie conditional
+// ones, runs of whitespace become one space and whitespace beside block elements goes
+// entirely; pre, script, style and textarea are copied as they are
+pub fn minify(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    // the name of the last tag written, so whitespace after a block tag can be dropped
+    let mut last_tag = String::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let start = rest.find('<').unwrap_or(rest.len());
+        if start > 0 {
+            let next_tag = tag_name(&rest[start..]);
+            text(&rest[..start], &last_tag, &next_tag, &mut out);
+            rest = &rest[start..];
+            continue;
+        }
+
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |e| e + 3);
+            if rest.starts_with("<!--[if") {
+                out.push_str(&rest[..end]);
+            }
+            rest = &rest[end..];
+            continue;
+        }
+        let end = tag_end(rest);
+        let tag = &rest[..end];
+        rest = &rest[end..];
+        out.push_str(tag);
+        last_tag = tag_name(tag);
+
+        if RAW_ELEMENTS.contains(&last_tag.as_str()) {
+            let closing = format!("</{}", last_tag);
+            let end = rest
+                .to_ascii_lowercase()
+                .find(&closing)
+                .unwrap_or(rest.len());
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+        }
+    }
+    out
+}
+
+// text between two tags, collapsed
+fn text(text: &str, last_tag: &str, next_tag: &str, out: &mut String) {
+    let is_block = |tag: &str| BLOCK_ELEMENTS.contains(&tag.trim_start_matches('/'));
+    let mut collapsed = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            space = true;
+            continue;
+        }
+        if space && (!collapsed.is_empty() || !is_block(last_tag)) {
+            collapsed.push(' ');
+        }
+        space = false;
+        collapsed.push(c);
+    }
+    if space && !is_block(next_tag) && !(collapsed.is_empty() && is_block(last_tag)) {
+        collapsed.push(' ');
+    }
+    out.push_str(&collapsed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_comments_and_whitespace_but_not_raw_content() {
+        let html = "<!DOCTYPE html>\n<html>\n  <head>\n    <!-- nav -->\n    <title> Hi </title>\n\
+                    <!--[if IE]><p>old</p><![endif]-->\n  </head>\n  <body>\n    <p>\n      A \
+                    <em>big</em>\n      day\n    </p>\n    <pre>keep\n    this</pre>\n  </body>\n\
+                    </html>\n";
+        assert_eq!(
+            minify(html),
+            "<!DOCTYPE html><html><head><title>Hi</title><!--[if IE]><p>old</p><![endif]-->\
+             </head><body><p>A <em>big</em> day</p><pre>keep\n    this</pre></body></html>"
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::feed_reader::{parse_feed, FeedItem};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+    Rss,
+    Lastfm,
+    Letterboxd,
+    Goodreads,
+}
+
+// the shape every typed source is normalized into
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct NowItem {
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub url: Option<String>,
+    pub image: Option<String>,
+    pub date: Option<String>,
+    pub rating: Option<String>,
+    pub current: bool,
+}
+
+pub fn normalize(kind: SourceKind, body: &str) -> Result<Value> {
+    let items = match kind {
+        SourceKind::Lastfm => lastfm(&serde_json::from_str(body)?),
+        SourceKind::Rss => parse_feed(body)?.iter().map(rss).collect(),
+        SourceKind::Letterboxd => parse_feed(body)?.iter().map(letterboxd).collect(),
+        SourceKind::Goodreads => parse_feed(body)?.iter().map(goodreads).collect(),
+    };
+    Ok(json!({ "items": items }))
+}
+
+fn field(item: &FeedItem, names: &[&str]) -> Option<String> {
+    names.iter().find_map(|n| item.get(*n).cloned())
+}
+
+// pull the first image out of an HTML description, which is where most feeds hide covers
+fn first_image(html: &str) -> Option<String> {
+    let start = html.find("src=\"")? + 5;
+    let end = html[start..].find('"')? + start;
+    Some(html[start..end].to_string())
+}
+
+fn rss(item: &FeedItem) -> NowItem {
+    NowItem {
+        title: field(item, &["title"]).unwrap_or_default(),
+        url: field(item, &["link"]),
+        image: field(item, &["thumbnail", "enclosure"])
+            .or_else(|| field(item, &["description", "content"]).and_then(|d| first_image(&d))),
+        date: field(item, &["pubDate", "published", "updated"]),
+        ..Default::default()
+    }
+}
+
+fn letterboxd(item: &FeedItem) -> NowItem {
+    NowItem {
+        title: field(item, &["filmTitle", "title"]).unwrap_or_default(),
+        subtitle: field(item, &["filmYear"]),
+        url: field(item, &["link"]),
+        image: field(item, &["description"]).and_then(|d| first_image(&d)),
+        date: field(item, &["watchedDate", "pubDate"]),
+        rating: field(item, &["memberRating"]),
+        ..Default::default()
+    }
+}
+
+fn goodreads(item: &FeedItem) -> NowItem {
+    NowItem {
+        title: field(item, &["title"]).unwrap_or_default(),
+        subtitle: field(item, &["author_name"]),
+        url: field(item, &["link"]),
+        image: field(item, &["book_large_image_url", "book_image_url"]),
+        date: field(item, &["user_read_at", "user_date_added", "pubDate"])
+            .filter(|d| !d.is_empty()),
+        rating: field(item, &["user_rating"]).filter(|r| r != "0"),
+        current: field(item, &["user_read_at"]).is_none(),
+    }
+}
+
+fn lastfm(data: &Value) -> Vec<NowItem> {
+    let tracks = match data["recenttracks"]["track"].as_array() {
+        Some(t) => t,
+        None => return vec![],
+    };
+    tracks
+        .iter()
+        .map(|track| {
+            let text = |v: &Value| v.as_str().map(String::from);
+            NowItem {
+                title: text(&track["name"]).unwrap_or_default(),
+                subtitle: text(&track["artist"]["#text"]),
+                url: text(&track["url"]),
+                image: track["image"]
+                    .as_array()
+                    .and_then(|i| i.last())
+                    .and_then(|i| text(&i["#text"]))
+                    .filter(|i| !i.is_empty()),
+                date: text(&track["date"]["#text"]),
+                rating: None,
+                current: track["@attr"]["nowplaying"] == "true",
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_lastfm_tracks() -> Result<()> {
+        let body = r##"{"recenttracks": {"track": [
+            {"name": "Song", "artist": {"#text": "Band"}, "url": "https://last.fm/song",
+             "image": [{"#text": "small.jpg"}, {"#text": "large.jpg"}],
+             "@attr": {"nowplaying": "true"}}
+        ]}}"##;
+        let data = normalize(SourceKind::Lastfm, body)?;
+        assert_eq!(data["items"][0]["title"], "Song");
+        assert_eq!(data["items"][0]["subtitle"], "Band");
+        assert_eq!(data["items"][0]["image"], "large.jpg");
+        assert_eq!(data["items"][0]["current"], true);
+        Ok(())
+    }
+
+    #[test]
+    fn normalizes_letterboxd_items() -> Result<()> {
+        let body = r#"<rss xmlns:letterboxd="https://letterboxd.com"><channel><item>
+            <title>Heat, 1995 - ★★★★★</title><link>https://letterboxd.com/heat</link>
+            <letterboxd:filmTitle>Heat</letterboxd:filmTitle>
+            <letterboxd:filmYear>1995</letterboxd:filmYear>
+            <letterboxd:memberRating>5.0</letterboxd:memberRating>
+            <description><![CDATA[<p><img src="https://img/heat.jpg"/></p>]]></description>
+            </item></channel></rss>"#;
+        let data = normalize(SourceKind::Letterboxd, body)?;
+        assert_eq!(data["items"][0]["title"], "Heat");
+        assert_eq!(data["items"][0]["rating"], "5.0");
+        assert_eq!(data["items"][0]["image"], "https://img/heat.jpg");
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, FixedOffset};
+use color_eyre::Result;
+use log::warn;
+use quick_xml::events::Event;
+use quick_xml::{Reader, XmlVersion};
+use serde::{Deserialize, Serialize};
+use voca_rs::strip::strip_tags;
+
+use crate::feed_reader::{parse_feed, FeedItem};
+use crate::remote::fetch_cached;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlanetConfig {
+    /// Feeds to aggregate, keyed by the name shown next to their items
+    #[serde(default)]
+    pub feeds: BTreeMap<String, String>,
+    /// An OPML subscription list to aggregate as well
+    pub opml: Option<String>,
+    pub title: Option<String>,
+    #[serde(default = "default_ttl")]
+    pub ttl: u64,
+}
+
+fn default_ttl() -> u64 {
+    3600
+}
+
+// one item from an external feed, in the shape the builder renders posts
+#[derive(Debug, PartialEq)]
+pub struct PlanetItem {
+    pub title: String,
+    pub url: String,
+    pub contents: String,
+    pub source: String,
+    pub tags: Vec<String>,
+    pub date: Option<DateTime<FixedOffset>>,
+}
+
+// fetch every subscribed feed (through the remote cache), newest items first
+pub fn fetch(config: &PlanetConfig, cache_dir: &Path, offline: bool) -> Result<Vec<PlanetItem>> {
+    let mut feeds = config.feeds.clone();
+    if let Some(path) = &config.opml {
+        feeds.extend(parse_opml(&fs::read_to_string(path)?)?);
+    }
+
+    let mut items = vec![];
+    for (name, url) in feeds.iter() {
+        let cache_name = format!("planet-{}", name);
+        let body = match fetch_cached(&cache_name, url, config.ttl, cache_dir, offline) {
+            Ok(b) => b,
+            // one dead feed shouldn't take the whole planet down
+            Err(e) => {
+                warn!("Skipping feed {}: {}", name, e);
+                continue;
+            }
+        };
+        for item in parse_feed(&body)? {
+            items.push(normalize(name, &item));
+        }
+    }
+    // undated items sink to the bottom
+    items.sort_by_key(|i| std::cmp::Reverse(i.date));
+    Ok(items)
+}
+
+fn normalize(source: &str, item: &FeedItem) -> PlanetItem {
+    let field = |names: &[&str]| names.iter().find_map(|n| item.get(*n).cloned());
+    let date = field(&["pubDate", "published", "updated", "date"]).and_then(|d| {
+        DateTime::parse_from_rfc3339(&d)
+            .or_else(|_e| DateTime::parse_from_rfc2822(&d))
+            .ok()
+    });
+    // feed content is someone else's html, keep only its text
+    let contents = field(&["content", "encoded", "description", "summary"])
+        .map(|c| format!("<p>{}</p>", strip_tags(&c).trim()))
+        .unwrap_or_default();
+
+    PlanetItem {
+        title: strip_tags(&field(&["title"]).unwrap_or_default()),
+        url: field(&["link", "guid", "id"]).unwrap_or_default(),
+        contents,
+        source: source.to_string(),
+        tags: field(&["category"]).into_iter().collect(),
+        date,
+    }
+}
+
+// every <outline> with an xmlUrl, keyed by its title
+fn parse_opml(xml: &str) -> Result<BTreeMap<String, String>> {
+    let mut reader = Reader::from_str(xml);
+    let mut feeds = BTreeMap::new();
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == "outline" => {
+                let attr = |name: &str| -> Result<Option<String>> {
+                    match e.try_get_attribute(name)? {
+                        Some(a) => Ok(Some(
+                            a.normalized_value(XmlVersion::Implicit1_0)?.to_string(),
+                        )),
+                        None => Ok(None),
+                    }
+                };
+                if let Some(url) = attr("xmlUrl")? {
+                    let name = attr("title")?
+                        .or(attr("text")?)
+                        .unwrap_or_else(|| url.clone());
+                    feeds.insert(name, url);
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+    Ok(feeds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_opml_outlines() -> Result<()> {
+        let xml = r#"<opml version="2.0"><body>
+            <outline text="Friends">
+              <outline text="Jane" xmlUrl="https://jane.example/feed.xml"/>
+              <outline title="Sam" text="ignored" xmlUrl="https://sam.example/atom.xml"/>
+            </outline></body></opml>"#;
+        let feeds = parse_opml(xml)?;
+        assert_eq!(feeds.len(), 2);
+        assert_eq!(feeds["Jane"], "https://jane.example/feed.xml");
+        assert_eq!(feeds["Sam"], "https://sam.example/atom.xml");
+        Ok(())
+    }
+
+    #[test]
+    fn normalizes_rss_and_atom_dates() {
+        let item = FeedItem::from([
+            ("title".to_string(), "Hi".to_string()),
+            ("link".to_string(), "https://jane.example/hi".to_string()),
+            (
+                "pubDate".to_string(),
+                "Tue, 12 Mar 2024 10:00:00 -0700".to_string(),
+            ),
+            (
+                "description".to_string(),
+                "<script>x()</script><b>bold</b> words".to_string(),
+            ),
+        ]);
+        let normalized = normalize("Jane", &item);
+        assert_eq!(normalized.url, "https://jane.example/hi");
+        assert_eq!(
+            normalized.date,
+            DateTime::parse_from_rfc3339("2024-03-12T10:00:00-07:00").ok()
+        );
+        assert!(!normalized.contents.contains("<b>"));
+    }
+}
@@ -0,0 +1,358 @@
+// elements that start their own line; anything else stays in the line it's part of
+pub const BLOCK_ELEMENTS: [&str; 48] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "dd",
+    "details",
+    "dialog",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hr",
+    "html",
+    "li",
+    "link",
+    "main",
+    "meta",
+    "nav",
+    "noscript",
+    "ol",
+    "p",
+    "pre",
+    "script",
+    "section",
+    "style",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "ul",
+];
+
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+// elements whose contents are copied exactly, whitespace and all
+pub const RAW_ELEMENTS: [&str; 4] = ["pre", "script", "style", "textarea"];
+
+const INDENT: &str = "  ";
+
+#[derive(Debug)]
+enum Node {
+    Element {
+        name: String,
+        open: String,
+        children: Vec<Node>,
+        close: Option<String>,
+    },
+    Text(String),
+    // comments, doctypes and raw elements, written as they are
+    Verbatim {
+        html: String,
+        block: bool,
+    },
+}
+
+impl Node {
+    fn is_block(&self) -> bool {
+        match self {
+            Node::Element { name, children, .. } => {
+                BLOCK_ELEMENTS.contains(&name.as_str()) || children.iter().any(Node::is_block)
+            }
+            Node::Text(_) => false,
+            Node::Verbatim { block, .. } => *block,
+        }
+    }
+
+    // the node on a single line, with runs of whitespace in text collapsed
+    fn inline(&self, out: &mut String) {
+        match self {
+            Node::Element {
+                open,
+                children,
+                close,
+                ..
+            } => {
+                out.push_str(open);
+                let mut inner = String::new();
+                for child in children {
+                    child.inline(&mut inner);
+                }
+                // whitespace just inside a block changes nothing when it's rendered
+                match BLOCK_ELEMENTS.contains(&self.name()) {
+                    true => out.push_str(inner.trim()),
+                    false => out.push_str(&inner),
+                }
+                out.push_str(close.as_deref().unwrap_or_default());
+            }
+            Node::Text(text) => collapse(text, out),
+            Node::Verbatim { html, .. } => out.push_str(html),
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Node::Element { name, .. } => name,
+            _ => "",
+        }
+    }
+}
+
+fn collapse(text: &str, out: &mut String) {
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            space = true;
+            continue;
+        }
+        if space {
+            out.push(' ');
+            space = false;
+        }
+        out.push(c);
+    }
+    if space {
+        out.push(' ');
+    }
+}
+
+// consistently indent rendered html for sites that want readable, diffable output: block
+// elements get a line each, nested two spaces deeper, unless everything in them fits on one
+pub fn prettify(html: &str) -> String {
+    let mut out = String::with_capacity(html.len() + html.len() / 4);
+    write_nodes(&parse(html), 0, &mut out);
+    out
+}
+
+fn write_nodes(nodes: &[Node], depth: usize, out: &mut String) {
+    let mut line = String::new();
+    for node in nodes {
+        if !node.is_block() {
+            node.inline(&mut line);
+            continue;
+        }
+        write_line(&line, depth, out);
+        line.clear();
+        match node {
+            Node::Element {
+                open,
+                children,
+                close,
+                ..
+            } if children.iter().any(Node::is_block) => {
+                write_line(open, depth, out);
+                write_nodes(children, depth + 1, out);
+                write_line(close.as_deref().unwrap_or_default(), depth, out);
+            }
+            _ => {
+                let mut single = String::new();
+                node.inline(&mut single);
+                write_line(&single, depth, out);
+            }
+        }
+    }
+    write_line(&line, depth, out);
+}
+
+fn write_line(line: &str, depth: usize, out: &mut String) {
+    let line = line.trim();
+    if line.is_empty() {
+        return;
+    }
+    for _ in 0..depth {
+        out.push_str(INDENT);
+    }
+    out.push_str(line);
+    out.push('\n');
+}
+
+// a forgiving tree of the html: unclosed elements end with their parent and stray closing
+// tags are kept as text
+fn parse(html: &str) -> Vec<Node> {
+    // (name, open tag, children) of every element still open
+    let mut stack: Vec<(String, String, Vec<Node>)> = vec![(String::new(), String::new(), vec![])];
+    let mut rest = html;
+    while !rest.is_empty() {
+        let start = match rest.find('<') {
+            Some(0) => 0,
+            Some(start) => {
+                push(&mut stack, Node::Text(rest[..start].to_string()));
+                start
+            }
+            None => {
+                push(&mut stack, Node::Text(rest.to_string()));
+                break;
+            }
+        };
+        rest = &rest[start..];
+
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |e| e + 3);
+            push(&mut stack, verbatim(&rest[..end], false));
+            rest = &rest[end..];
+            continue;
+        }
+        let end = tag_end(rest);
+        let tag = &rest[..end];
+        rest = &rest[end..];
+        if tag.starts_with("<!") || tag.starts_with("<?") {
+            push(&mut stack, verbatim(tag, true));
+            continue;
+        }
+
+        let name = tag_name(tag);
+        if name.is_empty() {
+            push(&mut stack, Node::Text(tag.to_string()));
+        } else if let Some(name) = name.strip_prefix('/') {
+            match stack.iter().rposition(|(open, ..)| open == name) {
+                Some(index) if index > 0 => {
+                    while stack.len() > index + 1 {
+                        close(&mut stack, None);
+                    }
+                    close(&mut stack, Some(tag.to_string()));
+                }
+                _ => push(&mut stack, Node::Text(tag.to_string())),
+            }
+        } else if RAW_ELEMENTS.contains(&name.as_str()) {
+            let closing = format!("</{}", name);
+            let end = rest
+                .to_ascii_lowercase()
+                .find(&closing)
+                .map_or(rest.len(), |e| e + tag_end(&rest[e..]));
+            let raw = format!("{}{}", tag, &rest[..end]);
+            push(&mut stack, verbatim(&raw, name != "textarea"));
+            rest = &rest[end..];
+        } else if VOID_ELEMENTS.contains(&name.as_str()) || tag.ends_with("/>") {
+            push(
+                &mut stack,
+                Node::Element {
+                    name,
+                    open: tag.to_string(),
+                    children: vec![],
+                    close: None,
+                },
+            );
+        } else {
+            stack.push((name, tag.to_string(), vec![]));
+        }
+    }
+    while stack.len() > 1 {
+        close(&mut stack, None);
+    }
+    stack.pop().map(|(_, _, nodes)| nodes).unwrap_or_default()
+}
+
+fn verbatim(html: &str, block: bool) -> Node {
+    Node::Verbatim {
+        html: html.to_string(),
+        block,
+    }
+}
+
+fn push(stack: &mut [(String, String, Vec<Node>)], node: Node) {
+    if let Some((_, _, children)) = stack.last_mut() {
+        children.push(node);
+    }
+}
+
+fn close(stack: &mut Vec<(String, String, Vec<Node>)>, tag: Option<String>) {
+    if let Some((name, open, children)) = stack.pop() {
+        push(
+            stack,
+            Node::Element {
+                name,
+                open,
+                children,
+                close: tag,
+            },
+        );
+    }
+}
+
+// the length of the tag rest starts with, skipping any > inside quoted attribute values
+pub fn tag_end(rest: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in rest.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return i + 1,
+            _ => (),
+        }
+    }
+    rest.len()
+}
+
+// an attribute's value, quoted or not
+pub fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!(" {}=", name))? + name.len() + 2;
+    let value = &tag[start..];
+    match value.chars().next()? {
+        q @ ('"' | '\'') => value[1..].split(q).next(),
+        _ => value.split(|c: char| c.is_whitespace() || c == '>').next(),
+    }
+}
+
+// "div" for <div class="x">, "/div" for </div>
+pub fn tag_name(tag: &str) -> String {
+    let inner = tag.trim_start_matches('<');
+    let (slash, inner) = match inner.strip_prefix('/') {
+        Some(inner) => ("/", inner),
+        None => ("", inner),
+    };
+    let name: String = inner
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+    match name.is_empty() {
+        true => name,
+        false => format!("{}{}", slash, name.to_ascii_lowercase()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indents_blocks_and_keeps_inline_and_raw_content() {
+        let html = "<!DOCTYPE html><html><head><title>Hi</title></head><body>\n\n\
+                    <main><h1>  A <em>big</em>\n   day </h1><ul><li><a href=\"/x?a>b\">x</a></li>\
+                    <li>y</li></ul><pre>keep\n    this</pre><p>one<br>two</p></main></body></html>";
+        assert_eq!(
+            prettify(html),
+            "<!DOCTYPE html>\n<html>\n  <head>\n    <title>Hi</title>\n  </head>\n  <body>\n    \
+             <main>\n      <h1>A <em>big</em> day</h1>\n      <ul>\n        \
+             <li><a href=\"/x?a>b\">x</a></li>\n        <li>y</li>\n      </ul>\n      \
+             <pre>keep\n    this</pre>\n      <p>one<br>two</p>\n    </main>\n  </body>\n</html>\n"
+        );
+        // stray and missing closing tags don't lose any content
+        assert_eq!(
+            prettify("<div><p>a</span></div>"),
+            "<div>\n  <p>a</span>\n</div>\n"
+        );
+    }
+}
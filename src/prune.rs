@@ -0,0 +1,63 @@
+use std::fs;
+use std::path::Path;
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::critical::{used_rules, without_comments, UsedSelectors};
+
+// the `[prune_css]` table: writing it, even empty, prunes stylesheets in production builds
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PruneConfig {
+    /// Classes, ids and elements to keep rules for though no page has them, like ones scripts
+    /// add; a trailing * keeps every name starting with the rest
+    #[serde(default)]
+    pub safelist: Vec<String>,
+}
+
+// the selectors every html page under dest uses
+pub fn used_selectors(dest: &Path, safelist: &[String]) -> Result<UsedSelectors> {
+    let mut used = UsedSelectors::new(safelist);
+    add_pages(dest, &mut used)?;
+    Ok(used)
+}
+
+fn add_pages(dir: &Path, used: &mut UsedSelectors) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            add_pages(&path, used)?;
+        } else if path.extension().is_some_and(|e| e == "html" || e == "htm") {
+            used.add_html(&fs::read_to_string(&path)?);
+        }
+    }
+    Ok(())
+}
+
+// css without the rules that style nothing the site's pages use
+pub fn prune(css: &str, used: &UsedSelectors) -> String {
+    used_rules(&without_comments(css), used)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_rules_no_page_uses() {
+        let css = ".btn { color: red } .btn-lg { padding: 2em } .modal.is-open { display: block }\n\
+                   nav a { color: blue } table { width: 100% } @keyframes spin { to { rotate: 1turn } }\n\
+                   @media print { .no-print { display: none } .btn { color: black } }";
+        let mut used = UsedSelectors::new(&["is-*".to_string(), "modal".to_string()]);
+        used.add_html("<nav><a class=\"btn\" href=\"/\">Home</a></nav>");
+        used.add_html("<main><p>No buttons here</p></main>");
+
+        assert_eq!(
+            prune(css, &used),
+            ".btn{color: red}.modal.is-open{display: block}nav a{color: blue}\
+             @keyframes spin{to { rotate: 1turn }}@media print{.btn{color: black}}"
+        );
+    }
+}
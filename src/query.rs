@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use color_eyre::Result;
+use handlebars::{
+    BlockContext, Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext,
+    RenderErrorReason, Renderable,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+enum QueryError {
+    #[error("Unknown sort {0}, use newest, oldest or title")]
+    UnknownSort(String),
+}
+
+// which posts a template or page asks for; every filter set has to match
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostQuery {
+    // a single post by its front matter id, for pinning it to a page
+    pub id: Option<String>,
+    pub tag: Option<String>,
+    pub section: Option<String>,
+    pub limit: Option<usize>,
+    // newest (the default), oldest or title
+    pub sort: Option<String>,
+}
+
+impl PostQuery {
+    // the matching posts from an index sorted newest first, as the builder keeps it
+    pub fn run<'a>(&self, posts: &'a [Value]) -> Result<Vec<&'a Value>> {
+        let mut found: Vec<&Value> = posts
+            .iter()
+            .filter(|p| match &self.id {
+                Some(id) => p["id"] == *id,
+                None => true,
+            })
+            .filter(|p| match &self.tag {
+                Some(tag) => p["tags"]
+                    .as_array()
+                    .is_some_and(|tags| tags.iter().any(|t| t == tag)),
+                None => true,
+            })
+            .filter(|p| match &self.section {
+                Some(section) => p["section"] == *section,
+                None => true,
+            })
+            .collect();
+        match self.sort.as_deref() {
+            None | Some("newest") => (),
+            Some("oldest") => found.reverse(),
+            Some("title") => found.sort_by_key(|p| p["title"].as_str().unwrap_or_default()),
+            Some(other) => return Err(QueryError::UnknownSort(other.to_string()).into()),
+        }
+        if let Some(limit) = self.limit {
+            found.truncate(limit);
+        }
+        Ok(found)
+    }
+}
+
+// a page of its own built from named queries, e.g. a homepage with the latest essays, recent
+// notes and a pinned intro; the template sees each query's posts under its name
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LandingPage {
+    /// Where the page is written, relative to dest
+    pub path: String,
+    /// The template that renders it
+    pub template: String,
+    pub title: Option<String>,
+    #[serde(default)]
+    pub queries: BTreeMap<String, PostQuery>,
+}
+
+impl LandingPage {
+    // every query's posts, by the query's name
+    pub fn run<'a>(&self, posts: &'a [Value]) -> Result<BTreeMap<&str, Vec<&'a Value>>> {
+        let mut found = BTreeMap::new();
+        for (name, query) in self.queries.iter() {
+            found.insert(name.as_str(), query.run(posts)?);
+        }
+        Ok(found)
+    }
+}
+
+// `{{#posts tag="rust" limit=5}}<a href="{{url}}">{{title}}</a>{{/posts}}` renders its block
+// once per matching post from the index built before any page renders
+#[derive(Debug, Default)]
+pub struct PostsHelper {
+    pub posts: Arc<Vec<Value>>,
+}
+
+impl HelperDef for PostsHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let text = |name: &str| {
+            h.hash_get(name)
+                .and_then(|v| v.value().as_str())
+                .map(String::from)
+        };
+        let query = PostQuery {
+            id: text("id"),
+            tag: text("tag"),
+            section: text("section"),
+            limit: h
+                .hash_get("limit")
+                .and_then(|v| v.value().as_u64())
+                .map(|l| l as usize),
+            sort: text("sort"),
+        };
+        let found = query
+            .run(&self.posts)
+            .map_err(|e| RenderErrorReason::Other(e.to_string()))?;
+
+        let template = match h.template() {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+        let count = found.len();
+        for (index, post) in found.into_iter().enumerate() {
+            let mut block = BlockContext::new();
+            block.set_base_value(post.clone());
+            block.set_local_var("index", json!(index));
+            block.set_local_var("first", json!(index == 0));
+            block.set_local_var("last", json!(index + 1 == count));
+            rc.push_block(block);
+            template.render(r, ctx, rc, out)?;
+            rc.pop_block();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> Vec<Value> {
+        vec![
+            json!({"title": "C", "url": "c.html", "tags": ["rust"], "section": "notes"}),
+            json!({"title": "A", "url": "a.html", "tags": ["cooking"], "section": null}),
+            json!({"title": "B", "url": "b.html", "tags": ["rust"], "section": "essays"}),
+        ]
+    }
+
+    #[test]
+    fn filters_sorts_and_limits() {
+        let posts = index();
+        let titles = |query: PostQuery| -> Vec<String> {
+            query
+                .run(&posts)
+                .unwrap()
+                .iter()
+                .map(|p| p["title"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let rust = PostQuery {
+            tag: Some("rust".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(titles(rust.clone()), vec!["C", "B"]);
+        assert_eq!(
+            titles(PostQuery {
+                sort: Some("oldest".to_string()),
+                limit: Some(1),
+                ..rust
+            }),
+            vec!["B"]
+        );
+        assert_eq!(
+            titles(PostQuery {
+                section: Some("notes".to_string()),
+                ..Default::default()
+            }),
+            vec!["C"]
+        );
+        let bad = PostQuery {
+            sort: Some("random".to_string()),
+            ..Default::default()
+        };
+        assert!(bad.run(&posts).is_err());
+    }
+
+    #[test]
+    fn runs_every_query_of_a_landing_page() -> Result<()> {
+        let page: LandingPage = toml::from_str(
+            r#"
+            path = "index.html"
+            template = "home"
+            [queries.intro]
+            id = "b"
+            [queries.notes]
+            section = "notes"
+            "#,
+        )?;
+        let mut posts = index();
+        posts[2]["id"] = json!("b");
+        let found = page.run(&posts)?;
+        assert_eq!(found["intro"], vec![&posts[2]]);
+        assert_eq!(found["notes"], vec![&posts[0]]);
+        Ok(())
+    }
+
+    #[test]
+    fn renders_its_block_per_post() -> Result<()> {
+        let mut hbs = Handlebars::new();
+        let posts = Arc::new(index());
+        hbs.register_helper("posts", Box::new(PostsHelper { posts }));
+        hbs.register_template_string(
+            "list",
+            "{{#posts sort=\"title\" limit=2}}{{@index}}:{{title}}{{#unless @last}}, {{/unless}}{{/posts}} on {{site}}",
+        )?;
+        assert_eq!(
+            hbs.render("list", &json!({"site": "mine"}))?,
+            "0:A, 1:B on mine"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use color_eyre::Result;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::now::{self, SourceKind};
+
+pub const DEFAULT_CACHE_DIR: &str = ".site-gen-cache";
+
+// the configured cache dir, or the default one
+pub fn cache_dir(configured: Option<&str>) -> PathBuf {
+    PathBuf::from(configured.unwrap_or(DEFAULT_CACHE_DIR))
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteSource {
+    pub url: String,
+
+    /// Seconds a cached response is considered fresh
+    #[serde(default = "default_ttl")]
+    pub ttl: u64,
+
+    /// Normalize the response into the standard "now" item shape
+    pub kind: Option<SourceKind>,
+}
+
+fn default_ttl() -> u64 {
+    3600
+}
+
+#[derive(Debug, Error)]
+enum RemoteError {
+    #[error("Remote source {0} is not cached and cannot be fetched while offline")]
+    NotCached(String),
+
+    #[error("Unable to fetch {0}: {1}")]
+    Fetch(String, String),
+}
+
+// a url without its query or fragment, which may hold a token, for logs and errors
+fn redacted(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(mut parsed) => {
+            parsed.set_query(None);
+            parsed.set_fragment(None);
+            let _ = parsed.set_password(None);
+            parsed.to_string()
+        }
+        Err(_e) => url.split(['?', '#']).next().unwrap_or_default().to_string(),
+    }
+}
+
+// fetch (or load from cache) every configured source, keyed by its config name
+pub fn fetch_all(
+    sources: &BTreeMap<String, RemoteSource>,
+    cache_dir: &Path,
+    offline: bool,
+) -> Result<Value> {
+    let mut remote = Map::new();
+    if sources.is_empty() {
+        return Ok(Value::Object(remote));
+    }
+
+    for (name, source) in sources.iter() {
+        let body = fetch_cached(name, &source.url, source.ttl, cache_dir, offline)?;
+        let value = match source.kind {
+            Some(kind) => now::normalize(kind, &body)?,
+            None => parse_body(&body),
+        };
+        remote.insert(name.to_string(), value);
+    }
+    Ok(Value::Object(remote))
+}
+
+// fetch a url, reusing the cached copy while it is younger than ttl seconds
+pub fn fetch_cached(
+    name: &str,
+    url: &str,
+    ttl: u64,
+    cache_dir: &Path,
+    offline: bool,
+) -> Result<String> {
+    Ok(String::from_utf8(fetch_cached_bytes(
+        name, url, ttl, cache_dir, offline,
+    )?)?)
+}
+
+// fetch_cached for binary responses like images
+pub fn fetch_cached_bytes(
+    name: &str,
+    url: &str,
+    ttl: u64,
+    cache_dir: &Path,
+    offline: bool,
+) -> Result<Vec<u8>> {
+    let cache_dir = cache_dir.join("remote");
+    let cache_file = cache_path(&cache_dir, name);
+    let cached = fs::read(&cache_file).ok();
+
+    if offline {
+        return cached.ok_or_else(|| RemoteError::NotCached(name.to_string()).into());
+    }
+
+    if cached.is_some() && is_fresh(&cache_file, ttl) {
+        debug!("Using cached {} from {:?}", name, cache_file);
+        return Ok(cached.unwrap_or_default());
+    }
+
+    info!("Fetching {} from {}", name, redacted(url));
+    match ureq::get(url).call() {
+        Ok(response) => {
+            let mut body = vec![];
+            response.into_reader().read_to_end(&mut body)?;
+            fs::DirBuilder::new().recursive(true).create(&cache_dir)?;
+            fs::write(&cache_file, &body)?;
+            Ok(body)
+        }
+        // a stale copy is better than a failed build
+        // ureq's errors include the url
+        Err(e) => {
+            let reason = e.to_string().replace(url, &redacted(url));
+            match cached {
+                Some(body) => {
+                    warn!("Unable to fetch {} ({}), using stale cache", name, reason);
+                    Ok(body)
+                }
+                None => Err(RemoteError::Fetch(name.to_string(), reason).into()),
+            }
+        }
+    }
+}
+
+fn cache_path(cache_dir: &Path, name: &str) -> PathBuf {
+    cache_dir.join(format!("{}.cache", name))
+}
+
+fn is_fresh(cache_file: &Path, ttl: u64) -> bool {
+    let modified = match fs::metadata(cache_file).and_then(|m| m.modified()) {
+        Ok(m) => m,
+        Err(_e) => return false,
+    };
+    match SystemTime::now().duration_since(modified) {
+        Ok(age) => age < Duration::from_secs(ttl),
+        Err(_e) => true,
+    }
+}
+
+// JSON responses are exposed as structured data, anything else as a string
+fn parse_body(body: &str) -> Value {
+    serde_json::from_str(body).unwrap_or_else(|_e| Value::String(body.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_json_bodies() {
+        let parsed = parse_body(r#"{"name": "site-gen"}"#);
+        assert_eq!(parsed["name"], "site-gen");
+    }
+
+    #[test]
+    fn keeps_other_bodies_as_strings() {
+        let parsed = parse_body("<rss></rss>");
+        assert_eq!(parsed, Value::String("<rss></rss>".to_string()));
+    }
+
+    #[test]
+    fn redacts_tokens_from_urls() {
+        assert_eq!(
+            redacted("https://webmention.io/api/mentions.jf2?target=x&token=secret"),
+            "https://webmention.io/api/mentions.jf2"
+        );
+        assert_eq!(
+            redacted("https://me:pw@example.com/feed#frag"),
+            "https://me@example.com/feed"
+        );
+    }
+
+    #[test]
+    fn offline_without_cache_is_an_error() {
+        let dir = PathBuf::from("fixtures/does-not-exist");
+        assert!(fetch_cached("missing", "https://example.com", 0, &dir, true).is_err());
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderErrorReason,
+};
+use serde_json::{Map, Value};
+
+use crate::helpers::content_hash;
+
+// `{{{cached "feeds" feeds=feeds}}}` renders a partial with only the hash arguments as its
+// context, so pages that share the same chrome reuse one render instead of redoing it
+#[derive(Debug, Default)]
+pub struct CachedPartial {
+    renders: Mutex<HashMap<String, String>>,
+}
+
+impl HelperDef for CachedPartial {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        _ctx: &'rc Context,
+        _rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let name = h
+            .param(0)
+            .and_then(|p| p.value().as_str())
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("cached", 0))?;
+        let data: Map<String, Value> = h
+            .hash()
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.value().clone()))
+            .collect();
+        let data = Value::Object(data);
+
+        // serde_json keeps object keys sorted, so equal contexts serialize identically
+        let key = format!("{}:{}", name, content_hash(data.to_string().as_bytes()));
+        let cached = self.renders.lock().unwrap().get(&key).cloned();
+        let rendered = match cached {
+            Some(hit) => hit,
+            None => {
+                let rendered = r.render(name, &data)?;
+                self.renders.lock().unwrap().insert(key, rendered.clone());
+                rendered
+            }
+        };
+        out.write(&rendered)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn renders_partials_once_per_context() -> color_eyre::Result<()> {
+        let mut hbs = Handlebars::new();
+        hbs.register_helper("cached", Box::new(CachedPartial::default()));
+        hbs.register_template_string("nav", "<nav>{{site}}</nav>")?;
+        hbs.register_template_string("page", "{{{cached \"nav\" site=site}}} {{title}}")?;
+
+        let first = hbs.render("page", &json!({"site": "a", "title": "one"}))?;
+        let second = hbs.render("page", &json!({"site": "a", "title": "two"}))?;
+        let other = hbs.render("page", &json!({"site": "b", "title": "three"}))?;
+        assert_eq!(first, "<nav>a</nav> one");
+        assert_eq!(second, "<nav>a</nav> two");
+        assert_eq!(other, "<nav>b</nav> three");
+
+        // a known context is served from the cache without rendering again
+        hbs.register_template_string("nav", "<nav>changed</nav>")?;
+        let again = hbs.render("page", &json!({"site": "a", "title": "four"}))?;
+        assert_eq!(again, "<nav>a</nav> four");
+        Ok(())
+    }
+}
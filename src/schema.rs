@@ -0,0 +1,67 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::front_matter::{FieldKind, FIELDS};
+
+// a front matter key of the site's own, e.g. `[fields.series] type = "text"`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomField {
+    #[serde(rename = "type")]
+    pub kind: FieldKind,
+    pub description: Option<String>,
+}
+
+// a JSON Schema of post headers, built from the keys the parser knows, so editors can
+// validate and complete them; unknown keys are flagged as the likely typos they are
+pub fn front_matter(title: &str, fields: &BTreeMap<String, CustomField>) -> Value {
+    let mut properties = Map::new();
+    for (key, kind, description) in FIELDS {
+        properties.insert(key.to_string(), property(kind, Some(description)));
+    }
+    for (key, field) in fields {
+        properties.insert(
+            key.clone(),
+            property(field.kind, field.description.as_deref()),
+        );
+    }
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": format!("{} front matter", title),
+        "type": "object",
+        "properties": properties,
+        "additionalProperties": false,
+    })
+}
+
+fn property(kind: FieldKind, description: Option<&str>) -> Value {
+    let mut property = match kind {
+        FieldKind::Text | FieldKind::List => json!({ "type": "string" }),
+        FieldKind::Date => json!({ "type": "string", "format": "date-time" }),
+        FieldKind::Boolean => json!({ "type": "boolean" }),
+    };
+    if let Some(description) = description {
+        property["description"] = description.into();
+    }
+    property
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_core_and_custom_fields() {
+        let fields: BTreeMap<String, CustomField> =
+            toml::from_str("[series]\ntype = \"list\"\n[pinned]\ntype = \"boolean\"").unwrap();
+        let schema = front_matter("Blog", &fields);
+        let properties = &schema["properties"];
+        assert_eq!(properties["date"]["format"], "date-time");
+        assert_eq!(properties["draft"]["type"], "boolean");
+        assert_eq!(properties["series"], json!({ "type": "string" }));
+        assert_eq!(properties["pinned"], json!({ "type": "boolean" }));
+        assert_eq!(schema["title"], "Blog front matter");
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use truncate_string_at_whitespace::truncate_text;
+
+use crate::builder::FileEntry;
+
+// the `[search]` table: writing it, even empty, turns the index on
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SearchConfig {
+    /// Where the index is written, relative to dest
+    #[serde(default = "default_output")]
+    pub output: String,
+    /// Index each post's whole text rather than its first `length` characters
+    #[serde(default)]
+    pub full_text: bool,
+    #[serde(default = "default_length")]
+    pub length: usize,
+    /// Split the index into a file per first letter of the post titles, with `output` listing
+    /// them, so large archives don't make every search download every post
+    #[serde(default)]
+    pub shard: bool,
+}
+
+fn default_output() -> String {
+    "search-index.json".to_string()
+}
+
+fn default_length() -> usize {
+    300
+}
+
+// one post in the index; `id` is the ref lunr and elasticlunr want, fuse.js ignores it
+#[derive(Debug, Serialize)]
+struct SearchDocument<'a> {
+    id: &'a str,
+    title: &'a str,
+    url: &'a str,
+    tags: &'a [String],
+    text: &'a str,
+}
+
+// a json array of every post, in the order given, for client-side search libraries
+pub fn index<'a>(
+    config: &SearchConfig,
+    entries: impl IntoIterator<Item = &'a FileEntry>,
+) -> Result<String> {
+    let documents: Vec<_> = entries
+        .into_iter()
+        .map(|entry| SearchDocument {
+            id: &entry.url,
+            title: &entry.title,
+            url: &entry.url,
+            tags: &entry.tags,
+            text: match config.full_text {
+                true => entry.raw_text.trim(),
+                false => truncate_text(entry.raw_text.trim(), config.length),
+            },
+        })
+        .collect();
+    Ok(serde_json::to_string(&documents)?)
+}
+
+// the shard a post belongs in: the first letter or digit of its title, or "other"
+fn shard_key(title: &str) -> String {
+    match title.chars().find(|c| c.is_alphanumeric()) {
+        Some(c) if c.is_ascii() => c.to_ascii_lowercase().to_string(),
+        _ => "other".to_string(),
+    }
+}
+
+// the index files to write as (path under dest, json). sharded, search-index.json becomes
+// {"a": "search-index-a.json", ...} beside a search-index-<key>.json per shard
+pub fn render(config: &SearchConfig, entries: &[FileEntry]) -> Result<Vec<(String, String)>> {
+    if !config.shard {
+        return Ok(vec![(config.output.clone(), index(config, entries)?)]);
+    }
+
+    let mut shards: BTreeMap<String, Vec<&FileEntry>> = BTreeMap::new();
+    for entry in entries {
+        shards
+            .entry(shard_key(&entry.title))
+            .or_default()
+            .push(entry);
+    }
+    let output = Path::new(&config.output);
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = output.extension().unwrap_or_default().to_string_lossy();
+    let mut files = vec![];
+    let mut listing = BTreeMap::new();
+    for (key, entries) in shards {
+        let name = format!("{}-{}.{}", stem, key, ext);
+        let path = output.with_file_name(&name).to_string_lossy().to_string();
+        files.push((path, index(config, entries)?));
+        listing.insert(key, name);
+    }
+    files.push((config.output.clone(), serde_json::to_string(&listing)?));
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn indexes_truncated_or_full_text() -> Result<()> {
+        let entry: FileEntry = serde_json::from_value(json!({
+            "id": null,
+            "section": null,
+            "modified": "2024-05-04T10:00:00-07:00",
+            "raw_text": " Rust is a systems language ",
+            "contents": "<p>Rust is a systems language</p>",
+            "tags": ["rust"],
+            "title": "Why Rust",
+            "url": "why-rust.html",
+            "hero_image": null,
+            "share_image": null,
+            "description": null,
+            "lang": null,
+            "dir": null,
+            "content_warning": null,
+            "license": null,
+            "syndicate": [],
+            "canonical": null,
+            "draft": false,
+        }))?;
+        let mut config: SearchConfig = toml::from_str("length = 8")?;
+        assert_eq!(config.output, "search-index.json");
+
+        let entries = [entry];
+        let truncated: Value = serde_json::from_str(&index(&config, &entries)?)?;
+        assert_eq!(
+            truncated,
+            json!([{
+                "id": "why-rust.html",
+                "title": "Why Rust",
+                "url": "why-rust.html",
+                "tags": ["rust"],
+                "text": "Rust is",
+            }])
+        );
+
+        config.full_text = true;
+        let full: Value = serde_json::from_str(&index(&config, &entries)?)?;
+        assert_eq!(full[0]["text"], "Rust is a systems language");
+        Ok(())
+    }
+
+    #[test]
+    fn shards_by_first_letter_of_the_title() -> Result<()> {
+        let entry = |title: &str, url: &str| -> Result<FileEntry> {
+            Ok(serde_json::from_value(json!({
+                "id": null,
+                "section": null,
+                "modified": "2024-05-04T10:00:00-07:00",
+                "raw_text": "text",
+                "contents": "<p>text</p>",
+                "tags": [],
+                "title": title,
+                "url": url,
+                "hero_image": null,
+                "share_image": null,
+                "description": null,
+                "lang": null,
+                "dir": null,
+                "content_warning": null,
+                "license": null,
+                "syndicate": [],
+                "canonical": null,
+                "draft": false,
+            }))?)
+        };
+        let entries = [
+            entry("Why Rust", "why-rust.html")?,
+            entry("\"what\" next", "next.html")?,
+            entry("Über", "uber.html")?,
+        ];
+
+        let config: SearchConfig = toml::from_str("")?;
+        let files = render(&config, &entries)?;
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, "search-index.json");
+
+        let config: SearchConfig = toml::from_str("output = \"search/index.json\"\nshard = true")?;
+        let files: BTreeMap<_, _> = render(&config, &entries)?.into_iter().collect();
+        let listing: Value = serde_json::from_str(&files["search/index.json"])?;
+        assert_eq!(
+            listing,
+            json!({"other": "index-other.json", "w": "index-w.json"})
+        );
+        let w: Value = serde_json::from_str(&files["search/index-w.json"])?;
+        assert_eq!(w.as_array().map(Vec::len), Some(2));
+        let other: Value = serde_json::from_str(&files["search/index-other.json"])?;
+        assert_eq!(other[0]["url"], "uber.html");
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+// how posts in one section (the first directory under src) are presented, overriding the
+// site wide settings, e.g. `[sections.notes] template = "note", entries = 50, feed = false`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SectionConfig {
+    /// The template the section's posts render with instead of entry
+    pub template: Option<String>,
+    /// Posts per page of the section's own listing, written to <section>.html
+    pub entries: Option<u8>,
+    /// The template for the section's listing instead of index
+    pub index_template: Option<String>,
+    /// Whether the section's posts go in the site's RSS and JSON feeds
+    #[serde(default = "default_feed")]
+    pub feed: bool,
+}
+
+fn default_feed() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn sections_keep_the_site_defaults() {
+        let sections: BTreeMap<String, SectionConfig> = toml::from_str(
+            r#"
+            [notes]
+            template = "note"
+            entries = 50
+            feed = false
+            [essays]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(sections["notes"].template.as_deref(), Some("note"));
+        assert!(!sections["notes"].feed);
+        assert!(sections["essays"].feed);
+        assert_eq!(sections["essays"].entries, None);
+        assert!(toml::from_str::<SectionConfig>("entires = 5").is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use color_eyre::Result;
+use log::{info, warn};
+
+pub const DEFAULT_ADDR: &str = "127.0.0.1:8080";
+
+// the version bumps after every rebuild, pages poll it and reload when it moves
+const VERSION_PATH: &str = "/__site-gen/version";
+const RELOAD_SCRIPT: &str = "<script>(function(){var v;setInterval(function(){\
+fetch('/__site-gen/version').then(function(r){return r.text()}).then(function(t){\
+if(v&&t!==v){location.reload()}v=t}).catch(function(){})},1000)})()</script>";
+
+// serve dest on addr from a background thread
+pub fn start(dest: PathBuf, addr: &str, version: Arc<AtomicUsize>) -> Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    info!("Serving {:?} on http://{}", dest, addr);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let dest = dest.clone();
+            let version = version.clone();
+            thread::spawn(move || {
+                if let Err(e) = respond(stream, &dest, &version) {
+                    warn!("Unable to answer request: {}", e);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+fn respond(mut stream: TcpStream, dest: &Path, version: &AtomicUsize) -> Result<()> {
+    let mut buf = [0; 8192];
+    let read = stream.read(&mut buf)?;
+    let request = String::from_utf8_lossy(&buf[..read]);
+    let mut parts = request.lines().next().unwrap_or_default().split(' ');
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or("/");
+
+    if method != "GET" && method != "HEAD" {
+        return send(&mut stream, "405 Method Not Allowed", "text/plain", b"");
+    }
+    let path = target.split(['?', '#']).next().unwrap_or("/");
+    if path == VERSION_PATH {
+        let body = version.load(Ordering::SeqCst).to_string();
+        return send(&mut stream, "200 OK", "text/plain", body.as_bytes());
+    }
+
+    let file = match resolve(dest, path) {
+        Some(f) => f,
+        None => return send(&mut stream, "404 Not Found", "text/plain", b"not found"),
+    };
+    let mut body = fs::read(&file)?;
+    let content_type = content_type(&file);
+    if content_type.starts_with("text/html") {
+        body = inject_reload(&String::from_utf8_lossy(&body)).into_bytes();
+    }
+    let body = if method == "HEAD" { vec![] } else { body };
+    send(&mut stream, "200 OK", content_type, &body)
+}
+
+// previews are never cached, a rebuilt page should show up on the next load
+fn send(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+         Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    Ok(())
+}
+
+// map a request path to a file in dest, never leaving it; directories serve their index.html
+fn resolve(dest: &Path, path: &str) -> Option<PathBuf> {
+    let decoded = percent_decode(path);
+    let relative = Path::new(decoded.trim_start_matches('/'));
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+    let mut file = dest.join(relative);
+    if file.is_dir() {
+        file = file.join("index.html");
+    }
+    file.is_file().then_some(file)
+}
+
+pub fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn content_type(file: &Path) -> &'static str {
+    match file.extension().and_then(|e| e.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css",
+        Some("js") => "text/javascript",
+        Some("json") => "application/json",
+        Some("rss") => "application/atom+xml",
+        Some("xml") => "application/xml",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("woff2") => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
+
+fn inject_reload(html: &str) -> String {
+    match html.rfind("</body>") {
+        Some(i) => format!("{}{}{}", &html[..i], RELOAD_SCRIPT, &html[i..]),
+        None => format!("{}{}", html, RELOAD_SCRIPT),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_paths_inside_dest_only() {
+        let dest = Path::new("fixtures");
+        assert_eq!(
+            resolve(dest, "/data/file%31.md"),
+            Some(PathBuf::from("fixtures/data/file1.md"))
+        );
+        assert_eq!(resolve(dest, "/data"), None);
+        assert_eq!(resolve(dest, "/../Cargo.toml"), None);
+        assert_eq!(resolve(dest, "/data/%2e%2e/../Cargo.toml"), None);
+        assert_eq!(resolve(dest, "/missing.html"), None);
+    }
+
+    #[test]
+    fn injects_the_reload_script_before_body_closes() {
+        let html = inject_reload("<html><body><p>hi</p></body></html>");
+        assert!(html.ends_with(&format!("{}</body></html>", RELOAD_SCRIPT)));
+    }
+}
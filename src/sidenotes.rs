@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+
+// turn footnote-style `[^name]` markers and their `[^name]: text` definitions into tufte
+// style sidenotes that sit in the margin beside the paragraph citing them
+pub fn sidenotes(body: &str) -> String {
+    let mut notes = BTreeMap::new();
+    let mut text = String::with_capacity(body.len());
+    for line in body.split_inclusive('\n') {
+        match definition(line) {
+            Some((name, note)) => {
+                notes.insert(name.to_string(), note.trim().to_string());
+            }
+            None => text.push_str(line),
+        }
+    }
+    if notes.is_empty() {
+        return body.to_string();
+    }
+
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find("[^") {
+        output.push_str(&rest[..start]);
+        let marker = &rest[start..];
+        let note = marker
+            .find(']')
+            .and_then(|end| notes.get(&marker[2..end]).map(|note| (end, note)));
+        match note {
+            Some((end, note)) => {
+                output.push_str(&sidenote(&marker[2..end], note));
+                rest = &marker[end + 1..];
+            }
+            None => {
+                output.push_str("[^");
+                rest = &marker[2..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+// `[^name]: text` at the start of a line
+fn definition(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix("[^")?;
+    let (name, note) = rest.split_once("]:")?;
+    match name.is_empty() || name.contains(char::is_whitespace) {
+        true => None,
+        false => Some((name, note)),
+    }
+}
+
+fn sidenote(name: &str, note: &str) -> String {
+    format!(
+        "<label for=\"sn-{n}\" class=\"margin-toggle sidenote-number\"></label>\
+         <input type=\"checkbox\" id=\"sn-{n}\" class=\"margin-toggle\"/>\
+         <span class=\"sidenote\">{note}</span>",
+        n = name,
+        note = note
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_definitions_beside_their_markers() {
+        let body = "Some claim[^1] and [^x].\n\n[^1]: A *note*.\n";
+        assert_eq!(
+            sidenotes(body),
+            "Some claim<label for=\"sn-1\" class=\"margin-toggle sidenote-number\"></label>\
+             <input type=\"checkbox\" id=\"sn-1\" class=\"margin-toggle\"/>\
+             <span class=\"sidenote\">A *note*.</span> and [^x].\n\n"
+        );
+        assert_eq!(sidenotes("no notes [^here]\n"), "no notes [^here]\n");
+    }
+}
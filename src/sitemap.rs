@@ -0,0 +1,125 @@
+use quick_xml::escape::escape;
+
+// the limits from the sitemaps protocol, per file
+pub const MAX_URLS: usize = 50_000;
+pub const MAX_BYTES: usize = 50 * 1024 * 1024;
+
+const SITEMAP_FILE: &str = "sitemap.xml";
+const HEADER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+
+#[derive(Debug)]
+pub struct SitemapUrl {
+    pub loc: String,
+    pub lastmod: Option<String>,
+}
+
+// the sitemap files to write as (file name, xml); a site that outgrows one file is split
+// into sitemap-1.xml, sitemap-2.xml, ... with sitemap.xml becoming an index of them
+pub fn render(
+    urls: &[SitemapUrl],
+    site_url: &str,
+    max_urls: usize,
+    max_bytes: usize,
+) -> Vec<(String, String)> {
+    let open = format!(
+        "{}<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+        HEADER
+    );
+    let close = "</urlset>\n";
+
+    let mut shards: Vec<String> = vec![];
+    let mut current = open.clone();
+    let mut count = 0;
+    for url in urls {
+        let entry = url_entry(url);
+        if count > 0 && (count == max_urls || current.len() + entry.len() + close.len() > max_bytes)
+        {
+            shards.push(current + close);
+            current = open.clone();
+            count = 0;
+        }
+        current.push_str(&entry);
+        count += 1;
+    }
+    shards.push(current + close);
+
+    if shards.len() == 1 {
+        return vec![(SITEMAP_FILE.to_string(), shards.remove(0))];
+    }
+
+    let mut files: Vec<_> = shards
+        .into_iter()
+        .enumerate()
+        .map(|(i, xml)| (format!("sitemap-{}.xml", i + 1), xml))
+        .collect();
+    let mut index = format!(
+        "{}<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+        HEADER
+    );
+    for (name, _xml) in files.iter() {
+        index.push_str(&format!(
+            "  <sitemap><loc>{}</loc></sitemap>\n",
+            escape(format!("{}/{}", site_url, name).as_str())
+        ));
+    }
+    index.push_str("</sitemapindex>\n");
+    files.push((SITEMAP_FILE.to_string(), index));
+    files
+}
+
+fn url_entry(url: &SitemapUrl) -> String {
+    match &url.lastmod {
+        Some(lastmod) => format!(
+            "  <url><loc>{}</loc><lastmod>{}</lastmod></url>\n",
+            escape(url.loc.as_str()),
+            lastmod
+        ),
+        None => format!("  <url><loc>{}</loc></url>\n", escape(url.loc.as_str())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(n: usize) -> Vec<SitemapUrl> {
+        (0..n)
+            .map(|i| SitemapUrl {
+                loc: format!("https://example.com/{}.html?a&b", i),
+                lastmod: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn small_sites_get_one_file() {
+        let files = render(&urls(3), "https://example.com", MAX_URLS, MAX_BYTES);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, "sitemap.xml");
+        assert!(files[0]
+            .1
+            .contains("<loc>https://example.com/2.html?a&amp;b</loc>"));
+    }
+
+    #[test]
+    fn shards_by_count_and_size() {
+        let files = render(&urls(5), "https://example.com", 2, MAX_BYTES);
+        let names: Vec<_> = files.iter().map(|f| f.0.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "sitemap-1.xml",
+                "sitemap-2.xml",
+                "sitemap-3.xml",
+                "sitemap.xml"
+            ]
+        );
+        assert!(files[3]
+            .1
+            .contains("<sitemap><loc>https://example.com/sitemap-3.xml</loc></sitemap>"));
+
+        let files = render(&urls(5), "https://example.com", MAX_URLS, 300);
+        assert!(files.len() > 2);
+        assert!(files.iter().all(|f| f.1.len() <= 300));
+    }
+}
@@ -0,0 +1,132 @@
+use color_eyre::Result;
+use thiserror::Error;
+
+use crate::config::parse_opts;
+use crate::Opt;
+
+// another site in the workspace whose posts may be syndicated into this one
+#[derive(Debug, Clone)]
+pub struct Peer {
+    pub name: String,
+    pub src: String,
+    pub url: String,
+}
+
+#[derive(Debug, Error)]
+enum SiteError {
+    #[error("No site named {0} in the config's [sites] table")]
+    UnknownSite(String),
+}
+
+// expand a config into one set of options per site, layering each [sites.<name>]
+// table over the shared top-level keys (templates, author, etc)
+pub fn resolve(mut config: toml::Table, only: &[String]) -> Result<Vec<(Option<String>, Opt)>> {
+    let sites = match config.remove("sites") {
+        Some(toml::Value::Table(sites)) => sites,
+        _ => {
+            if let Some(name) = only.first() {
+                return Err(SiteError::UnknownSite(name.to_string()).into());
+            }
+            return Ok(vec![(None, parse_opts(config)?)]);
+        }
+    };
+
+    if let Some(name) = only.iter().find(|name| !sites.contains_key(name.as_str())) {
+        return Err(SiteError::UnknownSite(name.to_string()).into());
+    }
+
+    let mut resolved = vec![];
+    for (name, site) in sites {
+        if !only.is_empty() && !only.contains(&name) {
+            continue;
+        }
+        let mut merged = config.clone();
+        if let toml::Value::Table(site) = site {
+            merged.extend(site);
+        }
+        resolved.push((Some(name), parse_opts(merged)?));
+    }
+    Ok(resolved)
+}
+
+pub fn peers(config: &toml::Table) -> Result<Vec<Peer>> {
+    Ok(resolve(config.clone(), &[])?
+        .into_iter()
+        .filter_map(|(name, opts)| {
+            Some(Peer {
+                name: name?,
+                src: opts.src,
+                url: opts.url?,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        template_dir = "theme"
+        entries = 10
+        title = "shared"
+
+        [sites.blog]
+        src = "blog"
+        dest = "public/blog"
+        url = "https://blog.example.com"
+
+        [sites.photos]
+        src = "photos"
+        dest = "public/photos"
+        url = "https://photos.example.com"
+        title = "photos"
+    "#;
+
+    #[test]
+    fn layers_sites_over_shared_config() -> Result<()> {
+        let sites = resolve(toml::from_str(CONFIG)?, &[])?;
+        assert_eq!(sites.len(), 2);
+        let (name, blog) = &sites[0];
+        assert_eq!(name.as_deref(), Some("blog"));
+        assert_eq!(blog.template_dir, "theme");
+        assert_eq!(blog.title, "shared");
+        assert_eq!(sites[1].1.title, "photos");
+        Ok(())
+    }
+
+    #[test]
+    fn builds_only_requested_sites() -> Result<()> {
+        let sites = resolve(toml::from_str(CONFIG)?, &["photos".to_string()])?;
+        assert_eq!(sites.len(), 1);
+        assert_eq!(sites[0].1.src, "photos");
+        Ok(())
+    }
+
+    #[test]
+    fn lists_every_site_as_a_peer() -> Result<()> {
+        let peers = peers(&toml::from_str(CONFIG)?)?;
+        let names: Vec<_> = peers.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["blog", "photos"]);
+        assert_eq!(peers[1].url, "https://photos.example.com");
+        Ok(())
+    }
+
+    #[test]
+    fn minimal_configs_use_defaults() -> Result<()> {
+        let config = "title = \"blog\"\nurl = \"https://example.com\"";
+        let sites = resolve(toml::from_str(config)?, &[])?;
+        let (_name, opts) = &sites[0];
+        assert_eq!(opts.entries, 10);
+        assert_eq!(opts.template_dir, "templates");
+        assert_eq!(opts.src, "content");
+        assert_eq!(opts.dest, "public");
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_unknown_sites() -> Result<()> {
+        assert!(resolve(toml::from_str(CONFIG)?, &["nope".to_string()]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use color_eyre::Result;
+use quick_xml::escape::escape;
+use serde::{Deserialize, Serialize};
+
+// every url each post has been published at, keyed by its front matter id; the last url of
+// each list is where the post lives now
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SlugHistory {
+    #[serde(default)]
+    pub posts: BTreeMap<String, Vec<String>>,
+}
+
+impl SlugHistory {
+    pub fn load(path: &Path) -> Result<SlugHistory> {
+        match fs::read_to_string(path) {
+            Ok(data) => Ok(toml::from_str(&data)?),
+            Err(_e) => Ok(SlugHistory::default()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    // note where a post lives now, returning whether it moved or is new
+    pub fn record(&mut self, id: &str, url: &str) -> bool {
+        let urls = self.posts.entry(id.to_string()).or_default();
+        if urls.last().map(|u| u.as_str()) == Some(url) {
+            return false;
+        }
+        urls.retain(|u| u != url);
+        urls.push(url.to_string());
+        true
+    }
+
+    // (old url, last url) for every url a post has moved away from, unless a post built now
+    // has that url. a post that's gone still redirects to where it last was, which may
+    // itself redirect on
+    pub fn redirects(&self, live: &BTreeSet<&str>) -> Vec<(&str, &str)> {
+        let mut redirects = vec![];
+        for urls in self.posts.values() {
+            if let Some((now, before)) = urls.split_last() {
+                for old in before.iter().filter(|u| !live.contains(u.as_str())) {
+                    redirects.push((old.as_str(), now.as_str()));
+                }
+            }
+        }
+        redirects
+    }
+}
+
+// a redirect that led to another redirect, now pointed straight at where the last one goes,
+// or one caught in a loop of them that goes nowhere
+#[derive(Debug, PartialEq)]
+pub enum Hops<'a> {
+    Flattened(Vec<&'a str>),
+    Loop(Vec<&'a str>),
+}
+
+impl fmt::Display for Hops<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Hops::Flattened(urls) => write!(
+                f,
+                "Redirect {} now goes straight to {}",
+                urls.join(" -> "),
+                urls.last().unwrap_or(&"")
+            ),
+            Hops::Loop(urls) => write!(
+                f,
+                "Redirect {} loops, so it isn't written",
+                urls.join(" -> ")
+            ),
+        }
+    }
+}
+
+// redirects (old, new) with every chain of them flattened to end at a url that isn't itself
+// redirected, and any caught in a loop left out, with what had to change
+pub fn flatten<'a>(redirects: Vec<(&'a str, &'a str)>) -> (Vec<(&'a str, &'a str)>, Vec<Hops<'a>>) {
+    let next: BTreeMap<&str, &str> = redirects.iter().copied().collect();
+    let mut flattened = vec![];
+    let mut fixed = vec![];
+    for (old, new) in redirects {
+        let mut hops = vec![old, new];
+        let mut at = new;
+        while let Some(to) = next.get(at) {
+            let looped = hops.contains(to);
+            hops.push(to);
+            if looped {
+                break;
+            }
+            at = to;
+        }
+        match (hops.len(), hops.last() == Some(&at)) {
+            (2, _) => flattened.push((old, new)),
+            (_, true) => {
+                flattened.push((old, at));
+                fixed.push(Hops::Flattened(hops));
+            }
+            (_, false) => fixed.push(Hops::Loop(hops)),
+        }
+    }
+    (flattened, fixed)
+}
+
+// a page that sends readers and crawlers on to where a post lives now
+pub fn redirect_page(to: &str) -> String {
+    let to = escape(to);
+    format!(
+        "<!DOCTYPE html>\n<html>\n  <head>\n    <meta charset=\"utf-8\">\n    \
+         <meta http-equiv=\"refresh\" content=\"0; url={to}\">\n    \
+         <link rel=\"canonical\" href=\"{to}\">\n    <title>Moved</title>\n  </head>\n  \
+         <body><a href=\"{to}\">This post has moved.</a></body>\n</html>\n",
+        to = to
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redirects_old_urls_to_the_current_one() {
+        let mut history = SlugHistory::default();
+        assert!(history.record("a", "first.html"));
+        assert!(!history.record("a", "first.html"));
+        assert!(history
+            .redirects(&BTreeSet::from(["first.html"]))
+            .is_empty());
+
+        assert!(history.record("a", "second.html"));
+        assert!(history.record("b", "other.html"));
+        let live = BTreeSet::from(["second.html", "other.html"]);
+        assert_eq!(
+            history.redirects(&live),
+            vec![("first.html", "second.html")]
+        );
+
+        // moving back drops the loop, and a url another post now uses isn't redirected
+        assert!(history.record("a", "first.html"));
+        assert_eq!(history.posts["a"], vec!["second.html", "first.html"]);
+        assert!(history.record("b", "second.html"));
+        let live = BTreeSet::from(["first.html", "second.html"]);
+        assert_eq!(
+            history.redirects(&live),
+            vec![("other.html", "second.html")]
+        );
+
+        let saved = toml::to_string_pretty(&history).unwrap();
+        let loaded: SlugHistory = toml::from_str(&saved).unwrap();
+        assert_eq!(loaded.posts, history.posts);
+    }
+
+    #[test]
+    fn flattens_chains_and_drops_loops() {
+        let (redirects, fixed) = flatten(vec![
+            ("old.html", "older.html"),
+            ("older.html", "current.html"),
+            ("a.html", "b.html"),
+            ("b.html", "a.html"),
+            ("moved.html", "here.html"),
+        ]);
+        assert_eq!(
+            redirects,
+            vec![
+                ("old.html", "current.html"),
+                ("older.html", "current.html"),
+                ("moved.html", "here.html"),
+            ]
+        );
+        assert_eq!(
+            fixed,
+            vec![
+                Hops::Flattened(vec!["old.html", "older.html", "current.html"]),
+                Hops::Loop(vec!["a.html", "b.html", "a.html"]),
+                Hops::Loop(vec!["b.html", "a.html", "b.html"]),
+            ]
+        );
+        assert_eq!(
+            fixed[0].to_string(),
+            "Redirect old.html -> older.html -> current.html now goes straight to current.html"
+        );
+    }
+}
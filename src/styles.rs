@@ -0,0 +1,73 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use color_eyre::Result;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+enum StylesError {
+    #[cfg(feature = "sass")]
+    #[error("Unable to compile {0:?}: {1}")]
+    Compile(PathBuf, String),
+
+    #[cfg(not(feature = "sass"))]
+    #[error("Can't compile {0:?}, site-gen was built without the sass feature")]
+    NoSass(PathBuf),
+}
+
+// every stylesheet under dir to compile, relative to it; partials (_name.scss) are only
+// ever imported by the others
+pub fn stylesheets(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut sheets = vec![];
+    collect(dir, Path::new(""), &mut sheets)?;
+    sheets.sort();
+    Ok(sheets)
+}
+
+fn collect(dir: &Path, relative: &Path, sheets: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir.join(relative))? {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            collect(dir, &path, sheets)?;
+            continue;
+        }
+        let is_sheet = path.extension().is_some_and(|e| e == "scss" || e == "sass");
+        if is_sheet && !entry.file_name().to_string_lossy().starts_with('_') {
+            sheets.push(path);
+        }
+    }
+    Ok(())
+}
+
+// a stylesheet compiled to css
+#[cfg(feature = "sass")]
+pub fn compile(file: &Path) -> Result<String> {
+    grass::from_path(file, &grass::Options::default())
+        .map_err(|e| StylesError::Compile(file.to_owned(), e.to_string()).into())
+}
+
+#[cfg(not(feature = "sass"))]
+pub fn compile(file: &Path) -> Result<String> {
+    Err(StylesError::NoSass(file.to_owned()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_stylesheets_but_not_partials() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("site-gen-styles-{}", std::process::id()));
+        fs::create_dir_all(dir.join("pages"))?;
+        for file in ["main.scss", "_vars.scss", "pages/post.sass", "notes.txt"] {
+            fs::write(dir.join(file), "")?;
+        }
+        assert_eq!(
+            stylesheets(&dir)?,
+            vec![PathBuf::from("main.scss"), PathBuf::from("pages/post.sass")]
+        );
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
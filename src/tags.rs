@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use color_eyre::Result;
+use serde_json::json;
+
+use crate::front_matter::rewrite_tags;
+use crate::helpers::get_posts;
+
+// why two tags look like the same thing
+#[derive(Debug, PartialEq)]
+pub enum Similarity {
+    Case,
+    Plural,
+    Spelling,
+}
+
+// list every tag with how many posts use it, most used first
+pub fn report(counts: &BTreeMap<String, usize>, as_json: bool) -> Result<String> {
+    let mut tags: Vec<_> = counts.iter().collect();
+    tags.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+    if as_json {
+        let tags: Vec<_> = tags
+            .iter()
+            .map(|(tag, count)| json!({ "tag": tag, "count": count }))
+            .collect();
+        return Ok(serde_json::to_string_pretty(&tags)?);
+    }
+
+    let width = tags.first().map(|t| t.1.to_string().len()).unwrap_or(1);
+    Ok(tags
+        .iter()
+        .map(|(tag, count)| format!("{:>width$} {}", count, tag, width = width))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+// pairs of tags that probably mean the same thing
+pub fn near_duplicates(tags: &[&String]) -> Vec<(String, String, Similarity)> {
+    let mut pairs = vec![];
+    for (i, a) in tags.iter().enumerate() {
+        for b in tags[i + 1..].iter() {
+            if let Some(similarity) = compare(a, b) {
+                pairs.push((a.to_string(), b.to_string(), similarity));
+            }
+        }
+    }
+    pairs
+}
+
+fn compare(a: &str, b: &str) -> Option<Similarity> {
+    let (a, b) = (a.to_lowercase(), b.to_lowercase());
+    if a == b {
+        return Some(Similarity::Case);
+    }
+    if is_plural_of(&a, &b) || is_plural_of(&b, &a) {
+        return Some(Similarity::Plural);
+    }
+    // short tags like "go" and "js" are too close to everything to compare this way
+    if a.len().min(b.len()) >= 4 && strsim::levenshtein(&a, &b) <= 1 {
+        return Some(Similarity::Spelling);
+    }
+    None
+}
+
+fn is_plural_of(plural: &str, singular: &str) -> bool {
+    let stem = singular.strip_suffix('y').map(|s| format!("{}ies", s));
+    plural == format!("{}s", singular)
+        || plural == format!("{}es", singular)
+        || stem.as_deref() == Some(plural)
+}
+
+// apply the alias map to every post in src, returning the files that changed
+pub fn fix(src: &Path, aliases: &BTreeMap<String, String>) -> Result<Vec<PathBuf>> {
+    let mut changed = vec![];
+    if aliases.is_empty() {
+        return Ok(changed);
+    }
+    for file in get_posts(src)? {
+        if let Some(updated) = rewrite_tags(&fs::read_to_string(&file)?, aliases) {
+            fs::write(&file, updated)?;
+            changed.push(file);
+        }
+    }
+    Ok(changed)
+}
+
+pub fn duplicates_report(counts: &BTreeMap<String, usize>) -> String {
+    let tags: Vec<_> = counts.keys().collect();
+    let pairs = near_duplicates(&tags);
+    if pairs.is_empty() {
+        return "No near-duplicate tags found".to_string();
+    }
+    pairs
+        .iter()
+        .map(|(a, b, similarity)| {
+            let reason = match similarity {
+                Similarity::Case => "differ only by case",
+                Similarity::Plural => "singular and plural",
+                Similarity::Spelling => "spelled almost the same",
+            };
+            format!("{} ({}) / {} ({}): {}", a, counts[a], b, counts[b], reason)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts() -> BTreeMap<String, usize> {
+        BTreeMap::from([
+            ("cooking".to_string(), 2),
+            ("rust".to_string(), 12),
+            ("bread".to_string(), 2),
+        ])
+    }
+
+    #[test]
+    fn lists_most_used_first() -> Result<()> {
+        assert_eq!(report(&counts(), false)?, "12 rust\n 2 bread\n 2 cooking");
+        Ok(())
+    }
+
+    #[test]
+    fn finds_near_duplicates() {
+        let tags: Vec<String> = [
+            "Rust", "rust", "recipe", "recipes", "berry", "berries", "bread", "breads", "cooking",
+            "cookng", "go", "js",
+        ]
+        .iter()
+        .map(|t| t.to_string())
+        .collect();
+        let tags: Vec<_> = tags.iter().collect();
+        let pairs = near_duplicates(&tags);
+        assert!(pairs.contains(&("Rust".to_string(), "rust".to_string(), Similarity::Case)));
+        assert!(pairs.contains(&(
+            "recipe".to_string(),
+            "recipes".to_string(),
+            Similarity::Plural
+        )));
+        assert!(pairs.contains(&(
+            "berry".to_string(),
+            "berries".to_string(),
+            Similarity::Plural
+        )));
+        assert!(pairs.contains(&(
+            "cooking".to_string(),
+            "cookng".to_string(),
+            Similarity::Spelling
+        )));
+        assert!(!pairs.iter().any(|(a, _b, _s)| a == "go"));
+    }
+
+    #[test]
+    fn lists_as_json() -> Result<()> {
+        let parsed: serde_json::Value = serde_json::from_str(&report(&counts(), true)?)?;
+        assert_eq!(parsed[0]["tag"], "rust");
+        assert_eq!(parsed[0]["count"], 12);
+        Ok(())
+    }
+}
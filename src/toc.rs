@@ -0,0 +1,121 @@
+use comrak::nodes::{AstNode, NodeCode, NodeValue};
+use comrak::Anchorizer;
+use serde::{Deserialize, Serialize};
+
+/// A heading in a post's table of contents, with the headings under it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TocEntry {
+    pub text: String,
+    /// 1 for an h1, through 6
+    pub level: u8,
+    /// The heading's id, to link to as #anchor
+    pub anchor: String,
+    pub children: Vec<TocEntry>,
+}
+
+// the headings of a parsed post nested under the one before them of a higher level, with the
+// ids comrak's header_ids gives them when rendering with prefix
+pub fn table_of_contents<'a>(root: &'a AstNode<'a>, prefix: &str) -> Vec<TocEntry> {
+    // a fresh one numbers repeated headings the same way rendering does
+    let mut anchorizer = Anchorizer::new();
+    let mut toc = vec![];
+    for node in root.descendants() {
+        let level = match &node.data.borrow().value {
+            NodeValue::Heading(heading) => heading.level,
+            _ => continue,
+        };
+        let mut text = String::new();
+        collect_text(node, &mut text);
+        let anchor = format!("{}{}", prefix, anchorizer.anchorize(text.clone()));
+        insert(
+            &mut toc,
+            TocEntry {
+                text,
+                level,
+                anchor,
+                children: vec![],
+            },
+        );
+    }
+    toc
+}
+
+// a heading's text the way comrak reads it for its id
+fn collect_text<'a>(node: &'a AstNode<'a>, text: &mut String) {
+    match &node.data.borrow().value {
+        NodeValue::Text(literal) | NodeValue::Code(NodeCode { literal, .. }) => {
+            text.push_str(literal)
+        }
+        NodeValue::LineBreak | NodeValue::SoftBreak => text.push(' '),
+        _ => {
+            for child in node.children() {
+                collect_text(child, text);
+            }
+        }
+    }
+}
+
+// whether templates show a table of contents: toc in any {{...}}, like {{#each toc}}
+pub fn used_by(templates: &str) -> bool {
+    templates
+        .split("{{")
+        .skip(1)
+        .filter_map(|rest| rest.split_once("}}"))
+        .any(|(expression, _rest)| {
+            expression
+                .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .any(|word| word == "toc")
+        })
+}
+
+fn insert(entries: &mut Vec<TocEntry>, entry: TocEntry) {
+    match entries.last_mut() {
+        Some(last) if last.level < entry.level => insert(&mut last.children, entry),
+        _ => entries.push(entry),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use comrak::{parse_document, Arena, ComrakOptions};
+
+    use super::*;
+
+    #[test]
+    fn nests_headings_under_the_one_before() {
+        let markdown = "# Setup\n\n## Installing `site-gen`\n\ntext\n\n### On Linux\n\n\
+                        ## Installing `site-gen`\n\n# Usage\n";
+        let arena = Arena::new();
+        let root = parse_document(&arena, markdown, &ComrakOptions::default());
+        let toc = table_of_contents(root, "");
+
+        let outline: Vec<_> = toc
+            .iter()
+            .map(|e| {
+                let children: Vec<_> = e.children.iter().map(|c| c.anchor.as_str()).collect();
+                (e.level, e.text.as_str(), e.anchor.as_str(), children)
+            })
+            .collect();
+        assert_eq!(
+            outline,
+            vec![
+                (
+                    1,
+                    "Setup",
+                    "setup",
+                    vec!["installing-site-gen", "installing-site-gen-1"]
+                ),
+                (1, "Usage", "usage", vec![]),
+            ]
+        );
+        assert_eq!(toc[0].children[0].children[0].anchor, "on-linux");
+        assert_eq!(toc[0].children[0].text, "Installing site-gen");
+    }
+
+    #[test]
+    fn finds_templates_that_use_the_toc() {
+        assert!(used_by("<nav>{{#each toc}}{{text}}{{/each}}</nav>"));
+        assert!(used_by("{{> contents entries=../toc}}"));
+        assert!(!used_by("<p>table of contents: toc</p>{{title}}{{tocs}}"));
+    }
+}
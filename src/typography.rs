@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+use crate::helpers::map_text;
+
+const NBSP: &str = "&nbsp;";
+
+// one letter words that can't end a line in languages whose typesetting rules say so
+fn default_short_words(lang: &str) -> &'static [&'static str] {
+    match lang {
+        "cs" | "sk" => &["a", "i", "k", "o", "s", "u", "v", "z"],
+        "pl" => &["a", "i", "o", "u", "w", "z"],
+        _ => &[],
+    }
+}
+
+// keep the last two words of every heading together and short words on the same line as the
+// word after them; configured short words for a language replace the built in ones
+pub fn no_widows(html: &str, lang: &str, short_words: &BTreeMap<String, Vec<String>>) -> String {
+    let primary = lang.split(['-', '_']).next().unwrap_or(lang).to_lowercase();
+    let words: Vec<String> = match short_words.get(&primary) {
+        Some(words) => words.iter().map(|w| w.to_lowercase()).collect(),
+        None => default_short_words(&primary)
+            .iter()
+            .map(|w| w.to_string())
+            .collect(),
+    };
+    let html = match words.is_empty() {
+        true => html.to_string(),
+        false => map_text(html, |text| bind_short_words(text, &words)),
+    };
+    bind_heading_ends(&html)
+}
+
+fn bind_short_words(text: &str, words: &[String]) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut pieces = text.split(' ').peekable();
+    while let Some(piece) = pieces.next() {
+        output.push_str(piece);
+        if pieces.peek().is_none() {
+            break;
+        }
+        let word = piece
+            .rsplit(char::is_whitespace)
+            .next()
+            .unwrap_or(piece)
+            .trim_start_matches(|c: char| !c.is_alphanumeric())
+            .to_lowercase();
+        match words.contains(&word) {
+            true => output.push_str(NBSP),
+            false => output.push(' '),
+        }
+    }
+    output
+}
+
+fn bind_heading_ends(html: &str) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = find_heading(rest) {
+        let level = &rest[start + 2..start + 3];
+        let close = format!("</h{}>", level);
+        let (open_end, end) = match (rest[start..].find('>'), rest[start..].find(&close)) {
+            (Some(o), Some(e)) if o < e => (start + o + 1, start + e),
+            _ => break,
+        };
+        output.push_str(&rest[..open_end]);
+        let inner = &rest[open_end..end];
+        match last_text_space(inner) {
+            Some(space) => {
+                output.push_str(&inner[..space]);
+                output.push_str(NBSP);
+                output.push_str(&inner[space + 1..]);
+            }
+            None => output.push_str(inner),
+        }
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+fn find_heading(html: &str) -> Option<usize> {
+    let mut offset = 0;
+    while let Some(i) = html[offset..].find("<h") {
+        let at = offset + i;
+        let bytes = html.as_bytes();
+        let is_heading = matches!(bytes.get(at + 2), Some(b'1'..=b'6'))
+            && matches!(bytes.get(at + 3), Some(b'>' | b' '));
+        if is_heading {
+            return Some(at);
+        }
+        offset = at + 2;
+    }
+    None
+}
+
+// the last space in html that isn't inside a tag
+fn last_text_space(html: &str) -> Option<usize> {
+    let mut in_tag = false;
+    let mut last = None;
+    for (i, c) in html.char_indices() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            ' ' if !in_tag => last = Some(i),
+            _ => (),
+        }
+    }
+    last
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binds_heading_ends_and_short_words() {
+        let none = BTreeMap::new();
+        assert_eq!(
+            no_widows(
+                "<h2 id=\"x\">A <em>long</em> title</h2><p>a b</p>",
+                "en",
+                &none
+            ),
+            "<h2 id=\"x\">A <em>long</em>&nbsp;title</h2><p>a b</p>"
+        );
+        assert_eq!(
+            no_widows(
+                "<p>Byl v Praze a (v lese) <code>v x</code></p>",
+                "cs-CZ",
+                &none
+            ),
+            "<p>Byl v&nbsp;Praze a&nbsp;(v&nbsp;lese) <code>v x</code></p>"
+        );
+
+        let custom = BTreeMap::from([("en".to_string(), vec!["A".to_string()])]);
+        assert_eq!(
+            no_widows("<p>a cat and a dog</p>", "en", &custom),
+            "<p>a&nbsp;cat and a&nbsp;dog</p>"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use log::info;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+// editors often save in several writes, let them finish before rebuilding
+const SETTLE_TIME: Duration = Duration::from_millis(150);
+
+type Snapshot = BTreeMap<PathBuf, SystemTime>;
+
+// block until a file under one of the paths is added, removed or saved, returning those files
+pub fn wait_for_change(paths: &[PathBuf]) -> Vec<PathBuf> {
+    info!("Watching {:?} for changes", paths);
+    let before = snapshot(paths);
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let after = snapshot(paths);
+        if after != before {
+            thread::sleep(SETTLE_TIME);
+            return changed(&before, &snapshot(paths));
+        }
+    }
+}
+
+// modification times of every file under the paths, missing paths are skipped
+fn snapshot(paths: &[PathBuf]) -> Snapshot {
+    let mut files = Snapshot::new();
+    for path in paths {
+        collect(path, &mut files);
+    }
+    files
+}
+
+fn collect(path: &Path, files: &mut Snapshot) {
+    let meta = match fs::metadata(path) {
+        Ok(m) => m,
+        Err(_e) => return,
+    };
+    if meta.is_dir() {
+        for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+            collect(&entry.path(), files);
+        }
+    } else if let Ok(modified) = meta.modified() {
+        files.insert(path.to_owned(), modified);
+    }
+}
+
+fn changed(before: &Snapshot, after: &Snapshot) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = after
+        .iter()
+        .filter(|(path, modified)| before.get(*path) != Some(modified))
+        .map(|(path, _modified)| path.clone())
+        .collect();
+    files.extend(before.keys().filter(|p| !after.contains_key(*p)).cloned());
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_saved_added_and_removed_files() {
+        let old = SystemTime::UNIX_EPOCH;
+        let new = old + Duration::from_secs(1);
+        let before = Snapshot::from([
+            (PathBuf::from("saved.md"), old),
+            (PathBuf::from("same.md"), old),
+            (PathBuf::from("removed.md"), old),
+        ]);
+        let after = Snapshot::from([
+            (PathBuf::from("added.md"), new),
+            (PathBuf::from("saved.md"), new),
+            (PathBuf::from("same.md"), old),
+        ]);
+        assert_eq!(
+            changed(&before, &after),
+            vec![
+                PathBuf::from("added.md"),
+                PathBuf::from("saved.md"),
+                PathBuf::from("removed.md")
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshots_directories_recursively() {
+        let files = snapshot(&[PathBuf::from("fixtures"), PathBuf::from("missing")]);
+        assert!(files.keys().any(|p| p.starts_with("fixtures/data")));
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::remote::fetch_cached;
+
+const DEFAULT_ENDPOINT: &str = "https://webmention.io/api/mentions.jf2";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebmentionConfig {
+    /// A webmention.io compatible JF2 endpoint
+    pub endpoint: Option<String>,
+    pub token: Option<String>,
+    #[serde(default = "default_ttl")]
+    pub ttl: u64,
+}
+
+fn default_ttl() -> u64 {
+    3600
+}
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct Mention {
+    pub author: String,
+    pub author_url: Option<String>,
+    pub photo: Option<String>,
+    pub url: Option<String>,
+    pub published: Option<String>,
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Mentions {
+    pub likes: Vec<Mention>,
+    pub reposts: Vec<Mention>,
+    pub replies: Vec<Mention>,
+    pub mentions: Vec<Mention>,
+}
+
+// fetch every mention of the site, grouped by the relative url they target
+pub fn fetch(
+    config: &WebmentionConfig,
+    site_url: &url::Url,
+    cache_dir: &Path,
+    offline: bool,
+) -> Result<BTreeMap<String, Mentions>> {
+    let domain = site_url.host_str().unwrap_or_default();
+    let endpoint = config.endpoint.as_deref().unwrap_or(DEFAULT_ENDPOINT);
+    let mut url = format!("{}?domain={}&per-page=1000", endpoint, domain);
+    if let Some(token) = &config.token {
+        url = format!("{}&token={}", url, token);
+    }
+
+    let body = fetch_cached("webmentions", &url, config.ttl, cache_dir, offline)?;
+    Ok(group(&serde_json::from_str(&body)?))
+}
+
+fn group(feed: &Value) -> BTreeMap<String, Mentions> {
+    let mut grouped: BTreeMap<String, Mentions> = BTreeMap::new();
+    let children = match feed["children"].as_array() {
+        Some(c) => c,
+        None => return grouped,
+    };
+
+    for child in children {
+        let target = match child["wm-target"].as_str().and_then(relative_url) {
+            Some(t) => t,
+            None => continue,
+        };
+        let text = |v: &Value| v.as_str().map(String::from);
+        let mention = Mention {
+            author: text(&child["author"]["name"]).unwrap_or("someone".to_string()),
+            author_url: text(&child["author"]["url"]),
+            photo: text(&child["author"]["photo"]).filter(|p| !p.is_empty()),
+            url: text(&child["url"]),
+            published: text(&child["published"]).or_else(|| text(&child["wm-received"])),
+            content: text(&child["content"]["text"]),
+        };
+
+        let mentions = grouped.entry(target).or_default();
+        match child["wm-property"].as_str() {
+            Some("like-of") => mentions.likes.push(mention),
+            Some("repost-of") => mentions.reposts.push(mention),
+            Some("in-reply-to") => mentions.replies.push(mention),
+            _ => mentions.mentions.push(mention),
+        }
+    }
+    grouped
+}
+
+// entries are keyed by their url relative to the site root
+fn relative_url(target: &str) -> Option<String> {
+    let target = url::Url::parse(target).ok()?;
+    Some(target.path().trim_start_matches('/').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn groups_mentions_by_target_and_type() {
+        let feed = json!({
+            "type": "feed",
+            "children": [
+                {"wm-target": "https://example.com/post.html", "wm-property": "like-of",
+                 "author": {"name": "Jane", "url": "https://jane.example"}},
+                {"wm-target": "https://example.com/post.html", "wm-property": "in-reply-to",
+                 "author": {"name": "Sam"}, "content": {"text": "Great!"}},
+                {"wm-target": "https://example.com/other.html", "wm-property": "repost-of",
+                 "author": {"name": "Kim"}},
+            ]
+        });
+        let grouped = group(&feed);
+        assert_eq!(grouped["post.html"].likes[0].author, "Jane");
+        assert_eq!(
+            grouped["post.html"].replies[0].content.as_deref(),
+            Some("Great!")
+        );
+        assert_eq!(grouped["other.html"].reposts.len(), 1);
+    }
+}
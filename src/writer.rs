@@ -0,0 +1,132 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use color_eyre::Result;
+use thiserror::Error;
+
+// pending writes per worker before write() blocks, so rendering can't race far ahead of disk
+const QUEUE_PER_WORKER: usize = 16;
+const MAX_WORKERS: usize = 8;
+
+type Job = (PathBuf, Vec<u8>);
+
+#[derive(Debug, Error)]
+enum WriterError {
+    #[error("{} file(s) couldn't be written:\n{}", .0.len(), .0.join("\n"))]
+    Failed(Vec<String>),
+
+    #[error("The output writer has already finished")]
+    Closed,
+}
+
+// writes output files from a bounded pool of threads, each through a buffered writer
+#[derive(Debug)]
+pub struct OutputWriter {
+    sender: Option<SyncSender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+    errors: Arc<Mutex<Vec<String>>>,
+}
+
+impl OutputWriter {
+    pub fn new() -> OutputWriter {
+        let count = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(MAX_WORKERS);
+        OutputWriter::with_workers(count)
+    }
+
+    pub fn with_workers(count: usize) -> OutputWriter {
+        let count = count.max(1);
+        let (sender, receiver) = mpsc::sync_channel::<Job>(count * QUEUE_PER_WORKER);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let errors = Arc::new(Mutex::new(vec![]));
+        let workers = (0..count)
+            .map(|_| {
+                let receiver = receiver.clone();
+                let errors = errors.clone();
+                thread::spawn(move || work(&receiver, &errors))
+            })
+            .collect();
+        OutputWriter {
+            sender: Some(sender),
+            workers,
+            errors,
+        }
+    }
+
+    // queue a file, blocking only while the queue is full
+    pub fn write(&self, path: PathBuf, data: Vec<u8>) -> Result<()> {
+        let sender = self.sender.as_ref().ok_or(WriterError::Closed)?;
+        sender
+            .send((path, data))
+            .map_err(|_e| WriterError::Closed.into())
+    }
+
+    // wait for every queued write, reporting all the files that failed
+    pub fn finish(&mut self) -> Result<()> {
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+        let errors = std::mem::take(&mut *self.errors.lock().unwrap());
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(WriterError::Failed(errors).into()),
+        }
+    }
+}
+
+impl Drop for OutputWriter {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+fn work(receiver: &Mutex<Receiver<Job>>, errors: &Mutex<Vec<String>>) {
+    loop {
+        // the lock is only held while waiting for the next job, not while writing it
+        let job = receiver.lock().unwrap().recv();
+        let (path, data) = match job {
+            Ok(j) => j,
+            Err(_closed) => return,
+        };
+        let written = File::create(&path).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            out.write_all(&data)?;
+            out.flush()
+        });
+        if let Err(e) = written {
+            errors.lock().unwrap().push(format!("{:?}: {}", path, e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn writes_every_file_and_reports_failures() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("site-gen-writer-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+
+        let mut writer = OutputWriter::with_workers(2);
+        for i in 0..50 {
+            writer.write(dir.join(format!("{}.html", i)), i.to_string().into_bytes())?;
+        }
+        writer.write(dir.join("missing/dir.html"), vec![])?;
+        let err = writer.finish().unwrap_err().to_string();
+        assert!(err.starts_with("1 file(s) couldn't be written"));
+        assert_eq!(fs::read_to_string(dir.join("49.html"))?, "49");
+        assert!(writer.write(dir.join("late.html"), vec![]).is_err());
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
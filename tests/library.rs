@@ -0,0 +1,182 @@
+use std::fs;
+
+use color_eyre::Result;
+use site_gen::{Builder, Config, PageMetadata};
+
+#[test]
+fn builds_a_site_without_the_cli() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("site-gen-library-{}", std::process::id()));
+    fs::create_dir_all(dir.join("src"))?;
+    let post = dir.join("src/hello.md");
+    fs::write(
+        &post,
+        "---\ntitle: Hello\ndate: 2024-05-04T10:00:00-07:00\ntags: greetings\n---\nHi *there*\n",
+    )?;
+    fs::write(
+        dir.join("src/again.md"),
+        "---\ntitle: Hello again\ndate: 2024-05-01T10:00:00-07:00\ntags: greetings\n---\nHi\n",
+    )?;
+
+    let config = Config::from_toml(&format!(
+        "src = {:?}\ndest = {:?}\ntemplate_dir = \"templates\"\nurl = \"https://example.com\"\ntitle = \"Library\"\n",
+        dir.join("src"),
+        dir.join("public"),
+    ))?;
+    let mut builder = Builder::new(config)?;
+    builder.build()?;
+
+    let pages = builder.pages();
+    assert_eq!(pages.len(), 2);
+    assert_eq!(pages[0].url, "hello.html");
+    assert!(pages[0].contents.contains("there"));
+    assert_eq!(pages[0].word_count, 2);
+    assert_eq!(pages[0].reading_time_minutes, 1);
+    assert_eq!(pages[0].related[0].url, "again.html");
+    assert_eq!(pages[0].related[0].shared_tags, 1);
+    assert!(dir.join("public/hello.html").is_file());
+    assert_eq!(PageMetadata::from_file(&post)?.tags, vec!["greetings"]);
+
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn renders_one_file_without_writing() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("site-gen-preview-{}", std::process::id()));
+    fs::create_dir_all(dir.join("src"))?;
+    let post = dir.join("src/draft.md");
+    fs::write(
+        &post,
+        "---\ntitle: Preview me\ndate: 2024-05-04T10:00:00-07:00\ndraft: true\n---\nNot yet\n",
+    )?;
+
+    let config = Config::from_toml(&format!(
+        "src = {:?}\ndest = {:?}\ntemplate_dir = \"templates\"\ntitle = \"Library\"\n",
+        dir.join("src"),
+        dir.join("public"),
+    ))?;
+    let mut builder = Builder::new(config)?;
+    let html = builder.render_file(&post)?;
+
+    assert!(html.contains("Preview me"));
+    assert!(html.contains("Not yet"));
+    assert!(!dir.join("public/draft.html").exists());
+
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn language_pages_advertise_their_own_feeds() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("site-gen-languages-{}", std::process::id()));
+    fs::create_dir_all(dir.join("src"))?;
+    fs::write(
+        dir.join("src/hello.md"),
+        "---\ntitle: Hello\ndate: 2024-05-04T10:00:00-07:00\n---\nHi\n",
+    )?;
+    fs::write(
+        dir.join("src/hello.de.md"),
+        "---\ntitle: Hallo\ndate: 2024-05-04T10:00:00-07:00\n---\nHallo\n",
+    )?;
+
+    let config = Config::from_toml(&format!(
+        "src = {:?}\ndest = {:?}\ntemplate_dir = \"templates\"\nurl = \"https://example.com\"\ntitle = \"Library\"\nlanguages = [\"en\", \"de\"]\n",
+        dir.join("src"),
+        dir.join("public"),
+    ))?;
+    Builder::new(config)?.build()?;
+
+    let german = fs::read_to_string(dir.join("public/de/index.html"))?;
+    assert!(german.contains("https://example.com/de/index.rss"));
+    assert!(german.contains("https://example.com/de/feed.json"));
+    assert!(german.contains("href=\"/de/hello.html\""));
+    let english = fs::read_to_string(dir.join("public/index.html"))?;
+    assert!(!english.contains("/de/index.rss"));
+
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[cfg(feature = "scripting")]
+#[test]
+fn renders_rhai_helpers() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("site-gen-scripting-{}", std::process::id()));
+    fs::create_dir_all(dir.join("src"))?;
+    fs::create_dir_all(dir.join("templates"))?;
+    fs::create_dir_all(dir.join("helpers"))?;
+    let post = dir.join("src/hello.md");
+    fs::write(
+        &post,
+        "---\ntitle: Hello\ndate: 2024-05-04T10:00:00-07:00\n---\nHi\n",
+    )?;
+    fs::write(dir.join("templates/entry.hbs"), "<h1>{{shout title}}</h1>")?;
+    fs::write(dir.join("helpers/shout.rhai"), "params[0].to_upper()")?;
+
+    let config = Config::from_toml(&format!(
+        "src = {:?}\ndest = {:?}\ntemplate_dir = {:?}\nhelper_dir = {:?}\ntitle = \"Library\"\n",
+        dir.join("src"),
+        dir.join("public"),
+        dir.join("templates"),
+        dir.join("helpers"),
+    ))?;
+    let html = Builder::new(config)?.render_file(&post)?;
+    assert!(html.contains("<h1>HELLO</h1>"));
+
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn posts_written_to_the_same_url_fail_the_build() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("site-gen-collide-{}", std::process::id()));
+    for year in ["2023", "2024"] {
+        fs::create_dir_all(dir.join("src").join(year))?;
+        fs::write(
+            dir.join("src").join(year).join("notes.md"),
+            format!(
+                "---\ntitle: Notes {}\ndate: {}-05-04T10:00:00-07:00\n---\nHi\n",
+                year, year
+            ),
+        )?;
+    }
+
+    let config = Config::from_toml(&format!(
+        "src = {:?}\ndest = {:?}\ntemplate_dir = \"templates\"\ntitle = \"Library\"\n",
+        dir.join("src"),
+        dir.join("public"),
+    ))?;
+    let error = Builder::new(config)?.build().unwrap_err().to_string();
+    assert!(error.contains("2023/notes.md"), "{}", error);
+    assert!(error.contains("2024/notes.md"), "{}", error);
+    assert!(error.contains("notes.html"), "{}", error);
+
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn tags_with_the_same_slug_fail_the_build() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("site-gen-tags-{}", std::process::id()));
+    fs::create_dir_all(dir.join("src"))?;
+    for (name, tag) in [("c", "C"), ("cpp", "C++")] {
+        fs::write(
+            dir.join("src").join(format!("{}.md", name)),
+            format!(
+                "---\ntitle: {}\ndate: 2024-05-04T10:00:00-07:00\ntags: {}\n---\nHi\n",
+                name, tag
+            ),
+        )?;
+    }
+
+    let config = Config::from_toml(&format!(
+        "src = {:?}\ndest = {:?}\ntemplate_dir = \"templates\"\nurl = \"https://example.com\"\ntitle = \"Library\"\n",
+        dir.join("src"),
+        dir.join("public"),
+    ))?;
+    let error = Builder::new(config)?.build().unwrap_err().to_string();
+    assert!(error.contains("\"C++\""), "{}", error);
+    assert!(error.contains("tags/c.html"), "{}", error);
+
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}